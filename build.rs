@@ -12,14 +12,76 @@ fn main() {
 
     println!("cargo:rerun-if-env-changed=OTA_BUILD");
     println!("cargo:rerun-if-env-changed=OTA_BASE_URL");
+    println!("cargo:rerun-if-env-changed=OTA_MANIFEST_FILE");
     println!("cargo:rerun-if-env-changed=WIFI_SSID");
     println!("cargo:rerun-if-env-changed=WIFI_PASS");
+    println!("cargo:rerun-if-env-changed=WIFI_COUNTRY");
+    println!("cargo:rerun-if-env-changed=WIFI_CHANNEL");
+    println!("cargo:rerun-if-env-changed=STAGGER_INIT_MS");
     println!("cargo:rerun-if-env-changed=MQTT_HOST");
     println!("cargo:rerun-if-env-changed=MQTT_PORT");
     println!("cargo:rerun-if-env-changed=MQTT_USER");
     println!("cargo:rerun-if-env-changed=MQTT_PASS");
     println!("cargo:rerun-if-env-changed=MQTT_CLIENT_ID");
     println!("cargo:rerun-if-env-changed=MQTT_PREFIX");
+    println!("cargo:rerun-if-env-changed=MQTT_STATE_PREFIX");
+    println!("cargo:rerun-if-env-changed=MQTT_DISCOVERY_PREFIX");
+    println!("cargo:rerun-if-env-changed=MQTT_STATUS_QOS");
+    println!("cargo:rerun-if-env-changed=MQTT_STATUS_RETAIN");
+    println!("cargo:rerun-if-env-changed=MQTT_LWT_QOS");
+    println!("cargo:rerun-if-env-changed=MQTT_LWT_RETAIN");
+    println!("cargo:rerun-if-env-changed=MQTT_KEEPALIVE_S");
+    println!("cargo:rerun-if-env-changed=MQTT_NETWORK_TIMEOUT_S");
+    println!("cargo:rerun-if-env-changed=MQTT_PUBLISH_MODE");
+    println!("cargo:rerun-if-env-changed=MQTT_COMPACT_FORMAT");
+    println!("cargo:rerun-if-env-changed=MQTT_COMPACT_ONLY");
+    println!("cargo:rerun-if-env-changed=MQTT_HEARTBEAT_INTERVALS");
+    println!("cargo:rerun-if-env-changed=MQTT_CHANGE_CO2_PPM");
+    println!("cargo:rerun-if-env-changed=MQTT_CHANGE_TEMP_C");
+    println!("cargo:rerun-if-env-changed=MQTT_CHANGE_HUMIDITY_PCT");
+    println!("cargo:rerun-if-env-changed=MQTT_CHANGE_BATTERY_V");
+    println!("cargo:rerun-if-env-changed=MQTT_ENTITY_CO2");
+    println!("cargo:rerun-if-env-changed=MQTT_ENTITY_HUMIDITY");
+    println!("cargo:rerun-if-env-changed=MQTT_ENTITY_BATTERY");
+    println!("cargo:rerun-if-env-changed=MQTT_PAYLOAD_ONLINE");
+    println!("cargo:rerun-if-env-changed=MQTT_PAYLOAD_OFFLINE");
+    println!("cargo:rerun-if-env-changed=AMBIENT_LIGHT");
+    println!("cargo:rerun-if-env-changed=QUIET_HOURS");
+    println!("cargo:rerun-if-env-changed=THEME");
+    println!("cargo:rerun-if-env-changed=LAYOUT");
+    println!("cargo:rerun-if-env-changed=TEMP_UNIT");
+    println!("cargo:rerun-if-env-changed=CO2_HYSTERESIS_PPM");
+    println!("cargo:rerun-if-env-changed=CO2_GAUGE_MAX_PPM");
+    println!("cargo:rerun-if-env-changed=RELAY_ON_PPM");
+    println!("cargo:rerun-if-env-changed=RELAY_OFF_PPM");
+    println!("cargo:rerun-if-env-changed=RELAY_MIN_DWELL_S");
+    println!("cargo:rerun-if-env-changed=TZ");
+    println!("cargo:rerun-if-env-changed=I2C_BAUD_KHZ");
+    println!("cargo:rerun-if-env-changed=TOUCH_REPORT_FORMAT");
+    println!("cargo:rerun-if-env-changed=SHT_INTERVAL_MS");
+    println!("cargo:rerun-if-env-changed=SHT31_REPEATABILITY");
+    println!("cargo:rerun-if-env-changed=SHT31_CLOCK_STRETCH");
+    println!("cargo:rerun-if-env-changed=SHT31_STRETCH_TIMEOUT_MS");
+    println!("cargo:rerun-if-env-changed=CO2_INTERVAL_MS");
+    println!("cargo:rerun-if-env-changed=CO2_INTERVAL_MIN_MS");
+    println!("cargo:rerun-if-env-changed=CO2_INTERVAL_MAX_MS");
+    println!("cargo:rerun-if-env-changed=MHZ19B_TIMEOUT_MS");
+    println!("cargo:rerun-if-env-changed=MHZ19B_UART");
+    println!("cargo:rerun-if-env-changed=MHZ19B_READ_ATTEMPTS");
+    println!("cargo:rerun-if-env-changed=MHZ19B_DISCARD_FRAMES");
+    println!("cargo:rerun-if-env-changed=MHZ19B_WARMUP_S");
+    println!("cargo:rerun-if-env-changed=MHZ19B_FRAMING");
+    println!("cargo:rerun-if-env-changed=BATTERY_INTERVAL_MS");
+    println!("cargo:rerun-if-env-changed=BATTERY_SAMPLES");
+    println!("cargo:rerun-if-env-changed=BATTERY_CRITICAL_V");
+    println!("cargo:rerun-if-env-changed=BATTERY_OUTLIER_MV");
+    println!("cargo:rerun-if-env-changed=DISPLAY_INTERVAL_MS");
+    println!("cargo:rerun-if-env-changed=SMOOTH_DISPLAY");
+    println!("cargo:rerun-if-env-changed=BACKLIGHT_RAMP_MS");
+    println!("cargo:rerun-if-env-changed=LCD_INVERT");
+    println!("cargo:rerun-if-env-changed=SELFTEST_WITHOUT_BUTTON");
+    println!("cargo:rerun-if-env-changed=EPAPER_INTERVAL_S");
+    println!("cargo:rerun-if-env-changed=LOOP_SLEEP_MS");
 
     let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
     let build_file = manifest_dir.join("scripts").join("build-number.txt");
@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Run the host-side driver tests (tools/driver_tests) with mock I2C/UART.
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+
+# Run from outside the repo so the ESP target/toolchain in .cargo/config.toml
+# and rust-toolchain.toml don't apply to this host build.
+cd /
+cargo test \
+  --manifest-path "${ROOT_DIR}/tools/driver_tests/Cargo.toml" \
+  --target-dir "${ROOT_DIR}/target/driver_tests" \
+  "$@"
@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Render the LCD screens on the host into PNGs (default: ./ui_sim_out).
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+OUT_DIR="$(realpath -m "${1:-${ROOT_DIR}/ui_sim_out}")"
+
+# Run from outside the repo so the ESP target/toolchain in .cargo/config.toml
+# and rust-toolchain.toml don't apply to this host build.
+cd /
+cargo run --release \
+  --manifest-path "${ROOT_DIR}/tools/ui_sim/Cargo.toml" \
+  --target-dir "${ROOT_DIR}/target/ui_sim" \
+  -- "$OUT_DIR"
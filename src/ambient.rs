@@ -0,0 +1,48 @@
+use anyhow::Result;
+use esp_idf_hal::adc::attenuation::DB_11;
+use esp_idf_hal::adc::oneshot::config::AdcChannelConfig;
+use esp_idf_hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+use esp_idf_hal::adc::ADC1;
+use esp_idf_hal::gpio::Gpio3;
+use esp_idf_hal::peripheral::Peripheral;
+
+use crate::battery::calibration_mode;
+
+// LDR divider full-scale (mV) at DB_11 attenuation.
+const AMBIENT_FULL_SCALE_MV: u16 = 3100;
+const AMBIENT_MIN_BRIGHTNESS: u8 = 5;
+const AMBIENT_MAX_BRIGHTNESS: u8 = 100;
+
+pub const AMBIENT_LIGHT_ENABLED: bool = match option_env!("AMBIENT_LIGHT") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+
+// Optional photoresistor on GPIO3, sharing ADC1 with the battery reader.
+pub struct AmbientLight<'d> {
+    channel: AdcChannelDriver<'d, Gpio3, &'d AdcDriver<'d, ADC1>>,
+}
+
+impl<'d> AmbientLight<'d> {
+    pub fn new(adc: &'d AdcDriver<'d, ADC1>, pin: impl Peripheral<P = Gpio3> + 'd) -> Result<Self> {
+        let config = AdcChannelConfig {
+            attenuation: DB_11,
+            calibration: calibration_mode(),
+            ..Default::default()
+        };
+        let channel = AdcChannelDriver::new(adc, pin, &config)?;
+        Ok(Self { channel })
+    }
+
+    pub fn read_mv(&mut self) -> Result<u16> {
+        Ok(self.channel.read()?)
+    }
+
+    // Map the LDR voltage (brighter room -> higher voltage) to backlight percent.
+    pub fn read_brightness(&mut self) -> Result<u8> {
+        let mv = self.read_mv()?.min(AMBIENT_FULL_SCALE_MV) as u32;
+        let span = (AMBIENT_MAX_BRIGHTNESS - AMBIENT_MIN_BRIGHTNESS) as u32;
+        let pct = AMBIENT_MIN_BRIGHTNESS as u32 + mv * span / AMBIENT_FULL_SCALE_MV as u32;
+        Ok(pct as u8)
+    }
+}
@@ -1,35 +1,158 @@
+use std::time::Duration;
+
 use anyhow::Result;
+use log::warn;
 use esp_idf_hal::adc::attenuation::DB_11;
 use esp_idf_hal::adc::oneshot::config::{AdcChannelConfig, Calibration};
 use esp_idf_hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
 use esp_idf_hal::adc::ADC1;
 use esp_idf_hal::gpio::Gpio0;
+use esp_idf_hal::i2c::I2cDriver;
 use esp_idf_hal::peripheral::Peripheral;
 
+use crate::fuel_gauge::Max17048;
+
 const BATTERY_SCALE: f32 = 3.0;
+// ADC samples per reading (1..=32); the median-filtered ones are averaged.
+const BATTERY_SAMPLES_DEFAULT: usize = 8;
+const BATTERY_SAMPLES_ENV: Option<&str> = option_env!("BATTERY_SAMPLES");
+// Samples further than this from the median (at the pin, before scaling) are
+// dropped as spikes.
+const BATTERY_OUTLIER_MV_DEFAULT: u16 = 50;
+const BATTERY_OUTLIER_MV_ENV: Option<&str> = option_env!("BATTERY_OUTLIER_MV");
+// Below this voltage the device shuts down cleanly instead of browning out
+// mid-write; 0 disables the check.
+const BATTERY_CRITICAL_V_DEFAULT: f32 = 3.3;
+const BATTERY_CRITICAL_V_ENV: Option<&str> = option_env!("BATTERY_CRITICAL_V");
+// Readings this low mean no battery (USB power only), not an empty one.
+const BATTERY_ABSENT_V: f32 = 2.5;
+// Consecutive critical readings needed, so one sag under load is ignored.
+const BATTERY_CRITICAL_READS: u8 = 3;
+// After a low battery shutdown, wake this often to check whether it was charged.
+pub const LOW_BATTERY_WAKE_INTERVAL: Duration = Duration::from_secs(1800);
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatteryReading {
+    pub voltage_v: f32,
+    // Only a fuel gauge knows the state of charge.
+    pub soc_pct: Option<u8>,
+}
+
+// Where battery readings come from: a fuel gauge on the I2C bus when one
+// answered at boot, otherwise the ADC divider.
+pub enum BatteryMonitor<'d> {
+    FuelGauge(Max17048),
+    Adc(Battery<'d>),
+}
+
+impl BatteryMonitor<'_> {
+    pub fn has_soc(&self) -> bool {
+        matches!(self, Self::FuelGauge(_))
+    }
+
+    // None on a failed read, like `Battery::read_voltage`.
+    pub fn read(&mut self, i2c: &mut I2cDriver<'_>) -> Option<BatteryReading> {
+        match self {
+            Self::FuelGauge(gauge) => match gauge.read(i2c) {
+                Ok(reading) => Some(BatteryReading {
+                    voltage_v: reading.voltage_v,
+                    soc_pct: Some(reading.soc_pct.round().min(100.0) as u8),
+                }),
+                Err(err) => {
+                    warn!("Fuel gauge read failed: {:?}", err);
+                    None
+                }
+            },
+            Self::Adc(battery) => battery.read_voltage().map(|voltage_v| BatteryReading {
+                voltage_v,
+                soc_pct: None,
+            }),
+        }
+    }
+}
+
+// Decides when the battery is too low to keep running.
+pub struct CriticalBattery {
+    threshold_v: Option<f32>,
+    low_reads: u8,
+}
+
+impl CriticalBattery {
+    pub fn new() -> Self {
+        let threshold_v = BATTERY_CRITICAL_V_ENV
+            .and_then(|v| v.trim().parse::<f32>().ok())
+            .unwrap_or(BATTERY_CRITICAL_V_DEFAULT);
+        Self {
+            threshold_v: (threshold_v > 0.0).then_some(threshold_v),
+            low_reads: 0,
+        }
+    }
+
+    // True once enough readings in a row were critical.
+    pub fn update(&mut self, voltage: f32) -> bool {
+        let Some(threshold_v) = self.threshold_v else {
+            return false;
+        };
+        if (BATTERY_ABSENT_V..threshold_v).contains(&voltage) {
+            self.low_reads = self.low_reads.saturating_add(1);
+        } else {
+            self.low_reads = 0;
+        }
+        self.low_reads >= BATTERY_CRITICAL_READS
+    }
+}
 
 pub struct Battery<'d> {
-    channel: AdcChannelDriver<'d, Gpio0, AdcDriver<'d, ADC1>>,
+    channel: AdcChannelDriver<'d, Gpio0, &'d AdcDriver<'d, ADC1>>,
+    samples: usize,
+    outlier_mv: u16,
 }
 
 impl<'d> Battery<'d> {
-    pub fn new(
-        adc: impl Peripheral<P = ADC1> + 'd,
-        pin: impl Peripheral<P = Gpio0> + 'd,
-    ) -> Result<Self> {
-        let adc = AdcDriver::new(adc)?;
+    pub fn new(adc: &'d AdcDriver<'d, ADC1>, pin: impl Peripheral<P = Gpio0> + 'd) -> Result<Self> {
         let config = AdcChannelConfig {
             attenuation: DB_11,
             calibration: calibration_mode(),
             ..Default::default()
         };
         let channel = AdcChannelDriver::new(adc, pin, &config)?;
-        Ok(Self { channel })
+        let samples = BATTERY_SAMPLES_ENV
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(BATTERY_SAMPLES_DEFAULT)
+            .clamp(1, 32);
+        let outlier_mv = BATTERY_OUTLIER_MV_ENV
+            .and_then(|v| v.trim().parse::<u16>().ok())
+            .unwrap_or(BATTERY_OUTLIER_MV_DEFAULT);
+        Ok(Self {
+            channel,
+            samples,
+            outlier_mv,
+        })
     }
 
-    pub fn read_voltage(&mut self) -> Result<f32> {
-        let mv = self.channel.read()? as f32;
-        Ok((mv / 1000.0) * BATTERY_SCALE)
+    // Battery voltage averaged over the configured samples, without spikes.
+    // Failed ADC reads are skipped; None only if every sample failed.
+    pub fn read_voltage(&mut self) -> Option<f32> {
+        let mut readings = Vec::with_capacity(self.samples);
+        let mut last_err = None;
+        for _ in 0..self.samples {
+            match self.channel.read() {
+                Ok(mv) => readings.push(mv),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        if readings.is_empty() {
+            warn!("Battery ADC read failed: {:?}", last_err);
+            return None;
+        }
+        readings.sort_unstable();
+        let median = readings[readings.len() / 2];
+        let kept: Vec<u16> = readings
+            .into_iter()
+            .filter(|mv| mv.abs_diff(median) <= self.outlier_mv)
+            .collect();
+        let mv = kept.iter().map(|&mv| mv as f32).sum::<f32>() / kept.len() as f32;
+        Some((mv / 1000.0) * BATTERY_SCALE)
     }
 }
 
@@ -45,7 +168,7 @@ impl<'d> Battery<'d> {
         esp32s3
     )
 ))]
-fn calibration_mode() -> Calibration {
+pub fn calibration_mode() -> Calibration {
     Calibration::Curve
 }
 
@@ -61,6 +184,6 @@ fn calibration_mode() -> Calibration {
         esp32s3
     )
 )))]
-fn calibration_mode() -> Calibration {
+pub fn calibration_mode() -> Calibration {
     Calibration::None
 }
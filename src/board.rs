@@ -1,69 +1,345 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
 use anyhow::Result;
+use esp_idf_hal::adc::oneshot::AdcDriver;
 use esp_idf_hal::gpio::AnyIOPin;
 use esp_idf_hal::i2c::I2cDriver;
 use esp_idf_hal::ledc;
+use esp_idf_hal::modem::Modem;
 use esp_idf_hal::peripherals::Peripherals;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::EspSntp;
 
-use crate::battery::Battery;
-use crate::st7789::{init_lcd, St7789};
-use crate::mhz19b::{init_mhz19b, Mhz19b};
+use crate::ambient::{AmbientLight, AMBIENT_LIGHT_ENABLED};
+use crate::battery::{Battery, BatteryMonitor};
+use crate::button::Button;
+use crate::co2::Co2Source;
+#[cfg(feature = "epaper")]
+use crate::epaper::Epaper;
+use crate::fuel_gauge::Max17048;
+use crate::panic_screen;
+use crate::pins::{self, StatusLedPins, UartPort};
+use crate::relay::Relay;
+use crate::st7789::{init_lcd, init_spi_bus, St7789};
+use crate::mhz19b::{init_mhz19b, MhzError, UartFraming};
+use crate::scd4x::{Scd4x, ScdError};
+use crate::settings::Settings;
 use crate::sht31::Sht31;
-use crate::touch::init_i2c;
+use crate::status_led::StatusLed;
+use crate::touch::{init_i2c, probe_touch};
 use crate::wifi::init_wifi;
-use log::warn;
+use log::{info, warn};
+
+// Staggered bring-up for marginal supplies (weak USB ports, small cells):
+// sensors first, then the LCD with its backlight fading in from dark, then
+// Wi-Fi last, with this pause between stages so their inrush currents do not
+// add up. Unset or 0 keeps the default order: Wi-Fi before the LCD, backlight
+// straight on.
+const STAGGER_INIT_MS_ENV: Option<&str> = option_env!("STAGGER_INIT_MS");
+// Backlight level the staggered LCD stage fades to; the main loop's default.
+const STAGGER_BACKLIGHT_PCT: u8 = 10;
+
+fn stagger_delay() -> Option<Duration> {
+    STAGGER_INIT_MS_ENV
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|&ms| ms > 0)
+        .map(Duration::from_millis)
+}
+
+// Which sensors answered at boot; absent sensors are skipped by the main loop.
+#[derive(Debug, Clone, Copy)]
+pub struct SensorPresence {
+    pub co2: bool,
+    pub sht31: bool,
+}
 
 pub struct Board {
     pub lcd: St7789<'static, ledc::TIMER0>,
     pub i2c: I2cDriver<'static>,
-    pub mhz19b: Mhz19b<'static>,
-    pub battery: Battery<'static>,
+    // MH-Z19B (UART) or SCD4x (I2C), whichever was detected at boot.
+    pub co2: Box<dyn Co2Source>,
+    // MAX17048 fuel gauge if one answered at boot, else the ADC divider.
+    pub battery: BatteryMonitor<'static>,
+    pub ambient: Option<AmbientLight<'static>>,
+    pub button: Option<Button>,
+    pub status_led: Option<StatusLed>,
+    pub relay: Option<Relay>,
+    #[cfg(feature = "epaper")]
+    pub epaper: Option<Epaper>,
     pub sht31: Sht31,
     pub wifi: Option<esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>>,
+    pub presence: SensorPresence,
+    // The touch controller answered its probe at boot.
+    pub touch: bool,
+    pub settings: Settings,
+    // Keeps SNTP running for the lifetime of the app (local time for night mode).
+    pub sntp: Option<EspSntp<'static>>,
 }
 
 impl Board {
     pub fn init() -> Result<Self> {
         let Peripherals {
-            pins,
+            pins: gpio_pins,
             i2c0,
             uart0,
+            uart1,
             spi2,
             ledc,
+            rmt,
             adc1,
             modem,
             ..
         } = Peripherals::take()?;
 
-        let i2c = init_i2c(i2c0, pins.gpio18, pins.gpio19)?;
-        let mut mhz19b = init_mhz19b(uart0, pins.gpio16, pins.gpio17)?;
-        mhz19b.set_abc(false)?;
-        let sht31 = Sht31::new_default();
-        let wifi = match init_wifi(modem) {
-            Ok(wifi) => Some(wifi),
+        let stagger = stagger_delay();
+        if let Some(delay) = stagger {
+            info!("Staggered init, {} ms between stages", delay.as_millis());
+        }
+
+        let mut i2c = init_i2c(i2c0, gpio(pins::I2C_SDA_GPIO), gpio(pins::I2C_SCL_GPIO))?;
+        let touch = match probe_touch(&mut i2c) {
+            Ok(()) => true,
             Err(err) => {
-                warn!("Wi-Fi init failed: {:?}", err);
-                None
+                warn!("Touch probe failed, touch disabled: {:?}", err);
+                false
             }
         };
-        let lcd = init_lcd(
-            spi2,
-            ledc,
-            AnyIOPin::from(pins.gpio1),
-            AnyIOPin::from(pins.gpio2),
-            AnyIOPin::from(pins.gpio14),
-            AnyIOPin::from(pins.gpio15),
-            AnyIOPin::from(pins.gpio22),
-            AnyIOPin::from(pins.gpio23),
+        let mut co2 = init_co2(&mut i2c, uart0, uart1)?;
+        co2.set_abc(&mut i2c, false)?;
+        let sht31 = Sht31::new_default();
+        let nvs = EspDefaultNvsPartition::take()?;
+        let settings = Settings::new(nvs.clone());
+        // Staggered: the modem waits for the last stage.
+        let (mut wifi, mut sntp, deferred_modem) = match stagger {
+            None => {
+                let (wifi, sntp) = init_network(modem, nvs.clone());
+                (wifi, sntp, None)
+            }
+            Some(_) => (None, None, Some(modem)),
+        };
+        if let Some(delay) = stagger {
+            thread::sleep(delay);
+        }
+        let spi_bus = init_spi_bus(spi2, gpio(pins::LCD_CLK_GPIO), gpio(pins::LCD_MOSI_GPIO))?;
+        let mut lcd = init_lcd(
+            spi_bus,
+            ledc.timer0,
+            ledc.channel0,
+            gpio(pins::LCD_CS_GPIO),
+            gpio(pins::LCD_DC_GPIO),
+            gpio(pins::LCD_RST_GPIO),
+            gpio(pins::LCD_BL_GPIO),
         )?;
-        let battery = Battery::new(adc1, pins.gpio0)?;
+        panic_screen::register_lcd(lcd.spi_handle());
+        match stagger {
+            Some(delay) => {
+                lcd.set_brightness(STAGGER_BACKLIGHT_PCT)?;
+                thread::sleep(delay);
+            }
+            None => lcd.set_brightness_immediate(100)?,
+        }
+        #[cfg(feature = "epaper")]
+        let epaper = match pins::EPAPER.as_ref().map(|p| {
+            Epaper::new(spi_bus, gpio(p.cs), gpio(p.dc), gpio(p.rst), gpio(p.busy))
+        }) {
+            Some(Ok(epaper)) => Some(epaper),
+            Some(Err(err)) => {
+                warn!("E-paper init failed: {:?}", err);
+                None
+            }
+            None => None,
+        };
+        // ADC1 is shared by the battery and the optional ambient light channel.
+        let adc1: &'static AdcDriver<'static, _> = Box::leak(Box::new(AdcDriver::new(adc1)?));
+        let battery = init_battery(&mut i2c, adc1, gpio_pins.gpio0)?;
+        let ambient = if AMBIENT_LIGHT_ENABLED {
+            match AmbientLight::new(adc1, gpio_pins.gpio3) {
+                Ok(ambient) => Some(ambient),
+                Err(err) => {
+                    warn!("Ambient light init failed: {:?}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let button = pins::BUTTON_GPIO.map(Button::new);
+        let status_led = match pins::STATUS_LED {
+            Some(StatusLedPins::Ws2812 { data }) => Some(StatusLed::ws2812(rmt.channel0, gpio(data))),
+            Some(StatusLedPins::Rgb { r, g, b }) => Some(StatusLed::rgb(
+                ledc.timer1,
+                (ledc.channel1, ledc.channel2, ledc.channel3),
+                (gpio(r), gpio(g), gpio(b)),
+            )),
+            None => None,
+        };
+        let status_led = match status_led {
+            Some(Ok(led)) => Some(led),
+            Some(Err(err)) => {
+                warn!("Status LED init failed: {:?}", err);
+                None
+            }
+            None => None,
+        };
+        let relay = match pins::RELAY_GPIO.map(|gpio| Relay::new(gpio, pins::RELAY_ACTIVE_HIGH)) {
+            Some(Ok(relay)) => Some(relay),
+            Some(Err(err)) => {
+                warn!("Relay init failed: {:?}", err);
+                None
+            }
+            None => None,
+        };
+        let presence = probe_all(co2.as_mut(), &sht31, &mut i2c);
+        if let Some(modem) = deferred_modem {
+            if let Some(delay) = stagger {
+                thread::sleep(delay);
+            }
+            (wifi, sntp) = init_network(modem, nvs);
+        }
 
         Ok(Self {
             lcd,
             i2c,
-            mhz19b,
+            co2,
             battery,
+            ambient,
+            button,
+            status_led,
+            relay,
+            #[cfg(feature = "epaper")]
+            epaper,
             sht31,
             wifi,
+            presence,
+            touch,
+            settings,
+            sntp,
         })
     }
 }
+
+// Wi-Fi plus SNTP (local time for night mode); both optional.
+fn init_network(
+    modem: Modem,
+    nvs: EspDefaultNvsPartition,
+) -> (
+    Option<esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>>,
+    Option<EspSntp<'static>>,
+) {
+    let wifi = match init_wifi(modem, nvs) {
+        Ok(wifi) => Some(wifi),
+        Err(err) => {
+            warn!("Wi-Fi init failed: {:?}", err);
+            None
+        }
+    };
+    let sntp = match wifi.as_ref().map(|_| EspSntp::new_default()) {
+        Some(Ok(sntp)) => Some(sntp),
+        Some(Err(err)) => {
+            warn!("SNTP init failed: {:?}", err);
+            None
+        }
+        None => None,
+    };
+    (wifi, sntp)
+}
+
+// An SCD4x answering on the I2C bus wins; otherwise fall back to the MH-Z19B on
+// the UART selected in `pins::MHZ_UART`.
+fn init_co2(
+    i2c: &mut I2cDriver<'_>,
+    uart0: esp_idf_hal::uart::UART0,
+    uart1: esp_idf_hal::uart::UART1,
+) -> Result<Box<dyn Co2Source>> {
+    let mut scd4x = Scd4x::new_default();
+    match scd4x.start(i2c) {
+        Ok(()) => {
+            info!("CO2 sensor: SCD4x on I2C");
+            Ok(Box::new(scd4x))
+        }
+        Err(err) => {
+            info!(
+                "SCD4x not detected ({}), using MH-Z19B on UART{}",
+                err,
+                pins::MHZ_UART.number()
+            );
+            let (tx, rx) = (gpio(pins::MHZ_TX_GPIO), gpio(pins::MHZ_RX_GPIO));
+            let mhz19b = match pins::MHZ_UART {
+                UartPort::Uart0 => init_mhz19b(uart0, tx, rx, UartFraming::from_env())?,
+                UartPort::Uart1 => init_mhz19b(uart1, tx, rx, UartFraming::from_env())?,
+            };
+            Ok(Box::new(mhz19b))
+        }
+    }
+}
+
+// A MAX17048 answering on the I2C bus wins over the ADC divider on GPIO0.
+fn init_battery(
+    i2c: &mut I2cDriver<'_>,
+    adc1: &'static AdcDriver<'static, esp_idf_hal::adc::ADC1>,
+    pin: esp_idf_hal::gpio::Gpio0,
+) -> Result<BatteryMonitor<'static>> {
+    let gauge = Max17048::new_default();
+    match gauge.probe(i2c) {
+        Ok(Some(version)) => {
+            info!("Battery: MAX17048 fuel gauge (version 0x{:04X})", version);
+            return Ok(BatteryMonitor::FuelGauge(gauge));
+        }
+        Ok(None) => warn!(
+            "Unknown chip at 0x{:02X}, using battery ADC",
+            Max17048::DEFAULT_ADDR
+        ),
+        Err(err) => info!("Fuel gauge not detected ({}), using battery ADC", err),
+    }
+    Ok(BatteryMonitor::Adc(Battery::new(adc1, pin)?))
+}
+
+pub fn probe_all(co2: &mut dyn Co2Source, sht31: &Sht31, i2c: &mut I2cDriver<'_>) -> SensorPresence {
+    let name = co2.name();
+    let co2 = match co2.read(i2c) {
+        Ok(_) => true,
+        // It answered; a cold MH-Z19B may still report implausible values.
+        Err(err) if matches!(err.downcast_ref::<MhzError>(), Some(MhzError::OutOfRange(_))) => {
+            warn!("{} probe: {}", name, err);
+            true
+        }
+        // The SCD4x ACKed `start()` in `init_co2` but its first sample takes
+        // ~5 s; asking before then is not a missing sensor.
+        Err(err) if matches!(err.downcast_ref::<ScdError>(), Some(ScdError::NotReady)) => {
+            info!("{} probe: {}", name, err);
+            true
+        }
+        Err(err) => {
+            warn!("{} probe failed: {}", name, err);
+            false
+        }
+    };
+    let sht31 = match sht31.read(i2c) {
+        Ok(_) => true,
+        Err(err) => {
+            warn!("SHT31 probe failed: {:?}", err);
+            false
+        }
+    };
+    let mark = |present: bool| if present { "present" } else { "MISSING" };
+    info!("Sensors: {} {}, SHT31 {}", name, mark(co2), mark(sht31));
+    SensorPresence { co2, sht31 }
+}
+
+// Bit n set = GPIOn handed out by `gpio()`.
+static CLAIMED_GPIOS: AtomicU64 = AtomicU64::new(0);
+
+// Pins are addressed by number from `pins.rs` so the wiring lives in one place.
+// `pins.rs` already rejects duplicates at build time; this catches a pin wired
+// here that is missing from its table.
+fn gpio(num: i32) -> AnyIOPin {
+    let bit = 1u64 << num;
+    if CLAIMED_GPIOS.fetch_or(bit, Ordering::Relaxed) & bit != 0 {
+        panic!("GPIO{} is assigned twice, check pins.rs", num);
+    }
+    // Safety: `Peripherals::take()` has succeeded, so no other driver owns the
+    // GPIOs, and each board pin number is claimed exactly once (checked above).
+    unsafe { AnyIOPin::new(num) }
+}
@@ -0,0 +1,104 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use esp_idf_sys as sys;
+use log::error;
+use std::time::{Duration, Instant};
+
+const DEBOUNCE: Duration = Duration::from_millis(30);
+const LONG_PRESS: Duration = Duration::from_secs(2);
+
+static BUTTON_PENDING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Press,
+    LongPress,
+}
+
+// Active-low momentary button with internal pull-up; edges set a pending flag
+// from the ISR and the main loop debounces in `poll()`.
+pub struct Button {
+    gpio: i32,
+    pressed_at: Option<Instant>,
+    long_fired: bool,
+}
+
+unsafe extern "C" fn button_isr_handler(_arg: *mut core::ffi::c_void) {
+    BUTTON_PENDING.store(true, Ordering::Release);
+}
+
+impl Button {
+    pub fn new(gpio: i32) -> Self {
+        BUTTON_PENDING.store(false, Ordering::Relaxed);
+        unsafe {
+            sys::gpio_reset_pin(gpio);
+            sys::gpio_set_direction(gpio, sys::gpio_mode_t_GPIO_MODE_INPUT);
+            sys::gpio_pullup_en(gpio);
+            sys::gpio_pulldown_dis(gpio);
+
+            let err = sys::gpio_install_isr_service(0);
+            if err != sys::ESP_OK as i32 && err != sys::ESP_ERR_INVALID_STATE as i32 {
+                error!("Button ISR install failed: {}", err);
+            }
+
+            let err = sys::gpio_set_intr_type(gpio, sys::gpio_int_type_t_GPIO_INTR_ANYEDGE);
+            if err != sys::ESP_OK as i32 {
+                error!("Button intr_type failed: {}", err);
+            }
+
+            let err = sys::gpio_isr_handler_add(gpio, Some(button_isr_handler), core::ptr::null_mut());
+            if err != sys::ESP_OK as i32 && err != sys::ESP_ERR_INVALID_STATE as i32 {
+                error!("Button ISR handler add failed: {}", err);
+            }
+
+            let err = sys::gpio_intr_enable(gpio);
+            if err != sys::ESP_OK as i32 {
+                error!("Button INT enable failed: {}", err);
+            }
+        }
+        Self {
+            gpio,
+            pressed_at: None,
+            long_fired: false,
+        }
+    }
+
+    fn is_down(&self) -> bool {
+        unsafe { sys::gpio_get_level(self.gpio) == 0 }
+    }
+
+    // Raw level check, e.g. for "hold the button while powering on" combos.
+    pub fn is_held(&self) -> bool {
+        self.is_down()
+    }
+
+    pub fn poll(&mut self) -> Option<ButtonEvent> {
+        let edge = BUTTON_PENDING.swap(false, Ordering::AcqRel);
+        if !edge && self.pressed_at.is_none() {
+            return None;
+        }
+
+        let down = self.is_down();
+        match (self.pressed_at, down) {
+            (None, true) => {
+                self.pressed_at = Some(Instant::now());
+                self.long_fired = false;
+                None
+            }
+            (Some(start), true) => {
+                if !self.long_fired && start.elapsed() >= LONG_PRESS {
+                    self.long_fired = true;
+                    return Some(ButtonEvent::LongPress);
+                }
+                None
+            }
+            (Some(start), false) => {
+                self.pressed_at = None;
+                if !self.long_fired && start.elapsed() >= DEBOUNCE {
+                    return Some(ButtonEvent::Press);
+                }
+                None
+            }
+            (None, false) => None,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use esp_idf_hal::i2c::I2cDriver;
+
+// One CO2 measurement. Sensors that also report temperature/humidity (SCD4x)
+// fill the optional fields; NDIR-only sensors leave them `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct Co2Reading {
+    pub ppm: u16,
+    pub temperature_c: Option<f32>,
+    pub humidity_pct: Option<f32>,
+}
+
+// Common interface for the supported CO2 sensors. The shared I2C bus is passed
+// per call (like `Sht31`) because the main loop owns it; UART sensors ignore it.
+pub trait Co2Source {
+    fn name(&self) -> &'static str;
+    fn read(&mut self, i2c: &mut I2cDriver<'_>) -> Result<Co2Reading>;
+    fn calibrate_zero(&mut self, i2c: &mut I2cDriver<'_>) -> Result<()>;
+    fn set_abc(&mut self, i2c: &mut I2cDriver<'_>, enabled: bool) -> Result<()>;
+    // True if `read` fills `temperature_c`/`humidity_pct`.
+    fn reports_temp_humidity(&self) -> bool {
+        false
+    }
+    // Time after power-up before readings are trustworthy; the main loop holds
+    // off reading (and shows a progress bar) until it has passed.
+    fn warmup(&self) -> Duration {
+        Duration::ZERO
+    }
+    // Last complete raw reply received (valid or not) since the previous call,
+    // for remote diagnostics; sensors without a frame protocol return None.
+    fn take_raw_frame(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+    // Called after repeated read errors; reset the transport if that helps.
+    fn recover(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+// Device commands as sent to `<prefix>/cmd` or typed on the serial console,
+// their parser and the per-command rate limit. Free of esp-idf so
+// tools/driver_tests can test every command string on the host.
+
+use std::time::{Duration, Instant};
+
+use crate::options::{
+    clamp_co2_alarm, clamp_co2_smoothing, Layout, RelayMode, TempHumSource, TempUnit,
+    ThemePreset,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    ZeroCalibrate,
+    SetAbc(bool),
+    SetBrightness(u8),
+    SetCo2Alarm(u16),
+    SetCo2Smoothing(u8),
+    SetTempHumSource(TempHumSource),
+    SetTheme(ThemePreset),
+    SetLayout(Layout),
+    SetLcdInvert(bool),
+    SetTempUnit(TempUnit),
+    SetTempDecimals(u8),
+    ResetPeak,
+    ResetCrashCount,
+    SetRelay(RelayMode),
+    ReportSettings,
+    Reboot,
+    FactoryReset,
+}
+
+impl Command {
+    // Stable key used in `<prefix>/cmd/ack` payloads.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::ZeroCalibrate => "zero_calibrate",
+            Command::SetAbc(_) => "abc",
+            Command::SetBrightness(_) => "brightness",
+            Command::SetCo2Alarm(_) => "co2_alarm",
+            Command::SetCo2Smoothing(_) => "co2_smoothing",
+            Command::SetTempHumSource(_) => "temp_hum_source",
+            Command::SetTheme(_) => "theme",
+            Command::SetLayout(_) => "layout",
+            Command::SetLcdInvert(_) => "lcd_invert",
+            Command::SetTempUnit(_) => "temp_unit",
+            Command::SetTempDecimals(_) => "temp_decimals",
+            Command::ResetPeak => "reset_peak",
+            Command::ResetCrashCount => "reset_crashes",
+            Command::SetRelay(_) => "relay",
+            Command::ReportSettings => "settings",
+            Command::Reboot => "reboot",
+            Command::FactoryReset => "factory_reset",
+        }
+    }
+
+    // Minimum time between two successful runs of this command type; a repeat
+    // inside the window is dropped (see CommandThrottle). Setters have none,
+    // so the last value of e.g. a dragged HA slider always lands.
+    fn cooldown(&self) -> Duration {
+        match self {
+            Command::Reboot | Command::FactoryReset => Duration::from_secs(60),
+            Command::ZeroCalibrate => Duration::from_secs(120),
+            Command::SetAbc(_)
+            | Command::ResetPeak
+            | Command::ResetCrashCount
+            | Command::ReportSettings => Duration::from_secs(5),
+            _ => Duration::ZERO,
+        }
+    }
+
+    // Setters whose value is part of SettingsReport; the retained settings
+    // topic is republished after one succeeds.
+    pub fn changes_settings(&self) -> bool {
+        matches!(
+            self,
+            Command::SetAbc(_)
+                | Command::SetBrightness(_)
+                | Command::SetCo2Alarm(_)
+                | Command::SetCo2Smoothing(_)
+                | Command::SetTempHumSource(_)
+                | Command::SetTheme(_)
+                | Command::SetLayout(_)
+                | Command::SetLcdInvert(_)
+                | Command::SetTempUnit(_)
+                | Command::SetTempDecimals(_)
+                | Command::SetRelay(_)
+        )
+    }
+
+    // Restarting commands also wait out their cooldown after boot, so a
+    // command storm cannot keep the device in a reboot loop.
+    fn cooldown_from_boot(&self) -> bool {
+        matches!(self, Command::Reboot | Command::FactoryReset)
+    }
+}
+
+// Per-command-type rate limit for commands from MQTT, against automations
+// that repeat a command in a loop.
+pub struct CommandThrottle {
+    booted_at: Instant,
+    last_run: Vec<(&'static str, Instant)>,
+}
+
+impl CommandThrottle {
+    pub fn new() -> Self {
+        Self {
+            booted_at: Instant::now(),
+            last_run: Vec::new(),
+        }
+    }
+
+    // Time left before `cmd` may run again, or None if it may run now.
+    pub fn remaining(&self, cmd: &Command) -> Option<Duration> {
+        let cooldown = cmd.cooldown();
+        let last = self
+            .last_run
+            .iter()
+            .find(|(name, _)| *name == cmd.name())
+            .map(|(_, at)| *at)
+            .or_else(|| cmd.cooldown_from_boot().then_some(self.booted_at))?;
+        let left = cooldown.saturating_sub(last.elapsed());
+        (!left.is_zero()).then_some(left)
+    }
+
+    // Start the cooldown; called only for commands that succeeded, so e.g.
+    // the factory_reset confirmation still gets through after arming.
+    pub fn record(&mut self, cmd: &Command) {
+        if cmd.cooldown().is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        match self.last_run.iter_mut().find(|(name, _)| *name == cmd.name()) {
+            Some((_, at)) => *at = now,
+            None => self.last_run.push((cmd.name(), now)),
+        }
+    }
+}
+
+pub fn parse_command(payload: &[u8]) -> Option<Command> {
+    let text = String::from_utf8_lossy(payload);
+    let text = text.trim().to_ascii_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    if text == "zero" || text == "zero_calibrate" {
+        return Some(Command::ZeroCalibrate);
+    }
+    if text == "reboot" {
+        return Some(Command::Reboot);
+    }
+    if text == "reset_peak" {
+        return Some(Command::ResetPeak);
+    }
+    if text == "reset_crashes" {
+        return Some(Command::ResetCrashCount);
+    }
+    if text == "settings" || text == "get" {
+        return Some(Command::ReportSettings);
+    }
+    if text == "factory_reset" {
+        return Some(Command::FactoryReset);
+    }
+    if let Some(value) = text.strip_prefix("abc=") {
+        return parse_on_off(value).map(Command::SetAbc);
+    }
+    if let Some(value) = text.strip_prefix("abc:") {
+        return parse_on_off(value).map(Command::SetAbc);
+    }
+    if let Some(value) = text.strip_prefix("brightness=") {
+        return parse_percent(value).map(Command::SetBrightness);
+    }
+    if let Some(value) = text.strip_prefix("brightness:") {
+        return parse_percent(value).map(Command::SetBrightness);
+    }
+    if let Some(value) = text.strip_prefix("co2_alarm=") {
+        return parse_co2_alarm(value).map(Command::SetCo2Alarm);
+    }
+    if let Some(value) = text.strip_prefix("co2_alarm:") {
+        return parse_co2_alarm(value).map(Command::SetCo2Alarm);
+    }
+    if let Some(value) = text.strip_prefix("co2_smoothing=") {
+        return parse_co2_smoothing(value).map(Command::SetCo2Smoothing);
+    }
+    if let Some(value) = text.strip_prefix("co2_smoothing:") {
+        return parse_co2_smoothing(value).map(Command::SetCo2Smoothing);
+    }
+    if let Some(value) = text.strip_prefix("temp_hum_source=") {
+        return TempHumSource::parse(value).map(Command::SetTempHumSource);
+    }
+    if let Some(value) = text.strip_prefix("temp_hum_source:") {
+        return TempHumSource::parse(value).map(Command::SetTempHumSource);
+    }
+    if let Some(value) = text.strip_prefix("temp_unit=") {
+        return TempUnit::parse(value).map(Command::SetTempUnit);
+    }
+    if let Some(value) = text.strip_prefix("temp_unit:") {
+        return TempUnit::parse(value).map(Command::SetTempUnit);
+    }
+    if let Some(value) = text.strip_prefix("temp_decimals=") {
+        return parse_temp_decimals(value).map(Command::SetTempDecimals);
+    }
+    if let Some(value) = text.strip_prefix("temp_decimals:") {
+        return parse_temp_decimals(value).map(Command::SetTempDecimals);
+    }
+    if let Some(value) = text.strip_prefix("relay=") {
+        return RelayMode::parse(value).map(Command::SetRelay);
+    }
+    if let Some(value) = text.strip_prefix("relay:") {
+        return RelayMode::parse(value).map(Command::SetRelay);
+    }
+    if let Some(value) = text.strip_prefix("theme=") {
+        return ThemePreset::parse(value).map(Command::SetTheme);
+    }
+    if let Some(value) = text.strip_prefix("theme:") {
+        return ThemePreset::parse(value).map(Command::SetTheme);
+    }
+    if let Some(value) = text.strip_prefix("layout=") {
+        return Layout::parse(value).map(Command::SetLayout);
+    }
+    if let Some(value) = text.strip_prefix("layout:") {
+        return Layout::parse(value).map(Command::SetLayout);
+    }
+    if let Some(value) = text.strip_prefix("lcd_invert=") {
+        return parse_on_off(value).map(Command::SetLcdInvert);
+    }
+    if let Some(value) = text.strip_prefix("lcd_invert:") {
+        return parse_on_off(value).map(Command::SetLcdInvert);
+    }
+    None
+}
+
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value.trim() {
+        "1" | "on" | "true" => Some(true),
+        "0" | "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_percent(value: &str) -> Option<u8> {
+    let raw = value.trim().parse::<u8>().ok()?;
+    Some(raw.min(100))
+}
+
+fn parse_co2_alarm(value: &str) -> Option<u16> {
+    let raw = value.trim().parse::<u16>().ok()?;
+    Some(clamp_co2_alarm(raw))
+}
+
+fn parse_temp_decimals(value: &str) -> Option<u8> {
+    match value.trim() {
+        "0" => Some(0),
+        "1" => Some(1),
+        _ => None,
+    }
+}
+
+fn parse_co2_smoothing(value: &str) -> Option<u8> {
+    let raw = value.trim().parse::<u8>().ok()?;
+    Some(clamp_co2_smoothing(raw))
+}
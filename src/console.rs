@@ -0,0 +1,100 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use anyhow::Result;
+use esp_idf_hal::task::thread::ThreadSpawnConfiguration;
+use esp_idf_sys as sys;
+use log::{info, warn};
+
+use crate::command::{parse_command, Command};
+
+const RX_BUFFER: u32 = 256;
+const TX_BUFFER: u32 = 256;
+// Same as the main task: never preempts the UI/sensor loop, only runs when it sleeps.
+const THREAD_PRIORITY: u8 = 1;
+const THREAD_STACK: usize = 4096;
+
+// A line typed on the serial console: either one of the MQTT commands or a
+// bench-debugging extra that only makes sense interactively.
+#[derive(Debug)]
+pub enum ConsoleRequest {
+    Command(Command),
+    Status,
+    ReadCo2,
+    I2cScan,
+}
+
+// Line-based command console on the USB-Serial-JTAG port. A reader thread
+// parses lines and hands them to the main loop, which owns the sensors and
+// the I2C bus; results are logged to the same console.
+pub struct Console {
+    rx: Receiver<ConsoleRequest>,
+}
+
+impl Console {
+    pub fn start() -> Result<Self> {
+        // Without the driver, stdin is non-blocking and the reader would spin.
+        let mut config = sys::usb_serial_jtag_driver_config_t {
+            tx_buffer_size: TX_BUFFER,
+            rx_buffer_size: RX_BUFFER,
+        };
+        sys::EspError::convert(unsafe { sys::usb_serial_jtag_driver_install(&mut config) })?;
+        unsafe { sys::esp_vfs_usb_serial_jtag_use_driver() };
+
+        let (tx, rx) = mpsc::channel::<ConsoleRequest>();
+        ThreadSpawnConfiguration {
+            name: Some(b"console\0"),
+            priority: THREAD_PRIORITY,
+            ..Default::default()
+        }
+        .set()?;
+        let spawned = thread::Builder::new()
+            .stack_size(THREAD_STACK)
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else { continue };
+                    match parse_line(&line) {
+                        Some(request) => {
+                            if tx.send(request).is_err() {
+                                return;
+                            }
+                        }
+                        None if line.trim().is_empty() => {}
+                        None => print_help(&line),
+                    }
+                }
+            });
+        ThreadSpawnConfiguration::default().set()?;
+        spawned?;
+
+        info!("Console ready (type 'help')");
+        Ok(Self { rx })
+    }
+
+    pub fn try_recv(&self) -> Option<ConsoleRequest> {
+        self.rx.try_recv().ok()
+    }
+}
+
+fn parse_line(line: &str) -> Option<ConsoleRequest> {
+    let text = line.trim().to_ascii_lowercase();
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["status"] => Some(ConsoleRequest::Status),
+        ["read", "co2"] => Some(ConsoleRequest::ReadCo2),
+        ["i2cscan"] => Some(ConsoleRequest::I2cScan),
+        _ => parse_command(text.as_bytes()).map(ConsoleRequest::Command),
+    }
+}
+
+fn print_help(line: &str) {
+    if line.trim() != "help" {
+        warn!("Console: unknown command {:?}", line.trim());
+    }
+    info!(
+        "Console commands: status, read co2, i2cscan, zero_calibrate, abc:on|off, brightness:NN, \
+         co2_alarm:PPM, co2_smoothing:N, temp_hum_source:auto|sht31|scd4x, theme:NAME, \
+         layout:cards|big, lcd_invert:on|off, temp_unit:c|f, temp_decimals:0|1, reset_peak, \
+         reset_crashes, relay:on|off|auto, settings, reboot, factory_reset"
+    );
+}
@@ -0,0 +1,46 @@
+use std::f32::consts::TAU;
+use std::time::Instant;
+
+use crate::sht31::{ShtError, ShtReading};
+
+// Plausible animated readings for UI work without a sensor rig.
+pub struct DemoData {
+    start: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DemoSample {
+    pub co2_ppm: u16,
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+    pub battery_v: f32,
+}
+
+impl DemoData {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    pub fn sample(&self) -> DemoSample {
+        let t = self.start.elapsed().as_secs_f32();
+        // Slow CO2 sweep through every status band (Good..Bad) every 2 minutes.
+        let co2_phase = (t / 120.0 * TAU).sin() * 0.5 + 0.5;
+        DemoSample {
+            co2_ppm: (450.0 + co2_phase * 1400.0) as u16,
+            temperature_c: 22.5 + (t / 45.0 * TAU).sin() * 1.5,
+            humidity_pct: 47.0 + (t / 70.0 * TAU).cos() * 8.0,
+            battery_v: 3.9 + (t / 300.0 * TAU).sin() * 0.2,
+        }
+    }
+
+    pub fn sht_reading(&self) -> Result<ShtReading, ShtError> {
+        let sample = self.sample();
+        ShtReading {
+            temperature_c: sample.temperature_c,
+            humidity_pct: sample.humidity_pct,
+        }
+        .validate()
+    }
+}
@@ -0,0 +1,83 @@
+use core::ffi::CStr;
+
+use esp_idf_sys as sys;
+use log::info;
+
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+const OTA_BUILD: Option<&str> = option_env!("OTA_BUILD");
+
+pub fn uptime_secs() -> u32 {
+    let micros = unsafe { sys::esp_timer_get_time() };
+    (micros / 1_000_000) as u32
+}
+
+// Human-readable reason for the last reset (stable for the whole boot).
+pub fn reset_reason() -> &'static str {
+    #[allow(non_upper_case_globals)]
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => "power_on",
+        sys::esp_reset_reason_t_ESP_RST_EXT => "external",
+        // esp_restart(): MQTT reboot command or a freshly applied OTA image.
+        sys::esp_reset_reason_t_ESP_RST_SW => "software_or_ota",
+        sys::esp_reset_reason_t_ESP_RST_PANIC => "panic",
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => "interrupt_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => "task_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_WDT => "watchdog",
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => "deep_sleep",
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => "brownout",
+        sys::esp_reset_reason_t_ESP_RST_SDIO => "sdio",
+        _ => "unknown",
+    }
+}
+
+// True when the last reset was a crash rather than a deliberate restart or
+// power cycle; these are tallied in NVS as the device's crash count.
+pub fn reset_was_crash() -> bool {
+    #[allow(non_upper_case_globals)]
+    matches!(
+        unsafe { sys::esp_reset_reason() },
+        sys::esp_reset_reason_t_ESP_RST_PANIC
+            | sys::esp_reset_reason_t_ESP_RST_INT_WDT
+            | sys::esp_reset_reason_t_ESP_RST_TASK_WDT
+            | sys::esp_reset_reason_t_ESP_RST_WDT
+            | sys::esp_reset_reason_t_ESP_RST_BROWNOUT
+    )
+}
+
+// One grep-able line with everything needed to reproduce a field issue.
+pub fn log_boot_banner() {
+    let idf_version = unsafe { CStr::from_ptr(sys::esp_get_idf_version()) }.to_string_lossy();
+
+    let mut chip = sys::esp_chip_info_t::default();
+    unsafe { sys::esp_chip_info(&mut chip) };
+    #[allow(non_upper_case_globals)]
+    let chip_model = match chip.model {
+        sys::esp_chip_model_t_CHIP_ESP32C6 => "esp32c6",
+        sys::esp_chip_model_t_CHIP_ESP32C3 => "esp32c3",
+        sys::esp_chip_model_t_CHIP_ESP32S3 => "esp32s3",
+        sys::esp_chip_model_t_CHIP_ESP32 => "esp32",
+        _ => "unknown",
+    };
+
+    let mut flash_size: u32 = 0;
+    let flash_mb = if unsafe { sys::esp_flash_get_size(core::ptr::null_mut(), &mut flash_size) }
+        == sys::ESP_OK as i32
+    {
+        flash_size / (1024 * 1024)
+    } else {
+        0
+    };
+
+    info!(
+        "BOOT app={} build={} idf={} chip={} rev=v{}.{} cores={} flash={}MB reset={}",
+        APP_VERSION,
+        OTA_BUILD.unwrap_or("none"),
+        idf_version,
+        chip_model,
+        chip.revision / 100,
+        chip.revision % 100,
+        chip.cores,
+        flash_mb,
+        reset_reason()
+    );
+}
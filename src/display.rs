@@ -1,24 +1,114 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
-use embedded_graphics::primitives::{CornerRadii, PrimitiveStyleBuilder, Rectangle, RoundedRectangle};
+use embedded_graphics::primitives::{
+    Arc, Circle, CornerRadii, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, RoundedRectangle,
+    Triangle,
+};
 use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
 use embedded_graphics_framebuf::backends::FrameBufferBackend;
 use u8g2_fonts::{fonts, U8g2TextStyle};
 
+use crate::board::SensorPresence;
+use crate::event_log::LogEntry;
+use crate::history::Co2Trend;
+use crate::options::{TempUnit, ThemePreset};
 use crate::st7789::{LCD_VIEW_H, LCD_VIEW_W};
 
-const COLOR_BG: Rgb565 = Rgb565::new(0, 0, 0);
-const COLOR_FRAME: Rgb565 = Rgb565::new(16, 32, 16);
-const COLOR_CARD: Rgb565 = Rgb565::new(3, 8, 5);
-const COLOR_LABEL: Rgb565 = Rgb565::new(31, 63, 33);
-const COLOR_CO2_ZERO: Rgb565 = Rgb565::new(0, 63, 31);
-const COLOR_TEMP: Rgb565 = Rgb565::new(31, 32, 0);
-const COLOR_HUM: Rgb565 = Rgb565::new(0, 32, 31);
-const COLOR_GOOD: Rgb565 = Rgb565::new(0, 63, 0);
-const COLOR_FAIR: Rgb565 = Rgb565::new(31, 63, 0);
-const COLOR_POOR: Rgb565 = Rgb565::new(31, 24, 0);
-const COLOR_BAD: Rgb565 = Rgb565::new(31, 0, 0);
+// Every colour the UI uses; `render_*` take one of the presets below.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub bg: Rgb565,
+    pub frame: Rgb565,
+    pub card: Rgb565,
+    pub label: Rgb565,
+    pub calibrating: Rgb565,
+    pub temp: Rgb565,
+    pub hum: Rgb565,
+    pub good: Rgb565,
+    pub fair: Rgb565,
+    pub poor: Rgb565,
+    pub bad: Rgb565,
+}
+
+const THEME_DARK: Theme = Theme {
+    bg: Rgb565::new(0, 0, 0),
+    frame: Rgb565::new(16, 32, 16),
+    card: Rgb565::new(3, 8, 5),
+    label: Rgb565::new(31, 63, 33),
+    calibrating: Rgb565::new(0, 63, 31),
+    temp: Rgb565::new(31, 32, 0),
+    hum: Rgb565::new(0, 32, 31),
+    good: Rgb565::new(0, 63, 0),
+    fair: Rgb565::new(31, 63, 0),
+    poor: Rgb565::new(31, 24, 0),
+    bad: Rgb565::new(31, 0, 0),
+};
+
+// Saturated colours on black with bright outlines, for bright rooms.
+const THEME_HIGH_CONTRAST: Theme = Theme {
+    bg: Rgb565::new(0, 0, 0),
+    frame: Rgb565::new(31, 63, 31),
+    card: Rgb565::new(4, 8, 4),
+    label: Rgb565::new(31, 63, 31),
+    calibrating: Rgb565::new(0, 63, 31),
+    temp: Rgb565::new(31, 63, 0),
+    hum: Rgb565::new(0, 63, 31),
+    good: Rgb565::new(0, 63, 0),
+    fair: Rgb565::new(31, 63, 0),
+    poor: Rgb565::new(31, 32, 0),
+    bad: Rgb565::new(31, 0, 0),
+};
+
+// Warm, blue-free palette for bedrooms/night use.
+const THEME_AMBER: Theme = Theme {
+    bg: Rgb565::new(0, 0, 0),
+    frame: Rgb565::new(20, 30, 0),
+    card: Rgb565::new(4, 6, 0),
+    label: Rgb565::new(31, 48, 8),
+    calibrating: Rgb565::new(31, 40, 0),
+    temp: Rgb565::new(31, 40, 0),
+    hum: Rgb565::new(28, 44, 6),
+    good: Rgb565::new(24, 48, 0),
+    fair: Rgb565::new(31, 52, 0),
+    poor: Rgb565::new(31, 32, 0),
+    bad: Rgb565::new(31, 8, 0),
+};
+
+// Colours for each preset (the enum itself lives in options.rs).
+impl ThemePreset {
+    pub fn theme(self) -> &'static Theme {
+        match self {
+            Self::Dark => &THEME_DARK,
+            Self::HighContrast => &THEME_HIGH_CONTRAST,
+            Self::Amber => &THEME_AMBER,
+        }
+    }
+}
+
+// Reading shown by the big layout; the main loop cycles through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BigPage {
+    Co2,
+    Temperature,
+    Humidity,
+}
+
+impl BigPage {
+    // Next page in the cycle; without a temperature/humidity source the CO2
+    // page stays up.
+    pub fn next(self, presence: SensorPresence) -> Self {
+        match self {
+            Self::Co2 if presence.sht31 => Self::Temperature,
+            Self::Temperature => Self::Humidity,
+            _ => Self::Co2,
+        }
+    }
+}
+
+const LOG_LINE_H: i32 = 14;
 
 struct LinearRgb565Slice<'a> {
     data: &'a mut [Rgb565],
@@ -75,30 +165,88 @@ pub fn co2_card_rect() -> Rectangle {
     left
 }
 
-pub fn render_ui_mock1(
-    frame: &mut [Rgb565],
-    temperature_c: Option<f32>,
-    humidity_pct: Option<u8>,
-    co2_ppm: Option<u16>,
-    co2_error: bool,
-    zero_mode: bool,
-    battery_v: Option<f32>,
-) -> Result<()> {
+// Which sensors have produced a reading since boot (or since the temp/humidity
+// source changed). Until then a card shows "..." (initializing) rather than
+// "n/a", so a fresh boot does not look like a fault.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SensorsRead {
+    pub co2: bool,
+    pub temp_hum: bool,
+}
+
+// Everything the main screen shows; built by the main loop on each redraw.
+#[derive(Debug, Clone, Copy)]
+pub struct UiState {
+    pub temperature_c: Option<f32>,
+    pub humidity_pct: Option<u8>,
+    pub co2_ppm: Option<u16>,
+    pub co2_error: bool,
+    // Zero calibration in progress; the CO2 card shows "CAL".
+    pub calibrating: bool,
+    pub battery_v: Option<f32>,
+    pub presence: SensorPresence,
+    pub co2_alarm_ppm: u16,
+    pub co2_trend: Co2Trend,
+    // Debounced quality band (see Co2QualityHysteresis); None until the first reading.
+    pub co2_quality: Option<AirQuality>,
+    // Today's (min, max) shown under the temperature/humidity values.
+    pub temp_range: Option<(f32, f32)>,
+    pub humidity_range: Option<(f32, f32)>,
+    // Humidity has been high long enough to risk mold (see stats::MoldRisk).
+    pub mold_risk: bool,
+    // Room looks occupied from the CO2 trend (see stats::Occupancy); shows a
+    // person icon.
+    pub occupied: bool,
+    // (elapsed, total) while the CO2 sensor is still warming up after boot.
+    pub co2_warmup: Option<(Duration, Duration)>,
+    // Highest CO2 since the last reset (see stats::Co2Peak).
+    pub co2_peak: Option<u16>,
+    pub temp_unit: TempUnit,
+    // Decimals on the temperature card and its range line: 0 or 1.
+    pub temp_decimals: u8,
+    pub sensors_read: SensorsRead,
+    // Data hold: the screen is frozen on a snapshot; the CO2 card shows "HOLD".
+    pub hold: bool,
+}
+
+pub fn render_ui_mock1(frame: &mut [Rgb565], ui: &UiState, theme: &Theme) -> Result<()> {
+    let UiState {
+        temperature_c,
+        humidity_pct,
+        co2_ppm,
+        co2_error,
+        calibrating,
+        battery_v,
+        presence,
+        co2_alarm_ppm,
+        co2_trend,
+        co2_quality,
+        temp_range,
+        humidity_range,
+        mold_risk,
+        occupied,
+        co2_warmup,
+        co2_peak,
+        temp_unit,
+        temp_decimals,
+        sensors_read,
+        hold,
+    } = *ui;
     let view_w = LCD_VIEW_W;
     let view_h = LCD_VIEW_H;
     let backend = LinearRgb565Slice::new(frame);
     let mut fb = embedded_graphics_framebuf::FrameBuf::<Rgb565, _>::new(backend, view_w, view_h);
 
-    fb.clear(COLOR_BG)?;
+    fb.clear(theme.bg)?;
 
     let frame_style = PrimitiveStyleBuilder::new()
-        .stroke_color(COLOR_FRAME)
+        .stroke_color(theme.frame)
         .stroke_width(3)
         .build();
     let card_style = PrimitiveStyleBuilder::new()
-        .stroke_color(COLOR_CARD)
+        .stroke_color(theme.card)
         .stroke_width(2)
-        .fill_color(COLOR_CARD)
+        .fill_color(theme.card)
         .build();
 
     let frame_rect = Rectangle::new(
@@ -115,22 +263,35 @@ pub fn render_ui_mock1(
     RoundedRectangle::new(panel_temp, card_radii).into_styled(card_style).draw(&mut fb)?;
     RoundedRectangle::new(panel_hum, card_radii).into_styled(card_style).draw(&mut fb)?;
 
-    let (co2_value_text, co2_value_color, status_text_opt, status_color) = if zero_mode {
-        ("ZERO".to_string(), COLOR_CO2_ZERO, None, COLOR_CO2_ZERO)
+    let gauge_fraction = co2_ppm
+        .filter(|_| presence.co2 && !co2_error && !calibrating)
+        .map(|ppm| (ppm as f32 / co2_gauge_max_ppm() as f32).min(1.0));
+    let (co2_value_text, co2_value_color, status_text_opt, status_color) = if calibrating {
+        ("CAL".to_string(), theme.calibrating, None, theme.calibrating)
+    } else if !presence.co2 {
+        ("--".to_string(), theme.label, None, theme.label)
     } else if co2_error {
-        ("ERR".to_string(), COLOR_BAD, None, COLOR_BAD)
+        ("ERR".to_string(), theme.bad, None, theme.bad)
     } else if let Some(ppm) = co2_ppm {
-        let (status_text, status_color) = co2_status(ppm);
+        let (status_text, status_color) = if ppm >= co2_alarm_ppm {
+            ("ALARM", theme.bad)
+        } else {
+            co2_status(co2_quality.unwrap_or_else(|| AirQuality::from_co2(ppm)), theme)
+        };
         (format!("{}", ppm), status_color, Some(status_text), status_color)
+    } else if sensors_read.co2 {
+        ("n/a".to_string(), theme.label, None, theme.label)
     } else {
-        ("...".to_string(), COLOR_LABEL, None, COLOR_LABEL)
+        ("...".to_string(), theme.label, None, theme.label)
     };
 
-    let style_label = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, COLOR_LABEL);
-    let style_co2_value = U8g2TextStyle::new(fonts::u8g2_font_fub35_tf, co2_value_color);
-    let style_temp_value = U8g2TextStyle::new(fonts::u8g2_font_helvB24_tf, COLOR_TEMP);
-    let style_hum_value = U8g2TextStyle::new(fonts::u8g2_font_helvB24_tf, COLOR_HUM);
+    let style_co2_value = U8g2TextStyle::new(fonts::u8g2_font_fub20_tf, co2_value_color);
+    let style_unit = U8g2TextStyle::new(fonts::u8g2_font_helvR08_tf, theme.label);
+    let style_temp_value = U8g2TextStyle::new(fonts::u8g2_font_helvB24_tf, theme.temp);
+    let style_hum_value = U8g2TextStyle::new(fonts::u8g2_font_helvB24_tf, theme.hum);
     let style_status = U8g2TextStyle::new(fonts::u8g2_font_helvB12_tf, status_color);
+    let style_range = U8g2TextStyle::new(fonts::u8g2_font_helvR08_tf, theme.label);
+    const RANGE_OFFSET_Y: i32 = 22;
     let center_text = TextStyleBuilder::new()
         .alignment(Alignment::Center)
         .baseline(Baseline::Middle)
@@ -140,7 +301,7 @@ pub fn render_ui_mock1(
         .baseline(Baseline::Top)
         .build();
 
-    let style_label_battery = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, COLOR_LABEL);
+    let style_label_battery = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, theme.label);
     let battery_text = match battery_v {
         Some(voltage) => format!("BAT {:.2}V", voltage),
         None => "BAT --.-V".to_string(),
@@ -155,29 +316,48 @@ pub fn render_ui_mock1(
     let left_center_x = panel_co.center().x;
     let left_top = panel_co.top_left;
     let left_h = panel_co.size.height as i32;
-    let co2_val_y = left_top.y + (left_h * 40) / 100;
-    let ppm_y = left_top.y + (left_h * 68) / 100;
-    let status_y = left_top.y + (left_h * 82) / 100;
+    let gauge_center = Point::new(left_center_x, left_top.y + (left_h * 39) / 100);
+    let co2_val_y = gauge_center.y - 4;
+    let ppm_y = gauge_center.y + 18;
+    let status_y = left_top.y + (left_h * 79) / 100;
+    let peak_y = left_top.y + (left_h * 91) / 100;
 
-    Text::with_text_style(
-        &co2_value_text,
-        Point::new(left_center_x, co2_val_y),
-        style_co2_value,
-        center_text,
-    )
-    .draw(&mut fb)?;
+    if let Some((elapsed, total)) = co2_warmup.filter(|_| presence.co2 && !calibrating) {
+        draw_warmup(&mut fb, panel_co, elapsed, total, theme)?;
+    } else {
+        draw_gauge_ring(&mut fb, gauge_center, gauge_fraction, status_color, theme)?;
+        Text::with_text_style(
+            &co2_value_text,
+            Point::new(left_center_x, co2_val_y),
+            style_co2_value,
+            center_text,
+        )
+        .draw(&mut fb)?;
+    }
 
-    if let Some(status_text) = status_text_opt {
-        Text::with_text_style("ppm", Point::new(left_center_x, ppm_y), style_label, center_text)
+    if let Some(status_text) = status_text_opt.filter(|_| co2_warmup.is_none()) {
+        Text::with_text_style("ppm", Point::new(left_center_x, ppm_y), style_unit, center_text)
             .draw(&mut fb)?;
         Text::with_text_style(status_text, Point::new(left_center_x, status_y), style_status, center_text)
             .draw(&mut fb)?;
+        draw_trend_arrow(&mut fb, Point::new(left_center_x + 40, status_y), co2_trend, theme)?;
+    }
+    if let Some(peak) = co2_peak.filter(|_| presence.co2 && co2_warmup.is_none()) {
+        Text::with_text_style(
+            &format!("peak: {}", peak),
+            Point::new(left_center_x, peak_y),
+            style_range.clone(),
+            center_text,
+        )
+        .draw(&mut fb)?;
     }
 
     let rt_center_x = panel_temp.center().x;
     let rt_center_y = panel_temp.center().y;
     let temp_text = match temperature_c {
-        Some(value) => format!("{:.1}°C", value),
+        _ if !presence.sht31 => "--".to_string(),
+        Some(value) => temp_unit.format(value, temp_decimals),
+        None if !sensors_read.temp_hum => "...".to_string(),
         None => "n/a".to_string(),
     };
     Text::with_text_style(
@@ -187,11 +367,29 @@ pub fn render_ui_mock1(
         center_text,
     )
     .draw(&mut fb)?;
+    if let Some((min, max)) = temp_range.filter(|_| presence.sht31) {
+        Text::with_text_style(
+            &format!(
+                "{:.*} - {:.*}{}",
+                usize::from(temp_decimals),
+                temp_unit.convert(min),
+                usize::from(temp_decimals),
+                temp_unit.convert(max),
+                temp_unit.symbol()
+            ),
+            Point::new(rt_center_x, rt_center_y + RANGE_OFFSET_Y),
+            style_range.clone(),
+            center_text,
+        )
+        .draw(&mut fb)?;
+    }
 
     let rb_center_x = panel_hum.center().x;
     let rb_center_y = panel_hum.center().y;
     let hum_text = match humidity_pct {
+        _ if !presence.sht31 => "--".to_string(),
         Some(value) => format!("{}%", value),
+        None if !sensors_read.temp_hum => "...".to_string(),
         None => "n/a".to_string(),
     };
     Text::with_text_style(
@@ -201,18 +399,536 @@ pub fn render_ui_mock1(
         center_text,
     )
     .draw(&mut fb)?;
+    if let Some((min, max)) = humidity_range.filter(|_| presence.sht31) {
+        Text::with_text_style(
+            &format!("{:.0} - {:.0}%", min, max),
+            Point::new(rb_center_x, rb_center_y + RANGE_OFFSET_Y),
+            style_range,
+            center_text,
+        )
+        .draw(&mut fb)?;
+    }
+    if hold {
+        let style_hold = U8g2TextStyle::new(fonts::u8g2_font_helvB08_tf, theme.calibrating);
+        let left_top_text = TextStyleBuilder::new()
+            .alignment(Alignment::Left)
+            .baseline(Baseline::Top)
+            .build();
+        Text::with_text_style(
+            "HOLD",
+            panel_co.top_left + Point::new(8, 6),
+            style_hold,
+            left_top_text,
+        )
+        .draw(&mut fb)?;
+    }
+    if occupied && presence.co2 {
+        let top_right = panel_co.top_left + Point::new(panel_co.size.width as i32, 0);
+        draw_person_icon(&mut fb, top_right + Point::new(-14, 8), theme.label)?;
+    }
+
+    if mold_risk && presence.sht31 {
+        let style_mold = U8g2TextStyle::new(fonts::u8g2_font_helvB08_tf, theme.poor);
+        Text::with_text_style(
+            "MOLD RISK",
+            Point::new(rb_center_x, rb_center_y - RANGE_OFFSET_Y),
+            style_mold,
+            center_text,
+        )
+        .draw(&mut fb)?;
+    }
 
     Ok(())
 }
 
-fn co2_status(co2_ppm: u16) -> (&'static str, Rgb565) {
-    if co2_ppm < 600 {
-        ("Good", COLOR_GOOD)
-    } else if co2_ppm <= 1000 {
-        ("Fair", COLOR_FAIR)
-    } else if co2_ppm <= 1500 {
-        ("Poor", COLOR_POOR)
+// Big layout: one reading filling the screen, with its name above and (for
+// CO2) the quality band below. Dots at the bottom show which page is up.
+pub fn render_big_number(
+    frame: &mut [Rgb565],
+    ui: &UiState,
+    page: BigPage,
+    theme: &Theme,
+) -> Result<()> {
+    let view_w = LCD_VIEW_W;
+    let view_h = LCD_VIEW_H;
+    let backend = LinearRgb565Slice::new(frame);
+    let mut fb = embedded_graphics_framebuf::FrameBuf::<Rgb565, _>::new(backend, view_w, view_h);
+
+    fb.clear(theme.bg)?;
+
+    let frame_style = PrimitiveStyleBuilder::new()
+        .stroke_color(theme.frame)
+        .stroke_width(3)
+        .build();
+    let frame_rect = Rectangle::new(
+        Point::new(4, 4),
+        Size::new((view_w - 8) as u32, (view_h - 8) as u32),
+    );
+    RoundedRectangle::with_equal_corners(frame_rect, Size::new(12, 12))
+        .into_styled(frame_style)
+        .draw(&mut fb)?;
+
+    let center_text = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+    let left_top_text = TextStyleBuilder::new()
+        .alignment(Alignment::Left)
+        .baseline(Baseline::Top)
+        .build();
+    let right_top_text = TextStyleBuilder::new()
+        .alignment(Alignment::Right)
+        .baseline(Baseline::Top)
+        .build();
+    let center_x = view_w as i32 / 2;
+    let center_y = view_h as i32 / 2;
+
+    let missing = |read: bool| if read { "n/a" } else { "..." };
+    let (label, value_text, value_color, status) = match page {
+        BigPage::Co2 => {
+            let (text, color, status) = if ui.calibrating {
+                ("CAL".to_string(), theme.calibrating, None)
+            } else if !ui.presence.co2 {
+                ("--".to_string(), theme.label, None)
+            } else if ui.co2_error {
+                ("ERR".to_string(), theme.bad, None)
+            } else if let Some((elapsed, total)) = ui.co2_warmup {
+                let left = total.saturating_sub(elapsed).as_secs();
+                let status = format!("warming up, {}:{:02} left", left / 60, left % 60);
+                ("...".to_string(), theme.label, Some((status, theme.label)))
+            } else if let Some(ppm) = ui.co2_ppm {
+                let (status, color) = if ppm >= ui.co2_alarm_ppm {
+                    ("ALARM", theme.bad)
+                } else {
+                    co2_status(
+                        ui.co2_quality.unwrap_or_else(|| AirQuality::from_co2(ppm)),
+                        theme,
+                    )
+                };
+                (ppm.to_string(), color, Some((status.to_string(), color)))
+            } else {
+                (missing(ui.sensors_read.co2).to_string(), theme.label, None)
+            };
+            ("CO2 ppm", text, color, status)
+        }
+        BigPage::Temperature => {
+            let text = match ui.temperature_c {
+                _ if !ui.presence.sht31 => "--".to_string(),
+                Some(value) => ui.temp_unit.format(value, ui.temp_decimals),
+                None => missing(ui.sensors_read.temp_hum).to_string(),
+            };
+            ("Temperature", text, theme.temp, None)
+        }
+        BigPage::Humidity => {
+            let text = match ui.humidity_pct {
+                _ if !ui.presence.sht31 => "--".to_string(),
+                Some(value) => format!("{}%", value),
+                None => missing(ui.sensors_read.temp_hum).to_string(),
+            };
+            let status = (ui.mold_risk && ui.presence.sht31)
+                .then(|| ("MOLD RISK".to_string(), theme.poor));
+            ("Humidity", text, theme.hum, status)
+        }
+    };
+
+    let style_label = U8g2TextStyle::new(fonts::u8g2_font_helvB14_tf, theme.label);
+    Text::with_text_style(label, Point::new(16, 14), style_label, left_top_text)
+        .draw(&mut fb)?;
+
+    let style_battery = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, theme.label);
+    let battery_text = match ui.battery_v {
+        Some(voltage) => format!("BAT {:.2}V", voltage),
+        None => "BAT --.-V".to_string(),
+    };
+    let battery_pos = Point::new(
+        frame_rect.top_left.x + frame_rect.size.width as i32 - 6,
+        frame_rect.top_left.y + 6,
+    );
+    Text::with_text_style(&battery_text, battery_pos, style_battery, right_top_text)
+        .draw(&mut fb)?;
+    if ui.hold {
+        let style_hold = U8g2TextStyle::new(fonts::u8g2_font_helvB10_tf, theme.calibrating);
+        Text::with_text_style(
+            "HOLD",
+            battery_pos + Point::new(0, 18),
+            style_hold,
+            right_top_text,
+        )
+        .draw(&mut fb)?;
+    }
+
+    let style_value = U8g2TextStyle::new(fonts::u8g2_font_fub42_tf, value_color);
+    Text::with_text_style(
+        &value_text,
+        Point::new(center_x, center_y + 2),
+        style_value,
+        center_text,
+    )
+    .draw(&mut fb)?;
+
+    if let Some((status, color)) = status {
+        let style_status = U8g2TextStyle::new(fonts::u8g2_font_helvB14_tf, color);
+        Text::with_text_style(&status, Point::new(center_x, center_y + 44), style_status, center_text)
+            .draw(&mut fb)?;
+    }
+
+    if ui.occupied && ui.presence.co2 {
+        draw_person_icon(&mut fb, Point::new(20, view_h as i32 - 30), theme.label)?;
+    }
+
+    if ui.presence.sht31 {
+        const DOT_D: u32 = 6;
+        const DOT_GAP: i32 = 12;
+        let dots_y = view_h as i32 - 18;
+        for (i, dot) in [BigPage::Co2, BigPage::Temperature, BigPage::Humidity]
+            .into_iter()
+            .enumerate()
+        {
+            let style = if dot == page {
+                PrimitiveStyle::with_fill(theme.label)
+            } else {
+                PrimitiveStyle::with_stroke(theme.label, 1)
+            };
+            Circle::with_center(Point::new(center_x + (i as i32 - 1) * DOT_GAP, dots_y), DOT_D)
+                .into_styled(style)
+                .draw(&mut fb)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Caption, progress bar and time left in the CO2 card while the sensor warms up,
+// so a cold boot does not look like a dead sensor.
+fn draw_warmup<D>(
+    target: &mut D,
+    card: Rectangle,
+    elapsed: Duration,
+    total: Duration,
+    theme: &Theme,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let center_x = card.center().x;
+    let top = card.top_left.y;
+    let h = card.size.height as i32;
+    let center_text = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+    let style_caption = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, theme.label);
+
+    Text::with_text_style("Warming up", Point::new(center_x, top + (h * 28) / 100), style_caption.clone(), center_text)
+        .draw(target)?;
+    Text::with_text_style("CO2 sensor", Point::new(center_x, top + (h * 40) / 100), style_caption.clone(), center_text)
+        .draw(target)?;
+
+    let fraction = if total.is_zero() {
+        1.0
     } else {
-        ("Bad", COLOR_BAD)
+        (elapsed.as_secs_f32() / total.as_secs_f32()).clamp(0.0, 1.0)
+    };
+    let bar_w = card.size.width.saturating_sub(32);
+    let bar = Rectangle::new(
+        Point::new(center_x - bar_w as i32 / 2, top + (h * 55) / 100),
+        Size::new(bar_w, 12),
+    );
+    bar.into_styled(PrimitiveStyle::with_stroke(theme.label, 1)).draw(target)?;
+    let fill_w = ((bar_w.saturating_sub(4)) as f32 * fraction).round() as u32;
+    if fill_w > 0 {
+        Rectangle::new(bar.top_left + Point::new(2, 2), Size::new(fill_w, 8))
+            .into_styled(PrimitiveStyle::with_fill(theme.calibrating))
+            .draw(target)?;
+    }
+
+    let left = total.saturating_sub(elapsed).as_secs();
+    Text::with_text_style(
+        &format!("{}:{:02} left", left / 60, left % 60),
+        Point::new(center_x, top + (h * 76) / 100),
+        style_caption,
+        center_text,
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+const CO2_GAUGE_MAX_PPM_DEFAULT: u16 = 2000;
+const CO2_GAUGE_MAX_PPM_ENV: Option<&str> = option_env!("CO2_GAUGE_MAX_PPM");
+
+// Reading that fills the gauge ring; anything above it shows a full ring.
+pub fn co2_gauge_max_ppm() -> u16 {
+    CO2_GAUGE_MAX_PPM_ENV
+        .and_then(|v| v.trim().parse::<u16>().ok())
+        .filter(|ppm| *ppm > 0)
+        .unwrap_or(CO2_GAUGE_MAX_PPM_DEFAULT)
+}
+
+const GAUGE_DIAMETER: u32 = 96;
+const GAUGE_STROKE: u32 = 8;
+
+// Ring around the CO2 value: a dim full-circle track with an arc from 12 o'clock
+// clockwise covering `fraction` (0..=1) of it. `None` draws only the track.
+fn draw_gauge_ring<D>(
+    target: &mut D,
+    center: Point,
+    fraction: Option<f32>,
+    color: Rgb565,
+    theme: &Theme,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    Circle::with_center(center, GAUGE_DIAMETER)
+        .into_styled(PrimitiveStyle::with_stroke(theme.frame, GAUGE_STROKE / 2))
+        .draw(target)?;
+    let sweep = fraction.unwrap_or(0.0).clamp(0.0, 1.0) * 360.0;
+    if sweep > 0.0 {
+        Arc::with_center(center, GAUGE_DIAMETER, (-90.0).deg(), sweep.deg())
+            .into_styled(PrimitiveStyle::with_stroke(color, GAUGE_STROKE))
+            .draw(target)?;
+    }
+    Ok(())
+}
+
+// Small ▲/▼/▬ glyph centered on `center`, drawn as primitives since the
+// u8g2 fonts in use have no arrow glyphs.
+fn draw_trend_arrow<D>(
+    target: &mut D,
+    center: Point,
+    trend: Co2Trend,
+    theme: &Theme,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    const HALF: i32 = 5;
+    match trend {
+        Co2Trend::Rising => Triangle::new(
+            center + Point::new(0, -HALF),
+            center + Point::new(-HALF, HALF),
+            center + Point::new(HALF, HALF),
+        )
+        .into_styled(PrimitiveStyle::with_fill(theme.poor))
+        .draw(target),
+        Co2Trend::Falling => Triangle::new(
+            center + Point::new(-HALF, -HALF),
+            center + Point::new(HALF, -HALF),
+            center + Point::new(0, HALF),
+        )
+        .into_styled(PrimitiveStyle::with_fill(theme.good))
+        .draw(target),
+        Co2Trend::Steady => Rectangle::new(center - Point::new(HALF, 1), Size::new(2 * HALF as u32 + 1, 3))
+            .into_styled(PrimitiveStyle::with_fill(theme.label))
+            .draw(target),
+    }
+}
+
+// Head and shoulders, 9 px wide and 12 px tall, hanging from `top_center`.
+fn draw_person_icon<D>(target: &mut D, top_center: Point, color: Rgb565) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let style = PrimitiveStyle::with_fill(color);
+    Circle::with_center(top_center + Point::new(0, 2), 5)
+        .into_styled(style)
+        .draw(target)?;
+    let body = Rectangle::new(top_center + Point::new(-4, 6), Size::new(9, 6));
+    RoundedRectangle::new(
+        body,
+        CornerRadii {
+            top_left: Size::new(4, 4),
+            top_right: Size::new(4, 4),
+            bottom_right: Size::zero(),
+            bottom_left: Size::zero(),
+        },
+    )
+    .into_styled(style)
+    .draw(target)
+}
+
+pub fn log_page_lines() -> usize {
+    ((LCD_VIEW_H as i32 - 2 * 12 - LOG_LINE_H) / LOG_LINE_H).max(1) as usize
+}
+
+pub fn render_log_page(frame: &mut [Rgb565], entries: &[LogEntry], theme: &Theme) -> Result<()> {
+    let view_w = LCD_VIEW_W;
+    let view_h = LCD_VIEW_H;
+    let backend = LinearRgb565Slice::new(frame);
+    let mut fb = embedded_graphics_framebuf::FrameBuf::<Rgb565, _>::new(backend, view_w, view_h);
+
+    fb.clear(theme.bg)?;
+
+    let frame_style = PrimitiveStyleBuilder::new()
+        .stroke_color(theme.frame)
+        .stroke_width(3)
+        .build();
+    let frame_rect = Rectangle::new(
+        Point::new(4, 4),
+        Size::new((view_w - 8) as u32, (view_h - 8) as u32),
+    );
+    RoundedRectangle::with_equal_corners(frame_rect, Size::new(12, 12))
+        .into_styled(frame_style)
+        .draw(&mut fb)?;
+
+    let left_top_text = TextStyleBuilder::new()
+        .alignment(Alignment::Left)
+        .baseline(Baseline::Top)
+        .build();
+    let style_title = U8g2TextStyle::new(fonts::u8g2_font_helvB10_tf, theme.label);
+    Text::with_text_style("Event log", Point::new(14, 12), style_title, left_top_text)
+        .draw(&mut fb)?;
+
+    if entries.is_empty() {
+        let style_empty = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, theme.label);
+        Text::with_text_style(
+            "No events",
+            Point::new(14, 12 + LOG_LINE_H),
+            style_empty,
+            left_top_text,
+        )
+        .draw(&mut fb)?;
+        return Ok(());
     }
+
+    for (i, entry) in entries.iter().take(log_page_lines()).enumerate() {
+        let color = match entry.level {
+            log::Level::Error => theme.bad,
+            log::Level::Warn => theme.poor,
+            _ => theme.label,
+        };
+        let style = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, color);
+        let line = format!("{:>6}s {}", entry.uptime_s, entry.msg);
+        let y = 12 + LOG_LINE_H * (i as i32 + 1);
+        Text::with_text_style(&line, Point::new(14, y), style, left_top_text).draw(&mut fb)?;
+    }
+
+    Ok(())
+}
+
+// Full-screen message (e.g. the low battery shutdown): a title in the alarm
+// colour over one line of detail.
+pub fn render_notice(frame: &mut [Rgb565], title: &str, detail: &str, theme: &Theme) -> Result<()> {
+    let view_w = LCD_VIEW_W;
+    let view_h = LCD_VIEW_H;
+    let backend = LinearRgb565Slice::new(frame);
+    let mut fb = embedded_graphics_framebuf::FrameBuf::<Rgb565, _>::new(backend, view_w, view_h);
+
+    fb.clear(theme.bg)?;
+
+    let frame_style = PrimitiveStyleBuilder::new()
+        .stroke_color(theme.bad)
+        .stroke_width(3)
+        .build();
+    let frame_rect = Rectangle::new(
+        Point::new(4, 4),
+        Size::new((view_w - 8) as u32, (view_h - 8) as u32),
+    );
+    RoundedRectangle::with_equal_corners(frame_rect, Size::new(12, 12))
+        .into_styled(frame_style)
+        .draw(&mut fb)?;
+
+    let center_text = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+    let center_x = view_w as i32 / 2;
+    let center_y = view_h as i32 / 2;
+    let style_title = U8g2TextStyle::new(fonts::u8g2_font_helvB24_tf, theme.bad);
+    Text::with_text_style(title, Point::new(center_x, center_y - 14), style_title, center_text)
+        .draw(&mut fb)?;
+    let style_detail = U8g2TextStyle::new(fonts::u8g2_font_helvB12_tf, theme.label);
+    Text::with_text_style(detail, Point::new(center_x, center_y + 22), style_detail, center_text)
+        .draw(&mut fb)?;
+
+    Ok(())
+}
+
+// CO2 quality bands shared by the CO2 card and the status LED, ordered from
+// best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AirQuality {
+    Good,
+    Fair,
+    Poor,
+    Bad,
+}
+
+impl AirQuality {
+    pub fn from_co2(co2_ppm: u16) -> Self {
+        if co2_ppm < 600 {
+            Self::Good
+        } else if co2_ppm <= 1000 {
+            Self::Fair
+        } else if co2_ppm <= 1500 {
+            Self::Poor
+        } else {
+            Self::Bad
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Good => "Good",
+            Self::Fair => "Fair",
+            Self::Poor => "Poor",
+            Self::Bad => "Bad",
+        }
+    }
+}
+
+const CO2_HYSTERESIS_PPM_DEFAULT: u16 = 50;
+const CO2_HYSTERESIS_PPM_ENV: Option<&str> = option_env!("CO2_HYSTERESIS_PPM");
+
+// Keeps the quality band from flickering when CO2 hovers at a threshold: the
+// band only moves once the reading is `margin` ppm past the boundary.
+#[derive(Debug)]
+pub struct Co2QualityHysteresis {
+    margin: u16,
+    current: Option<AirQuality>,
+}
+
+impl Co2QualityHysteresis {
+    pub fn from_env() -> Self {
+        let margin = CO2_HYSTERESIS_PPM_ENV
+            .and_then(|v| v.trim().parse::<u16>().ok())
+            .unwrap_or(CO2_HYSTERESIS_PPM_DEFAULT);
+        Self {
+            margin,
+            current: None,
+        }
+    }
+
+    pub fn update(&mut self, co2_ppm: u16) -> AirQuality {
+        let next = match self.current {
+            None => AirQuality::from_co2(co2_ppm),
+            Some(current) => {
+                let worse = AirQuality::from_co2(co2_ppm.saturating_sub(self.margin));
+                let better = AirQuality::from_co2(co2_ppm.saturating_add(self.margin));
+                if worse > current {
+                    worse
+                } else if better < current {
+                    better
+                } else {
+                    current
+                }
+            }
+        };
+        self.current = Some(next);
+        next
+    }
+
+    pub fn current(&self) -> Option<AirQuality> {
+        self.current
+    }
+}
+
+fn co2_status(quality: AirQuality, theme: &Theme) -> (&'static str, Rgb565) {
+    let color = match quality {
+        AirQuality::Good => theme.good,
+        AirQuality::Fair => theme.fair,
+        AirQuality::Poor => theme.poor,
+        AirQuality::Bad => theme.bad,
+    };
+    (quality.label(), color)
 }
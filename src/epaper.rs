@@ -0,0 +1,170 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use esp_idf_hal::gpio::{AnyIOPin, Input, Output, PinDriver};
+use esp_idf_hal::prelude::*;
+use esp_idf_hal::spi::config::Config as SpiDeviceConfig;
+use esp_idf_hal::spi::{SpiDeviceDriver, SpiDriver};
+use log::{info, warn};
+
+use crate::epaper_ui::{MonoFrame, EPAPER_VIEW_H, EPAPER_VIEW_W};
+
+// A full refresh flashes the panel for ~2 s, so it is redrawn at most this
+// often (and only when the picture changed).
+const EPAPER_INTERVAL_S_DEFAULT: u64 = 300;
+const EPAPER_INTERVAL_S_ENV: Option<&str> = option_env!("EPAPER_INTERVAL_S");
+// Reset/init normally clears BUSY within milliseconds, a refresh within ~3 s.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+// Controller RAM is portrait: 250 gate lines of 122 source pixels (16 bytes).
+const RAM_ROWS: usize = EPAPER_VIEW_W;
+const RAM_ROW_BYTES: usize = EPAPER_VIEW_H.div_ceil(8);
+
+// SSD1680 e-paper (2.13", 250x122) mirroring the main readings. Between
+// refreshes the controller is in deep sleep and the image stays without power;
+// a refresh is started by `show()` and finished by `poll()`, so the main loop
+// never waits on the panel.
+pub struct Epaper {
+    spi_dev: SpiDeviceDriver<'static, &'static SpiDriver<'static>>,
+    dc: PinDriver<'static, AnyIOPin, Output>,
+    rst: PinDriver<'static, AnyIOPin, Output>,
+    busy: PinDriver<'static, AnyIOPin, Input>,
+    interval: Duration,
+    last_show: Option<Instant>,
+    // RAM image of the last refresh, to skip one that would change nothing.
+    shown: Option<Vec<u8>>,
+    refresh_started: Option<Instant>,
+}
+
+impl Epaper {
+    pub fn new(
+        spi_bus: &'static SpiDriver<'static>,
+        cs: AnyIOPin,
+        dc: AnyIOPin,
+        rst: AnyIOPin,
+        busy: AnyIOPin,
+    ) -> Result<Self> {
+        let spi_dev_cfg = SpiDeviceConfig::new().baudrate(4.MHz().into());
+        let interval = EPAPER_INTERVAL_S_ENV
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(EPAPER_INTERVAL_S_DEFAULT);
+        let mut epaper = Self {
+            spi_dev: SpiDeviceDriver::new(spi_bus, Some(cs), &spi_dev_cfg)?,
+            dc: PinDriver::output(dc)?,
+            rst: PinDriver::output(rst)?,
+            busy: PinDriver::input(busy)?,
+            interval: Duration::from_secs(interval),
+            last_show: None,
+            shown: None,
+            refresh_started: None,
+        };
+        // Fail here rather than on the first refresh if the panel is not wired.
+        epaper.wake()?;
+        epaper.cmd(0x10, &[0x01])?;
+        info!("E-paper ready, refresh every {} s", interval);
+        Ok(epaper)
+    }
+
+    // Whether a new frame should be rendered and passed to `show()`.
+    pub fn due(&self) -> bool {
+        self.refresh_started.is_none()
+            && self
+                .last_show
+                .map_or(true, |at| at.elapsed() >= self.interval)
+    }
+
+    // Start a full refresh with `frame`, unless the panel already shows it.
+    pub fn show(&mut self, frame: &MonoFrame) -> Result<()> {
+        self.last_show = Some(Instant::now());
+        let ram = panel_ram(frame);
+        if self.shown.as_ref() == Some(&ram) {
+            return Ok(());
+        }
+        self.wake()?;
+        self.cmd(0x24, &ram)?;
+        // Display update control 2: full refresh with the OTP waveform.
+        self.cmd(0x22, &[0xF7])?;
+        self.cmd(0x20, &[])?;
+        self.refresh_started = Some(Instant::now());
+        self.shown = Some(ram);
+        Ok(())
+    }
+
+    // Put the controller back into deep sleep once the running refresh is done.
+    pub fn poll(&mut self) -> Result<()> {
+        let Some(started) = self.refresh_started else {
+            return Ok(());
+        };
+        if self.busy.is_high() {
+            if started.elapsed() < BUSY_TIMEOUT {
+                return Ok(());
+            }
+            warn!("E-paper refresh timed out");
+            // Redraw on the next interval even if the picture is unchanged.
+            self.shown = None;
+        }
+        self.refresh_started = None;
+        self.cmd(0x10, &[0x01])
+    }
+
+    // Deep sleep is only left through a hardware reset, which also drops the
+    // register setup.
+    fn wake(&mut self) -> Result<()> {
+        self.rst.set_low()?;
+        thread::sleep(Duration::from_millis(10));
+        self.rst.set_high()?;
+        thread::sleep(Duration::from_millis(10));
+        self.wait_idle()?;
+        self.cmd(0x12, &[])?; // SWRESET
+        self.wait_idle()?;
+
+        let last_row = (RAM_ROWS - 1) as u16;
+        self.cmd(0x01, &[last_row as u8, (last_row >> 8) as u8, 0x00])?; // gate lines
+        self.cmd(0x11, &[0x03])?; // data entry: X then Y increment
+        self.cmd(0x44, &[0x00, (RAM_ROW_BYTES - 1) as u8])?;
+        self.cmd(0x45, &[0x00, 0x00, last_row as u8, (last_row >> 8) as u8])?;
+        self.cmd(0x3C, &[0x05])?; // border: white
+        self.cmd(0x18, &[0x80])?; // internal temperature sensor
+        self.cmd(0x4E, &[0x00])?;
+        self.cmd(0x4F, &[0x00, 0x00])?;
+        Ok(())
+    }
+
+    fn wait_idle(&mut self) -> Result<()> {
+        let started = Instant::now();
+        while self.busy.is_high() {
+            if started.elapsed() >= BUSY_TIMEOUT {
+                return Err(anyhow!("e-paper BUSY stuck high"));
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        Ok(())
+    }
+
+    fn cmd(&mut self, cmd: u8, data: &[u8]) -> Result<()> {
+        // D/C low selects command phase, high the data phase.
+        self.dc.set_low()?;
+        self.spi_dev.write(&[cmd])?;
+        if !data.is_empty() {
+            self.dc.set_high()?;
+            for chunk in data.chunks(4096) {
+                self.spi_dev.write(chunk)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rotate the landscape frame into the portrait controller RAM (1 = white),
+// for a panel mounted with its flex cable on the right.
+fn panel_ram(frame: &MonoFrame) -> Vec<u8> {
+    let mut ram = vec![0xFF; RAM_ROW_BYTES * RAM_ROWS];
+    for row in 0..RAM_ROWS {
+        for source in 0..EPAPER_VIEW_H {
+            if frame.is_black(row, EPAPER_VIEW_H - 1 - source) {
+                ram[row * RAM_ROW_BYTES + source / 8] &= !(0x80 >> (source % 8));
+            }
+        }
+    }
+    ram
+}
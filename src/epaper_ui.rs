@@ -0,0 +1,164 @@
+// Monochrome layout for the optional e-paper mirror (`epaper` feature, see
+// epaper.rs). Fed the same UiState as the LCD, but drawn for a 1-bit panel
+// that is refreshed only every few minutes: big CO2 number, quality band,
+// peak and the temperature/humidity line, no colour or animation.
+
+use core::convert::Infallible;
+
+use anyhow::Result;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+use u8g2_fonts::{fonts, U8g2TextStyle};
+
+use crate::display::{AirQuality, UiState};
+
+// Landscape resolution of a 2.13" SSD1680 panel.
+pub const EPAPER_VIEW_W: usize = 250;
+pub const EPAPER_VIEW_H: usize = 122;
+
+const ROW_BYTES: usize = EPAPER_VIEW_W.div_ceil(8);
+
+// 1-bit frame: row-major, MSB-first within a byte, set = black.
+pub struct MonoFrame {
+    bits: Vec<u8>,
+}
+
+impl MonoFrame {
+    pub fn new() -> Self {
+        Self {
+            bits: vec![0; ROW_BYTES * EPAPER_VIEW_H],
+        }
+    }
+
+    pub fn is_black(&self, x: usize, y: usize) -> bool {
+        self.bits[y * ROW_BYTES + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+}
+
+impl Default for MonoFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for MonoFrame {
+    fn size(&self) -> Size {
+        Size::new(EPAPER_VIEW_W as u32, EPAPER_VIEW_H as u32)
+    }
+}
+
+impl DrawTarget for MonoFrame {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> core::result::Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x >= EPAPER_VIEW_W || y >= EPAPER_VIEW_H {
+                continue;
+            }
+            let byte = &mut self.bits[y * ROW_BYTES + x / 8];
+            let mask = 0x80 >> (x % 8);
+            if color.is_on() {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn render_epaper(frame: &mut MonoFrame, ui: &UiState) -> Result<()> {
+    frame.clear(BinaryColor::Off)?;
+
+    let left_top = TextStyleBuilder::new()
+        .alignment(Alignment::Left)
+        .baseline(Baseline::Top)
+        .build();
+    let right_top = TextStyleBuilder::new()
+        .alignment(Alignment::Right)
+        .baseline(Baseline::Top)
+        .build();
+    let style_label = U8g2TextStyle::new(fonts::u8g2_font_helvB10_tf, BinaryColor::On);
+    let style_co2 = U8g2TextStyle::new(fonts::u8g2_font_fub42_tr, BinaryColor::On);
+    let style_status = U8g2TextStyle::new(fonts::u8g2_font_helvB14_tf, BinaryColor::On);
+    let style_small = U8g2TextStyle::new(fonts::u8g2_font_helvR10_tf, BinaryColor::On);
+    let style_env = U8g2TextStyle::new(fonts::u8g2_font_helvB14_tf, BinaryColor::On);
+    let right_x = EPAPER_VIEW_W as i32 - 8;
+
+    Text::with_text_style("CO2 ppm", Point::new(8, 4), style_label.clone(), left_top)
+        .draw(frame)?;
+
+    let (co2_text, status) = if ui.calibrating {
+        ("CAL".to_string(), None)
+    } else if !ui.presence.co2 {
+        ("--".to_string(), None)
+    } else if ui.co2_error {
+        ("ERR".to_string(), None)
+    } else if let Some(ppm) = ui.co2_ppm {
+        let status = if ppm >= ui.co2_alarm_ppm {
+            "ALARM"
+        } else {
+            ui.co2_quality
+                .unwrap_or_else(|| AirQuality::from_co2(ppm))
+                .label()
+        };
+        (ppm.to_string(), Some(status))
+    } else if ui.sensors_read.co2 {
+        ("n/a".to_string(), None)
+    } else {
+        ("...".to_string(), None)
+    };
+    Text::with_text_style(&co2_text, Point::new(8, 24), style_co2, left_top).draw(frame)?;
+    if let Some(status) = status {
+        Text::with_text_style(status, Point::new(right_x, 28), style_status, right_top)
+            .draw(frame)?;
+    }
+    if let Some(peak) = ui.co2_peak.filter(|_| ui.presence.co2) {
+        let peak = format!("peak {}", peak);
+        Text::with_text_style(&peak, Point::new(right_x, 54), style_small, right_top)
+            .draw(frame)?;
+    }
+
+    let divider_y = 90;
+    Line::new(Point::new(8, divider_y), Point::new(right_x, divider_y))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(frame)?;
+
+    let (temp_text, hum_text) = if !ui.presence.sht31 {
+        ("--".to_string(), "--".to_string())
+    } else {
+        let missing = if ui.sensors_read.temp_hum {
+            "n/a"
+        } else {
+            "..."
+        };
+        (
+            ui.temperature_c.map_or(missing.to_string(), |c| {
+                ui.temp_unit.format(c, ui.temp_decimals)
+            }),
+            ui.humidity_pct
+                .map_or(missing.to_string(), |pct| format!("{} %", pct)),
+        )
+    };
+    let env_y = divider_y + 8;
+    Text::with_text_style(
+        &temp_text,
+        Point::new(8, env_y),
+        style_env.clone(),
+        left_top,
+    )
+    .draw(frame)?;
+    Text::with_text_style(&hum_text, Point::new(right_x, env_y), style_env, right_top)
+        .draw(frame)?;
+
+    Ok(())
+}
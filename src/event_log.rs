@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use log::Level;
+
+use crate::diagnostics::uptime_secs;
+
+pub const LOG_RING_CAPACITY: usize = 32;
+const LOG_MSG_MAX_LEN: usize = 40;
+
+static EVENT_LOG: Mutex<LogRing> = Mutex::new(LogRing::new());
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub uptime_s: u32,
+    pub level: Level,
+    pub msg: String,
+}
+
+// Fixed-capacity ring of notable events, oldest entries are dropped first.
+pub struct LogRing {
+    entries: VecDeque<LogEntry>,
+    pushed: u32,
+}
+
+impl LogRing {
+    pub const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pushed: 0,
+        }
+    }
+
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= LOG_RING_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.pushed = self.pushed.wrapping_add(1);
+    }
+
+    pub fn recent(&self, count: usize) -> Vec<LogEntry> {
+        self.entries.iter().rev().take(count).cloned().collect()
+    }
+}
+
+pub fn push(level: Level, msg: &str) {
+    let msg: String = msg.chars().take(LOG_MSG_MAX_LEN).collect();
+    let entry = LogEntry {
+        uptime_s: uptime_secs(),
+        level,
+        msg,
+    };
+    if let Ok(mut ring) = EVENT_LOG.lock() {
+        ring.push(entry);
+    }
+}
+
+// Most recent entries first.
+pub fn recent(count: usize) -> Vec<LogEntry> {
+    match EVENT_LOG.lock() {
+        Ok(ring) => ring.recent(count),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Total number of entries ever pushed; changes whenever the log changes.
+pub fn revision() -> u32 {
+    match EVENT_LOG.lock() {
+        Ok(ring) => ring.pushed,
+        Err(_) => 0,
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+// Exponential moving average; `alpha` in (0, 1], 1.0 disables smoothing.
+#[derive(Debug, Clone, Copy)]
+pub struct Ema {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.01, 1.0),
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, sample: f32) -> f32 {
+        let next = match self.value {
+            Some(prev) => prev + self.alpha * (sample - prev),
+            None => sample,
+        };
+        self.value = Some(next);
+        next
+    }
+
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+}
+
+// Move `current` a fraction of the way to `target`, at least 1 unit per step,
+// so repeated calls always land exactly on the target.
+pub fn ease_toward(current: u16, target: u16, fraction: f32) -> u16 {
+    let diff = target as i32 - current as i32;
+    if diff == 0 {
+        return target;
+    }
+    let step = ((diff.abs() as f32 * fraction).round() as i32).max(1);
+    if diff > 0 {
+        (current as i32 + step.min(diff)) as u16
+    } else {
+        (current as i32 - step.min(-diff)) as u16
+    }
+}
+
+// Running median of the last `window` CO2 readings, for the display only: a
+// lone spike never reaches the card. A window of 1 passes readings through.
+#[derive(Debug, Clone)]
+pub struct SmoothedCo2 {
+    samples: VecDeque<u16>,
+    window: usize,
+}
+
+impl SmoothedCo2 {
+    pub fn new(window: u8) -> Self {
+        let window = usize::from(window.max(1));
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    pub fn window(&self) -> u8 {
+        self.window as u8
+    }
+
+    // Start over with a buffer of the new size. It is seeded with the current
+    // median, so the shown value does not jump back to the next raw reading.
+    pub fn set_window(&mut self, window: u8) {
+        let seed = self.value();
+        *self = Self::new(window);
+        self.samples.extend(seed);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn push(&mut self, ppm: u16) -> u16 {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ppm);
+        // Non-empty after the push.
+        self.value().unwrap_or(ppm)
+    }
+
+    // Median of the buffered readings; the lower middle one for an even count.
+    pub fn value(&self) -> Option<u16> {
+        let mut sorted: Vec<u16> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted.get(sorted.len().saturating_sub(1) / 2).copied()
+    }
+}
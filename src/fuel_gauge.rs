@@ -0,0 +1,57 @@
+use esp_idf_hal::delay::BLOCK;
+use esp_idf_hal::i2c::I2cDriver;
+use esp_idf_hal::sys::EspError;
+
+// Registers are 16-bit, big endian.
+const REG_VCELL: u8 = 0x02;
+const REG_SOC: u8 = 0x04;
+const REG_VERSION: u8 = 0x08;
+// VCELL LSB is 78.125 uV per cell.
+const VCELL_V_PER_LSB: f32 = 78.125e-6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GaugeReading {
+    pub voltage_v: f32,
+    // ModelGauge state of charge; can briefly read above 100 while charging.
+    pub soc_pct: f32,
+}
+
+// MAX17048/MAX17049 single-cell fuel gauge. Works out of the box with its
+// default battery model; no configuration is written.
+pub struct Max17048 {
+    addr: u8,
+}
+
+impl Max17048 {
+    pub const DEFAULT_ADDR: u8 = 0x36;
+
+    pub fn new(addr: u8) -> Self {
+        Self { addr }
+    }
+
+    pub fn new_default() -> Self {
+        Self::new(Self::DEFAULT_ADDR)
+    }
+
+    // Some(version) if a MAX1704x answered; other parts at 0x36 are ignored.
+    pub fn probe(&self, i2c: &mut I2cDriver<'_>) -> Result<Option<u16>, EspError> {
+        let version = self.read_reg(i2c, REG_VERSION)?;
+        Ok((version & 0xFFF0 == 0x0010).then_some(version))
+    }
+
+    pub fn read(&self, i2c: &mut I2cDriver<'_>) -> Result<GaugeReading, EspError> {
+        let vcell = self.read_reg(i2c, REG_VCELL)?;
+        let soc = self.read_reg(i2c, REG_SOC)?;
+        Ok(GaugeReading {
+            voltage_v: vcell as f32 * VCELL_V_PER_LSB,
+            // High byte is whole percent, low byte 1/256 %.
+            soc_pct: soc as f32 / 256.0,
+        })
+    }
+
+    fn read_reg(&self, i2c: &mut I2cDriver<'_>, reg: u8) -> Result<u16, EspError> {
+        let mut buf = [0u8; 2];
+        i2c.write_read(self.addr, &[reg], &mut buf, BLOCK)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
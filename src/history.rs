@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// One averaged point per interval; CO2_HISTORY_LEN points cover the last 4 h.
+pub const CO2_HISTORY_INTERVAL: Duration = Duration::from_secs(300);
+pub const CO2_HISTORY_LEN: usize = 48;
+// Short-term raw readings kept (not persisted) for rate-of-change estimates.
+const CO2_RECENT_WINDOW: Duration = Duration::from_secs(300);
+// Slopes within +/- this many ppm/min read as "steady".
+const CO2_TREND_DEADBAND_PPM_PER_MIN: f32 = 15.0;
+// Need at least this much recent data before claiming a direction.
+const CO2_TREND_MIN_SPAN: Duration = Duration::from_secs(60);
+// |slope| at which adaptive polling reaches its fastest rate (a room filling up).
+const CO2_FAST_SLOPE_PPM_PER_MIN: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Co2Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+pub struct Co2History {
+    points: VecDeque<u16>,
+    recent: VecDeque<(Instant, u16)>,
+    window_start: Instant,
+    window_sum: u32,
+    window_count: u32,
+}
+
+impl Co2History {
+    pub fn new() -> Self {
+        Self {
+            points: VecDeque::with_capacity(CO2_HISTORY_LEN),
+            recent: VecDeque::new(),
+            window_start: Instant::now(),
+            window_sum: 0,
+            window_count: 0,
+        }
+    }
+
+    // Restore persisted points (oldest first), keeping at most CO2_HISTORY_LEN.
+    pub fn with_points(points: &[u16]) -> Self {
+        let mut history = Self::new();
+        let skip = points.len().saturating_sub(CO2_HISTORY_LEN);
+        history.points.extend(points.iter().skip(skip).copied());
+        history
+    }
+
+    // Returns true when a new downsampled point was appended (i.e. worth persisting).
+    pub fn push(&mut self, ppm: u16) -> bool {
+        let now = Instant::now();
+        self.recent.push_back((now, ppm));
+        while let Some(&(at, _)) = self.recent.front() {
+            if now.duration_since(at) <= CO2_RECENT_WINDOW {
+                break;
+            }
+            self.recent.pop_front();
+        }
+
+        self.window_sum += ppm as u32;
+        self.window_count += 1;
+        if now.duration_since(self.window_start) < CO2_HISTORY_INTERVAL {
+            return false;
+        }
+
+        let avg = (self.window_sum / self.window_count) as u16;
+        if self.points.len() >= CO2_HISTORY_LEN {
+            self.points.pop_front();
+        }
+        self.points.push_back(avg);
+        self.window_start = now;
+        self.window_sum = 0;
+        self.window_count = 0;
+        true
+    }
+
+    pub fn points(&self) -> Vec<u16> {
+        self.points.iter().copied().collect()
+    }
+
+    // Least-squares slope over the recent window, in ppm/min.
+    pub fn slope_ppm_per_min(&self) -> Option<f32> {
+        let (first_at, _) = *self.recent.front()?;
+        let (last_at, _) = *self.recent.back()?;
+        if last_at.duration_since(first_at) < CO2_TREND_MIN_SPAN {
+            return None;
+        }
+        let n = self.recent.len() as f32;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        for &(at, ppm) in &self.recent {
+            let x = at.duration_since(first_at).as_secs_f32() / 60.0;
+            let y = ppm as f32;
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+        let denom = n * sxx - sx * sx;
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+        Some((n * sxy - sx * sy) / denom)
+    }
+
+    // Poll interval scaled by how fast CO2 is changing: `max` while the trend
+    // is steady (or unknown), shrinking linearly to `min` at
+    // CO2_FAST_SLOPE_PPM_PER_MIN.
+    pub fn adaptive_interval(&self, min: Duration, max: Duration) -> Duration {
+        let speed = self.slope_ppm_per_min().map_or(0.0, f32::abs);
+        let t = ((speed - CO2_TREND_DEADBAND_PPM_PER_MIN)
+            / (CO2_FAST_SLOPE_PPM_PER_MIN - CO2_TREND_DEADBAND_PPM_PER_MIN))
+            .clamp(0.0, 1.0);
+        max.saturating_sub(max.saturating_sub(min).mul_f32(t))
+    }
+
+    pub fn trend(&self) -> Co2Trend {
+        match self.slope_ppm_per_min() {
+            Some(slope) if slope > CO2_TREND_DEADBAND_PPM_PER_MIN => Co2Trend::Rising,
+            Some(slope) if slope < -CO2_TREND_DEADBAND_PPM_PER_MIN => Co2Trend::Falling,
+            _ => Co2Trend::Steady,
+        }
+    }
+}
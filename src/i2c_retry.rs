@@ -0,0 +1,44 @@
+// Retry for I2C reads whose payload arrived but failed its integrity check
+// (CRC mismatch, or off-panel touch coordinates for the CRC-less touch report).
+// Touch and sensor traffic share one bus, and a collision occasionally flips a
+// byte; running the whole transaction again usually gets a clean copy. Bus
+// errors (NACK, timeout) are left to each driver's own retry.
+//
+// Only transactions that can be repeated belong here: the SHT31 periodic fetch
+// and the SCD4x measurement read clear the sensor's result on read, so a retry
+// would only get a NACK.
+
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+
+// Attempts per read, including the first.
+const ATTEMPTS: usize = 3;
+// Lets the other device's transaction finish before trying again.
+const RETRY_DELAY: Duration = Duration::from_millis(2);
+
+// Run `read` until it succeeds, fails with an error `is_corrupt` does not
+// accept, or the attempts are used up; returns the last result.
+pub fn retry_corrupt<T, E>(
+    what: &str,
+    mut read: impl FnMut() -> Result<T, E>,
+    is_corrupt: impl Fn(&E) -> bool,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match read() {
+            Err(err) if is_corrupt(&err) && attempt < ATTEMPTS => {
+                warn!(
+                    "{} corrupted, retrying ({}/{})",
+                    what,
+                    attempt,
+                    ATTEMPTS - 1
+                );
+                attempt += 1;
+                thread::sleep(RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
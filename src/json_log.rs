@@ -0,0 +1,121 @@
+// Structured event lines for log aggregators (`json-logs` feature). Notable
+// call sites emit an event next to their usual `info!`/`error!` line; each one
+// is printed straight to stdout as a single JSON object, bypassing EspLogger's
+// "I (1234) target:" prefix:
+//
+//   {"ts":1718000000,"uptime_s":42,"level":"INFO","module":"c6_demo::mqtt","event":"mqtt_reconnect","data":{"blip_s":3}}
+//
+// `ts` is Unix time, null until SNTP has synced. Without the feature the
+// macro compiles to nothing.
+
+use std::fmt::Write;
+
+use log::Level;
+
+use crate::diagnostics;
+use crate::schedule::unix_time;
+
+pub const JSON_LOGS: bool = cfg!(feature = "json-logs");
+
+// Emit an event: `json_event!(Level::Info, "co2_read", "ppm" => ppm, "raw" => raw)`.
+macro_rules! json_event {
+    ($level:expr, $event:expr $(, $key:literal => $value:expr)* $(,)?) => {
+        if $crate::json_log::JSON_LOGS {
+            $crate::json_log::emit(
+                $level,
+                module_path!(),
+                $event,
+                &[$(($key, &$value as &dyn $crate::json_log::JsonValue)),*],
+            );
+        }
+    };
+}
+pub(crate) use json_event;
+
+// A value that can appear under "data".
+pub trait JsonValue {
+    fn write_json(&self, out: &mut String);
+}
+
+macro_rules! json_number {
+    ($($ty:ty),*) => {
+        $(impl JsonValue for $ty {
+            fn write_json(&self, out: &mut String) {
+                let _ = write!(out, "{}", self);
+            }
+        })*
+    };
+}
+json_number!(u8, u16, u32, u64, i32, i64, usize, bool);
+
+impl JsonValue for f32 {
+    fn write_json(&self, out: &mut String) {
+        if self.is_finite() {
+            let _ = write!(out, "{:.2}", self);
+        } else {
+            out.push_str("null");
+        }
+    }
+}
+
+impl JsonValue for str {
+    fn write_json(&self, out: &mut String) {
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                c if (c as u32) < 0x20 => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl JsonValue for &str {
+    fn write_json(&self, out: &mut String) {
+        (**self).write_json(out);
+    }
+}
+
+impl JsonValue for String {
+    fn write_json(&self, out: &mut String) {
+        self.as_str().write_json(out);
+    }
+}
+
+impl<T: JsonValue> JsonValue for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+pub fn emit(level: Level, module: &str, event: &str, data: &[(&str, &dyn JsonValue)]) {
+    let mut line = String::with_capacity(128);
+    line.push_str("{\"ts\":");
+    unix_time().write_json(&mut line);
+    let _ = write!(line, ",\"uptime_s\":{},\"level\":", diagnostics::uptime_secs());
+    level.as_str().write_json(&mut line);
+    line.push_str(",\"module\":");
+    module.write_json(&mut line);
+    line.push_str(",\"event\":");
+    event.write_json(&mut line);
+    line.push_str(",\"data\":{");
+    for (i, (key, value)) in data.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        key.write_json(&mut line);
+        line.push(':');
+        value.write_json(&mut line);
+    }
+    line.push_str("}}");
+    println!("{}", line);
+}
@@ -1,39 +1,115 @@
 #![allow(clippy::needless_return)]
 
+mod ambient;
 mod board;
 mod battery;
+mod button;
+mod co2;
+mod command;
+#[cfg(feature = "console")]
+mod console;
+#[cfg(feature = "demo-mode")]
+mod demo;
+mod diagnostics;
 mod display;
+#[cfg(feature = "epaper")]
+mod epaper;
+#[cfg(feature = "epaper")]
+mod epaper_ui;
+mod event_log;
+mod filter;
+mod fuel_gauge;
+mod history;
+mod i2c_retry;
+mod json_log;
+mod options;
 mod ota;
+mod panic_screen;
+mod pins;
+mod relay;
+mod schedule;
+#[cfg(feature = "selftest")]
+mod selftest;
+mod sensor_proto;
+mod sensor_hal;
+mod settings;
 mod sht31;
 mod st7789;
+mod stats;
+mod status_led;
+mod telemetry;
+mod telemetry_compact;
 mod mhz19b;
 mod mqtt;
+mod scd4x;
 mod touch;
+mod watchdog;
 mod wifi;
 
-use crate::board::Board;
-use crate::display::{co2_card_rect, render_ui_mock1};
-use crate::mqtt::{Command as MqttCommand, Telemetry as MqttTelemetry};
-use crate::ota::{check_and_update, mark_app_valid, OTA_CHECK_INTERVAL};
-use crate::st7789::{LCD_H, LCD_W};
-use crate::touch::{read_touch, touch_take_pending};
+use crate::battery::{BatteryReading, CriticalBattery, LOW_BATTERY_WAKE_INTERVAL};
+use crate::board::{Board, SensorPresence};
+use crate::button::ButtonEvent;
+use crate::co2::Co2Reading;
+use crate::command::{Command as MqttCommand, CommandThrottle};
+use crate::display::{
+    co2_card_rect, log_page_lines, render_big_number, render_log_page, render_notice,
+    render_ui_mock1, BigPage, Co2QualityHysteresis, SensorsRead, Theme, UiState,
+};
+use crate::filter::{ease_toward, Ema, SmoothedCo2};
+use crate::history::Co2History;
+use crate::json_log::json_event;
+use crate::mqtt::{PublishOnChange, SensorGroup, SettingsReport, STATUS_PUBLISH_INTERVAL};
+use crate::options::{Layout, TempHumSource, TempUnit, ThemePreset};
+use crate::ota::{check_and_update, log_firmware_slots, mark_app_valid, OTA_CHECK_INTERVAL};
+use crate::schedule::{init_timezone, QuietHours};
+use crate::sensor_proto::mhz19b::compensate_temperature;
+use crate::stats::{Co2Peak, MoldRisk, Occupancy, TempHumStats, MOLD_RISK_RH_PCT};
+use crate::st7789::{LcdError, St7789, LCD_H, LCD_W};
+use crate::telemetry::{Telemetry as MqttTelemetry, TelemetryField};
+use crate::touch::{read_touch, touch_take_pending, TouchHealth};
+#[cfg(feature = "console")]
+use crate::console::{Console, ConsoleRequest};
+#[cfg(feature = "console")]
+use crate::touch::i2c_scan;
+#[cfg(feature = "epaper")]
+use crate::epaper_ui::{render_epaper, MonoFrame};
 
 use anyhow::Result;
 use embedded_graphics::geometry::Point;
 use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
 use esp_idf_svc::log::{set_target_level, EspLogger};
 use esp_idf_svc::sys::esp_restart;
-use log::{error, info, warn, LevelFilter};
+use log::{debug, error, info, warn, Level, LevelFilter};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use esp_idf_sys as sys;
 
+const DEMO_MODE: bool = cfg!(feature = "demo-mode");
+
+// Independent per-source cadences (build-time overridable, in ms).
+const SHT_INTERVAL_MS: Option<&str> = option_env!("SHT_INTERVAL_MS");
+const CO2_INTERVAL_MS: Option<&str> = option_env!("CO2_INTERVAL_MS");
+// Adaptive CO2 polling bounds: fast while the level changes, slow when flat.
+// Setting CO2_INTERVAL_MS instead pins a fixed interval.
+const CO2_INTERVAL_MIN_MS: Option<&str> = option_env!("CO2_INTERVAL_MIN_MS");
+const CO2_INTERVAL_MAX_MS: Option<&str> = option_env!("CO2_INTERVAL_MAX_MS");
+const BATTERY_INTERVAL_MS: Option<&str> = option_env!("BATTERY_INTERVAL_MS");
+const DISPLAY_INTERVAL_MS: Option<&str> = option_env!("DISPLAY_INTERVAL_MS");
+const LOOP_SLEEP_MS: Option<&str> = option_env!("LOOP_SLEEP_MS");
+// Animate the displayed CO2 number toward new readings (display only).
+const SMOOTH_DISPLAY: bool = match option_env!("SMOOTH_DISPLAY") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+const CO2_EASE_FRACTION: f32 = 0.2;
+
 fn main() -> Result<()> {
     sys::link_patches();
     EspLogger::initialize_default();
-    // Note: UART0 TX/RX are used for MH-Z19B on this board; logging over UART0
-    // will share the line with the sensor. Disable logs if that causes issues.
+    // Note: UART0 TX/RX are used for MH-Z19B on this board (unless built with
+    // MHZ19B_UART=1); logging over UART0 will share the line with the sensor.
+    // Disable logs if that causes issues.
     let log_level = if cfg!(debug_assertions) {
         LevelFilter::Debug
     } else {
@@ -58,30 +134,108 @@ fn main() -> Result<()> {
         warn!("Failed to set log level for adc_hal: {:?}", err);
     }
 
+    diagnostics::log_boot_banner();
+    panic_screen::install();
+
     if let Err(err) = run() {
         error!("Fatal error, exiting main loop: {:?}", err);
-        loop {
-            thread::sleep(Duration::from_secs(1));
-        }
+        // Route through the panic hook so the error is shown on the LCD and we reboot.
+        panic!("{err:#}");
     }
     Ok(())
 }
 
 fn run() -> Result<()> {
-    log::info!("App start");
+    event_log::push(Level::Info, &format!("Reset: {}", diagnostics::reset_reason()));
+    json_event!(Level::Info, "boot", "reset_reason" => diagnostics::reset_reason());
     let Board {
         mut lcd,
         mut i2c,
-        mut mhz19b,
+        mut co2,
         mut battery,
+        mut ambient,
+        mut button,
+        mut status_led,
+        mut relay,
+        #[cfg(feature = "epaper")]
+        mut epaper,
         sht31,
         mut wifi,
+        presence,
+        touch,
+        mut settings,
+        sntp: _sntp,
     } = Board::init()?;
+    // Hold the button while powering on to run it (see
+    // selftest::RUN_WITHOUT_BUTTON for boards without one).
+    #[cfg(feature = "selftest")]
+    if button
+        .as_ref()
+        .map_or(selftest::RUN_WITHOUT_BUTTON, |button| button.is_held())
+    {
+        selftest::run(&mut lcd, &mut i2c, co2.as_mut(), &mut battery, &sht31);
+    }
+    // Counted once Board::init has opened NVS, so a crash during init itself
+    // is not tallied.
+    let mut crash_count = if diagnostics::reset_was_crash() {
+        match settings.record_crash() {
+            Ok(count) => {
+                warn!(
+                    "Abnormal reset ({}), crash count now {}",
+                    diagnostics::reset_reason(),
+                    count
+                );
+                count
+            }
+            Err(err) => {
+                warn!("Crash count update failed: {:?}", err);
+                settings.crash_count()
+            }
+        }
+    } else {
+        settings.crash_count()
+    };
+    init_timezone();
+    let quiet_hours = QuietHours::from_env();
+    #[cfg(feature = "demo-mode")]
+    let demo = demo::DemoData::new();
+    let presence = if DEMO_MODE {
+        info!("Demo mode: using fake sensor data, MQTT/OTA disabled");
+        SensorPresence {
+            co2: true,
+            sht31: true,
+        }
+    } else {
+        presence
+    };
+    let mut th_source = resolve_temp_hum_source(
+        settings.temp_hum_source(),
+        presence,
+        co2.reports_temp_humidity(),
+    );
+    info!(
+        "Temp/humidity source: {} (configured {})",
+        th_source.map_or("none", TempHumSource::as_str),
+        settings.temp_hum_source().as_str()
+    );
+    if let Err(err) = log_firmware_slots() {
+        warn!("OTA partition info unavailable: {:?}", err);
+    }
     if let Err(err) = mark_app_valid() {
         warn!("OTA mark-running-valid failed: {:?}", err);
     }
-    let mut mqtt = match wifi.as_mut() {
-        Some(wifi) => match mqtt::init_mqtt(wifi) {
+    // Periodic mode lets the loop fetch results without the single-shot 15ms wait.
+    let sht_periodic = !DEMO_MODE && presence.sht31 && match sht31.start_periodic(&mut i2c) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("SHT31 periodic mode failed, using single-shot: {:?}", err);
+            false
+        }
+    };
+    let mut temp_unit = settings.temp_unit().unwrap_or_else(TempUnit::from_env);
+    let mut temp_decimals = settings.temp_decimals();
+    let mut mqtt = match wifi.as_mut().filter(|_| !DEMO_MODE) {
+        Some(wifi) => match mqtt::init_mqtt(wifi, temp_unit, battery.has_soc()) {
             Ok(client) => Some(client),
             Err(err) => {
                 warn!("MQTT init failed: {:?}", err);
@@ -90,41 +244,145 @@ fn run() -> Result<()> {
         },
         None => None,
     };
-    let env_interval = Duration::from_millis(2000);
+    if let Some(mqtt) = mqtt.as_mut() {
+        if let Err(err) = mqtt.publish_crash_count(crash_count) {
+            warn!("MQTT crash count publish failed: {:?}", err);
+        }
+    }
+    #[cfg(feature = "console")]
+    let console = match Console::start() {
+        Ok(console) => Some(console),
+        Err(err) => {
+            warn!("Console init failed: {:?}", err);
+            None
+        }
+    };
+    let env_interval = interval_ms(SHT_INTERVAL_MS, 2000);
     let mut last_env_read = Instant::now() - env_interval;
-    let mhz_interval = Duration::from_millis(5000);
-    let mut last_mhz_read = Instant::now() - mhz_interval;
-    const MHZ_ERR_REINIT_THRESHOLD: u8 = 3;
-    let mut mhz_error_count: u8 = 0;
-    let battery_interval = Duration::from_millis(10000);
+    // MH-Z19B only updates internally ~1/s; polling faster just adds bus traffic.
+    let (co2_interval_min, co2_interval_max) = match CO2_INTERVAL_MS {
+        Some(_) => {
+            let fixed = interval_ms(CO2_INTERVAL_MS, 5000);
+            (fixed, fixed)
+        }
+        None => (
+            interval_ms(CO2_INTERVAL_MIN_MS, 2000),
+            interval_ms(CO2_INTERVAL_MAX_MS, 10000),
+        ),
+    };
+    let co2_interval_min = co2_interval_min.max(Duration::from_secs(1));
+    let co2_interval_max = co2_interval_max.max(co2_interval_min);
+    let mut co2_interval = co2_interval_max;
+    let mut last_co2_read = Instant::now() - co2_interval;
+    // Temp/humidity is reported unavailable after this long without a good sample.
+    let env_stale_after = env_interval.max(co2_interval_max) * 3;
+    let mut last_env_ok: Option<Instant> = None;
+    let mut temp_hum_stats = TempHumStats::new();
+    let mut mold_risk = MoldRisk::new();
+    let mut occupancy = Occupancy::new();
+    // CO2 reads are held off (progress bar on the card) until the sensor has warmed up.
+    let co2_warmup = if DEMO_MODE { Duration::ZERO } else { co2.warmup() };
+    let co2_warmup_started = Instant::now();
+    let mut last_warmup_secs: Option<u64> = None;
+    const CO2_ERR_RECOVER_THRESHOLD: u8 = 3;
+    let mut co2_error_count: u8 = 0;
+    let battery_interval = interval_ms(BATTERY_INTERVAL_MS, 10000);
     let mut last_battery_read = Instant::now() - battery_interval;
+    let mut critical_battery = CriticalBattery::new();
+    // Redraws use cached readings, so the UI can refresh faster than the sensors.
+    let display_interval = interval_ms(DISPLAY_INTERVAL_MS, 200);
+    let mut last_render = Instant::now() - display_interval;
     let mut last_ota_check = Instant::now() - OTA_CHECK_INTERVAL;
 
     // ---- Framebuffer ----
     let mut frame: Vec<Rgb565> = vec![Rgb565::BLACK; LCD_W * LCD_H];
+    #[cfg(feature = "epaper")]
+    let mut epaper_frame = MonoFrame::new();
 
     // Live sensor readings.
+    let mut sensors_read = SensorsRead::default();
     let mut temperature_c: Option<f32> = None;
     let mut humidity_pct: Option<u8> = None;
     let mut co2_value: Option<u16> = None;
+    let mut co2_raw: Option<u16> = None;
+    // Persisted in NVS so the trend survives reboots/OTA.
+    let mut co2_history = Co2History::with_points(&settings.load_co2_history());
+    // Card tint and status LED follow this debounced band, not the raw reading.
+    let mut co2_quality = Co2QualityHysteresis::from_env();
+    // Worst CO2 since the last reset_peak; saved to NVS at most once a minute.
+    const CO2_PEAK_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+    let mut co2_peak = Co2Peak::with_peak(settings.co2_peak());
+    let mut saved_co2_peak = co2_peak.peak();
+    let mut last_co2_peak_save: Option<Instant> = None;
     let mut co2_error = false;
     let mut battery_v: Option<f32> = None;
+    // What MQTT sees: readings go null on a failed read, or once they are
+    // older than a few sensor intervals.
+    let mut telemetry = MqttTelemetry::default();
+    telemetry.set_max_age(TelemetryField::Co2, co2_interval_max * 3);
+    telemetry.set_max_age(TelemetryField::Temp, env_stale_after);
+    telemetry.set_max_age(TelemetryField::Humidity, env_stale_after);
+    telemetry.set_max_age(TelemetryField::Battery, battery_interval * 3);
+    // Display-only smoothing (MQTT keeps raw readings); 1.0 disables it.
+    const DISPLAY_EMA_ALPHA: f32 = 0.3;
+    let mut temp_ema = Ema::new(DISPLAY_EMA_ALPHA);
+    let mut humidity_ema = Ema::new(DISPLAY_EMA_ALPHA);
+    // Median of the last few CO2 readings; what the screens are eased toward.
+    let mut co2_smoother = SmoothedCo2::new(settings.co2_smoothing());
+    let mut co2_smoothed: Option<u16> = None;
+    let mut shown_co2: Option<u16> = None;
+    let mut co2_alarm_ppm = settings.co2_alarm_ppm();
+    let mut theme = settings.theme().unwrap_or_else(ThemePreset::from_env);
+    let mut layout = settings.layout().unwrap_or_else(Layout::from_env);
+    // Big layout: the reading on screen and when it came up. Pages advance on
+    // this timer or on a tap.
+    const BIG_PAGE_INTERVAL: Duration = Duration::from_secs(8);
+    let mut big_page = BigPage::Co2;
+    let mut big_page_at = Instant::now();
+    // Board::init switches ABC off; only the abc command turns it back on.
+    let mut abc_enabled = false;
+    // Publish <prefix>/settings once MQTT is up, and again on request.
+    let mut settings_report_due = true;
+    const FACTORY_RESET_CONFIRM: Duration = Duration::from_secs(10);
+    let mut factory_reset_armed_at: Option<Instant> = None;
+    let mut command_throttle = CommandThrottle::new();
 
     let co2_rect = co2_card_rect();
     let hold_duration = Duration::from_secs(2);
-    let zero_feedback_duration = Duration::from_secs(3);
-    let mut co2_hold_start: Option<Instant> = None;
-    let mut co2_hold_triggered = false;
-    let mut zero_feedback_until: Option<Instant> = None;
+    // Readings are meaningless while the sensor re-baselines after a zero
+    // calibration; the CO2 card shows "CAL" and re-triggers are ignored.
+    const CALIBRATION_LOCKOUT: Duration = Duration::from_secs(60);
+    let mut touch_press: Option<TouchPress> = None;
+    // Data hold: the LCD keeps showing the snapshot taken at the next redraw
+    // (with a HOLD tag) until the next long-press; MQTT and the e-paper keep
+    // getting live readings.
+    let mut display_hold = false;
+    let mut held_ui: Option<UiState> = None;
+    let mut calibrating_until: Option<Instant> = None;
     const DISPLAY_OFF_TIMEOUT: Duration = Duration::from_secs(5); // timeout aftter which displays starts reducing brightness
     const DISPLAY_OFF_DURATION: Duration = Duration::from_secs(2); // duration for which display reduces brightness
     const DEFAULT_BRIGHTNESS: u8 = 10;
-    const SLEEP_INTERFVAL: Duration = Duration::from_millis(200);
-    const MQTT_PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+    // Idle loop period; touch activity switches to the fast period for a while.
+    let sleep_interval = interval_ms(LOOP_SLEEP_MS, 200);
+    const FAST_SLEEP_INTERVAL: Duration = Duration::from_millis(20);
+    const FAST_LOOP_HOLD: Duration = Duration::from_secs(1);
+    const AMBIENT_INTERVAL: Duration = Duration::from_secs(1);
+    const AMBIENT_MANUAL_HOLD: Duration = Duration::from_secs(600); // MQTT brightness overrides ambient for this long
+    const DISPLAY_GAMMA: f32 = 1.0; // palette gamma applied at flush, 1.0 = unchanged
+    lcd.set_gamma(DISPLAY_GAMMA);
+    if let Some(invert) = settings.lcd_invert() {
+        if let Err(err) = lcd.set_invert(invert) {
+            warn!("LCD inversion setting failed: {:?}", err);
+        }
+    }
     lcd.set_brightness(DEFAULT_BRIGHTNESS)?;
+    let mut active_brightness = DEFAULT_BRIGHTNESS;
+    let mut manual_brightness_at: Option<Instant> = None;
+    let mut last_ambient_read = Instant::now() - AMBIENT_INTERVAL;
+    const QUIET_WAKE_DURATION: Duration = Duration::from_secs(3); // touch wake time during quiet hours
     let mut last_touch = Instant::now();
     let dimming_steps =
-        (DISPLAY_OFF_DURATION.as_millis() / SLEEP_INTERFVAL.as_millis()).max(1) as u32;
+        (DISPLAY_OFF_DURATION.as_millis() / sleep_interval.as_millis()).max(1) as u32;
     let dimming_step =
         ((DEFAULT_BRIGHTNESS as u32 + dimming_steps - 1) / dimming_steps) as u8;
     let mut dimming_in_progress = false;
@@ -134,172 +392,698 @@ fn run() -> Result<()> {
     let mut last_humidity_display: Option<u8> = None;
     let mut last_co2_display: Option<u16> = None;
     let mut last_co2_error = false;
-    let mut last_zero_mode = false;
+    let mut last_calibrating = false;
     let mut last_battery_display: Option<i32> = None;
     let mut touch_active = false;
+    let mut touch_health = TouchHealth::new(touch);
+    if !touch {
+        event_log::push(Level::Warn, "Touch unavailable");
+    }
+    let mut show_log_page = false;
+    let mut last_log_revision = event_log::revision();
     let mut last_mqtt_publish = Instant::now();
+    let mut publish_gate = PublishOnChange::from_env();
     loop {
+        if let Some(mqtt) = mqtt.as_mut() {
+            if let Err(err) = mqtt.poll() {
+                warn!("MQTT reconnect handling failed: {:?}", err);
+            }
+        }
+
+        // Commands from MQTT and, with the `console` feature, the serial console.
+        let mut commands: Vec<(MqttCommand, CommandOrigin)> = Vec::new();
         if let Some(mqtt) = mqtt.as_mut() {
             while let Some(cmd) = mqtt.try_recv_command() {
-                match cmd {
-                    MqttCommand::ZeroCalibrate => {
-                        if let Err(err) = mhz19b.calibrate_zero() {
-                            error!("MQTT zero calibration failed: {:?}", err);
-                        } else {
-                            info!("MQTT zero calibration triggered");
-                            zero_feedback_until = Some(Instant::now() + zero_feedback_duration);
+                commands.push((cmd, CommandOrigin::Mqtt));
+            }
+        }
+        #[cfg(feature = "console")]
+        while let Some(request) = console.as_ref().and_then(Console::try_recv) {
+            match request {
+                ConsoleRequest::Command(cmd) => commands.push((cmd, CommandOrigin::Console)),
+                ConsoleRequest::Status => info!(
+                    "Status: co2={:?} ppm (raw {:?}, error {}), temp={:?} C, humidity={:?} %, battery={:?} V, source={}, uptime={} s",
+                    co2_value,
+                    co2_raw,
+                    co2_error,
+                    temperature_c,
+                    humidity_pct,
+                    battery_v,
+                    th_source.map_or("none", TempHumSource::as_str),
+                    diagnostics::uptime_secs()
+                ),
+                ConsoleRequest::ReadCo2 => match co2.read(&mut i2c) {
+                    Ok(reading) => info!("{}: {:?}", co2.name(), reading),
+                    Err(err) => warn!("{} read failed: {:?}", co2.name(), err),
+                },
+                ConsoleRequest::I2cScan => {
+                    i2c_scan(&mut i2c);
+                }
+            }
+        }
+        for (cmd, origin) in commands {
+            let name = cmd.name();
+            // The console is someone at the serial port; only MQTT is throttled.
+            let throttled = matches!(origin, CommandOrigin::Mqtt);
+            if let Some(left) = command_throttle.remaining(&cmd).filter(|_| throttled) {
+                warn!("MQTT {} dropped, cooldown {} s left", name, left.as_secs().max(1));
+                send_ack(&mut mqtt, origin, name, &Err("cooldown".to_string()));
+                continue;
+            }
+            let result: Result<(), String> = match cmd {
+                MqttCommand::ZeroCalibrate if calibrating_until.is_some() => {
+                    warn!("MQTT zero calibration ignored, calibration in progress");
+                    Err("busy".to_string())
+                }
+                MqttCommand::ZeroCalibrate => match co2.calibrate_zero(&mut i2c) {
+                    Err(err) => {
+                        error!("MQTT zero calibration failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                    Ok(()) => {
+                        info!("MQTT zero calibration triggered");
+                        event_log::push(Level::Info, "MQTT zero calibration");
+                        calibrating_until = Some(Instant::now() + CALIBRATION_LOCKOUT);
+                        render_needed = true;
+                        Ok(())
+                    }
+                },
+                MqttCommand::SetAbc(enabled) => match co2.set_abc(&mut i2c, enabled) {
+                    Err(err) => {
+                        error!("MQTT set ABC failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                    Ok(()) => {
+                        info!("MQTT set ABC: {}", enabled);
+                        abc_enabled = enabled;
+                        Ok(())
+                    }
+                },
+                MqttCommand::SetBrightness(percent) => match lcd.set_brightness(percent) {
+                    Err(err) => {
+                        error!("MQTT set brightness failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                    Ok(()) => {
+                        info!("MQTT brightness set to {}%", percent);
+                        active_brightness = percent;
+                        manual_brightness_at = Some(Instant::now());
+                        Ok(())
+                    }
+                },
+                MqttCommand::SetCo2Alarm(ppm) => match settings.set_co2_alarm_ppm(ppm) {
+                    Ok(stored) => {
+                        info!("MQTT CO2 alarm set to {} ppm", stored);
+                        co2_alarm_ppm = stored;
+                        render_needed = true;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT set CO2 alarm failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetCo2Smoothing(window) => match settings.set_co2_smoothing(window) {
+                    Ok(stored) => {
+                        info!("MQTT CO2 smoothing set to {} readings", stored);
+                        co2_smoother.set_window(stored);
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT set CO2 smoothing failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetTempHumSource(source) => match settings.set_temp_hum_source(source) {
+                    Ok(()) => {
+                        let resolved =
+                            resolve_temp_hum_source(source, presence, co2.reports_temp_humidity());
+                        info!(
+                            "MQTT temp/humidity source set to {}, using {}",
+                            source.as_str(),
+                            resolved.map_or("none", TempHumSource::as_str)
+                        );
+                        if resolved != th_source {
+                            th_source = resolved;
+                            // Different sensors disagree slightly; don't blend them.
+                            temp_ema = Ema::new(DISPLAY_EMA_ALPHA);
+                            humidity_ema = Ema::new(DISPLAY_EMA_ALPHA);
+                            temperature_c = None;
+                            humidity_pct = None;
+                            telemetry.mark_stale(TelemetryField::Temp);
+                            telemetry.mark_stale(TelemetryField::Humidity);
+                            sensors_read.temp_hum = false;
+                            last_env_read = Instant::now() - env_interval;
                             render_needed = true;
                         }
+                        Ok(())
                     }
-                    MqttCommand::SetAbc(enabled) => {
-                        if let Err(err) = mhz19b.set_abc(enabled) {
-                            error!("MQTT set ABC failed: {:?}", err);
-                        } else {
-                            info!("MQTT set ABC: {}", enabled);
+                    Err(err) => {
+                        error!("MQTT set temp/humidity source failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetTheme(preset) => match settings.set_theme(preset) {
+                    Ok(()) => {
+                        info!("MQTT theme set to {}", preset.as_str());
+                        theme = preset;
+                        render_needed = true;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT set theme failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetLayout(next) => match settings.set_layout(next) {
+                    Ok(()) => {
+                        info!("MQTT layout set to {}", next.as_str());
+                        layout = next;
+                        big_page = BigPage::Co2;
+                        big_page_at = Instant::now();
+                        render_needed = true;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT set layout failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetLcdInvert(invert) => match lcd.set_invert(invert) {
+                    Ok(()) => {
+                        info!("MQTT LCD inversion set to {}", invert);
+                        if let Err(err) = settings.set_lcd_invert(invert) {
+                            warn!("LCD inversion save failed: {:?}", err);
                         }
+                        Ok(())
                     }
-                    MqttCommand::SetBrightness(percent) => {
-                        if let Err(err) = lcd.set_brightness(percent) {
-                            error!("MQTT set brightness failed: {:?}", err);
-                        } else {
-                            info!("MQTT brightness set to {}%", percent);
+                    Err(err) => {
+                        error!("MQTT set LCD inversion failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetTempUnit(unit) => match settings.set_temp_unit(unit) {
+                    Ok(()) => {
+                        info!("MQTT temperature unit set to {}", unit.symbol());
+                        temp_unit = unit;
+                        render_needed = true;
+                        match mqtt.as_mut().map(|mqtt| mqtt.set_temp_unit(unit)) {
+                            Some(Err(err)) => {
+                                warn!("MQTT discovery re-publish failed: {:?}", err);
+                                Err(err.to_string())
+                            }
+                            _ => Ok(()),
                         }
                     }
-                    MqttCommand::Reboot => unsafe {
-                        info!("MQTT reboot requested");
-                        esp_restart();
-                    },
+                    Err(err) => {
+                        error!("MQTT set temperature unit failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetTempDecimals(decimals) => match settings.set_temp_decimals(decimals) {
+                    Ok(()) => {
+                        info!("MQTT temperature decimals set to {}", decimals);
+                        temp_decimals = decimals;
+                        render_needed = true;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT set temperature decimals failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::ResetPeak => match settings.set_co2_peak(None) {
+                    Ok(()) => {
+                        info!("MQTT CO2 peak reset (was {:?} ppm)", co2_peak.peak());
+                        co2_peak.reset();
+                        saved_co2_peak = None;
+                        render_needed = true;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT reset CO2 peak failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::ResetCrashCount => match settings.reset_crash_count() {
+                    Ok(()) => {
+                        info!("MQTT crash count reset (was {})", crash_count);
+                        crash_count = 0;
+                        if let Some(mqtt) = mqtt.as_mut() {
+                            if let Err(err) = mqtt.publish_crash_count(crash_count) {
+                                warn!("MQTT crash count publish failed: {:?}", err);
+                            }
+                        }
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("MQTT reset crash count failed: {:?}", err);
+                        Err(err.to_string())
+                    }
+                },
+                MqttCommand::SetRelay(mode) => match relay.as_mut() {
+                    Some(relay) => {
+                        info!("MQTT relay mode set to {}", mode.as_str());
+                        relay.set_mode(mode);
+                        Ok(())
+                    }
+                    None => Err("no relay".to_string()),
+                },
+                MqttCommand::ReportSettings if mqtt.is_none() => Err("mqtt offline".to_string()),
+                MqttCommand::ReportSettings => {
+                    settings_report_due = true;
+                    Ok(())
+                }
+                MqttCommand::FactoryReset => {
+                    let confirmed = factory_reset_armed_at
+                        .is_some_and(|at| at.elapsed() < FACTORY_RESET_CONFIRM);
+                    if !confirmed {
+                        warn!("MQTT factory reset armed, repeat within 10s to confirm");
+                        factory_reset_armed_at = Some(Instant::now());
+                        Err("confirm".to_string())
+                    } else {
+                        factory_reset_armed_at = None;
+                        warn!("MQTT factory reset confirmed");
+                        event_log::push(Level::Warn, "Factory reset");
+                        match settings.factory_reset() {
+                            Err(err) => {
+                                error!("Factory reset failed: {:?}", err);
+                                Err(err.to_string())
+                            }
+                            Ok(()) => {
+                                info!("Factory reset done, rebooting");
+                                send_ack(&mut mqtt, origin, name, &Ok(()));
+                                thread::sleep(Duration::from_millis(300));
+                                unsafe { esp_restart() };
+                            }
+                        }
+                    }
+                }
+                MqttCommand::Reboot => {
+                    info!("MQTT reboot requested");
+                    send_ack(&mut mqtt, origin, name, &Ok(()));
+                    // Give the MQTT task a moment to flush the ack before restarting.
+                    thread::sleep(Duration::from_millis(300));
+                    unsafe { esp_restart() };
                 }
+            };
+            if result.is_ok() && throttled {
+                command_throttle.record(&cmd);
+            }
+            // Keep the retained settings in step, so HA never syncs back to
+            // a value that was just changed.
+            if result.is_ok() && cmd.changes_settings() {
+                settings_report_due = true;
             }
+            send_ack(&mut mqtt, origin, name, &result);
+        }
+
+        if settings_report_due {
+            if let Some(mqtt) = mqtt.as_mut() {
+                let report = SettingsReport {
+                    brightness: active_brightness,
+                    abc: abc_enabled,
+                    co2_alarm_ppm,
+                    co2_smoothing: co2_smoother.window(),
+                    co2_interval_ms: co2_interval.as_millis() as u64,
+                    env_interval_ms: env_interval.as_millis() as u64,
+                    theme,
+                    layout,
+                    lcd_invert: lcd.inverted(),
+                    temp_unit,
+                    temp_decimals,
+                    temp_hum_source: settings.temp_hum_source(),
+                    relay: relay.as_ref().map(|relay| relay.mode()),
+                };
+                if let Err(err) = mqtt.publish_settings(&report) {
+                    warn!("MQTT settings publish failed: {:?}", err);
+                }
+            }
+            settings_report_due = false;
         }
 
         if dimming_in_progress && dimmed_brightness > 0 {
             dimmed_brightness = dimmed_brightness.saturating_sub(dimming_step);
-            lcd.set_brightness(dimmed_brightness)?;
+            // Already a stepped fade; ramping each step would stall the loop.
+            lcd.set_brightness_immediate(dimmed_brightness)?;
         }
 
-        if last_env_read.elapsed() >= env_interval {
-            match sht31.read(&mut i2c) {
+        // Temperature/humidity sample from whichever sensor is the active source.
+        let mut env_sample: Option<(f32, f32)> = None;
+        if th_source == Some(TempHumSource::Sht31) && last_env_read.elapsed() >= env_interval {
+            #[cfg(not(feature = "demo-mode"))]
+            let sht_result = if sht_periodic {
+                sht31.fetch_periodic(&mut i2c)
+            } else {
+                sht31.read(&mut i2c)
+            };
+            #[cfg(feature = "demo-mode")]
+            let sht_result = demo.sht_reading();
+            match sht_result {
                 Ok(reading) => {
-                    let new_temp = reading.temperature_c;
-                    let new_humidity = reading.humidity_pct.clamp(0.0, 100.0).round() as u8;
-                    let new_temp_display = (new_temp * 10.0).round() as i32;
-                    if Some(new_temp_display) != last_temp_display
-                        || Some(new_humidity) != last_humidity_display
-                    {
-                        render_needed = true;
-                        last_temp_display = Some(new_temp_display);
-                        last_humidity_display = Some(new_humidity);
-                    }
-                    temperature_c = Some(new_temp);
-                    humidity_pct = Some(new_humidity);
+                    json_event!(
+                        Level::Debug,
+                        "sht31_read",
+                        "temp_c" => reading.temperature_c,
+                        "humidity_pct" => reading.humidity_pct,
+                    );
+                    env_sample = Some((reading.temperature_c, reading.humidity_pct));
                 }
                 Err(err) => {
                     error!("SHT31 read error: {:?}", err);
+                    event_log::push(Level::Error, "SHT31 read failed");
+                    json_event!(Level::Error, "sht31_read_error", "error" => format!("{:?}", err));
                 }
             }
             last_env_read = Instant::now();
         }
 
-        if last_mhz_read.elapsed() >= mhz_interval {
-            match mhz19b.read_ppm_with_frame(2000) {
-                Ok((ppm, _frame)) => {
-                    if last_co2_display != Some(ppm) || last_co2_error {
+        let co2_warmup_elapsed = co2_warmup_started.elapsed();
+        let co2_warming_up = presence.co2 && co2_warmup_elapsed < co2_warmup;
+        if co2_warming_up && last_warmup_secs != Some(co2_warmup_elapsed.as_secs()) {
+            // Once a second, so the bar and countdown keep moving.
+            render_needed = true;
+            last_warmup_secs = Some(co2_warmup_elapsed.as_secs());
+        }
+
+        if presence.co2 && !co2_warming_up && last_co2_read.elapsed() >= co2_interval {
+            #[cfg(not(feature = "demo-mode"))]
+            let co2_result = co2.read(&mut i2c);
+            #[cfg(feature = "demo-mode")]
+            let co2_result = anyhow::Ok(Co2Reading {
+                ppm: demo.sample().co2_ppm,
+                temperature_c: None,
+                humidity_pct: None,
+            });
+            match co2_result {
+                Ok(Co2Reading {
+                    ppm: raw_ppm,
+                    temperature_c: co2_temp,
+                    humidity_pct: co2_humidity,
+                }) => {
+                    if th_source == Some(TempHumSource::Scd4x) {
+                        env_sample = co2_temp.zip(co2_humidity);
+                    }
+                    let ppm = compensate_temperature(raw_ppm, temperature_c);
+                    json_event!(Level::Debug, "co2_read", "sensor" => co2.name(), "ppm" => ppm, "raw_ppm" => raw_ppm);
+                    co2_raw = Some(raw_ppm);
+                    // The median runs on raw readings; compensation is applied
+                    // to its output.
+                    let smoothed =
+                        compensate_temperature(co2_smoother.push(raw_ppm), temperature_c);
+                    if last_co2_display != Some(smoothed) || last_co2_error {
                         render_needed = true;
-                        last_co2_display = Some(ppm);
+                        last_co2_display = Some(smoothed);
                         last_co2_error = false;
                     }
+                    co2_smoothed = Some(smoothed);
                     co2_value = Some(ppm);
+                    telemetry.set_co2(ppm, raw_ppm);
+                    sensors_read.co2 = true;
+                    co2_quality.update(ppm);
                     co2_error = false;
-                    mhz_error_count = 0;
+                    co2_error_count = 0;
+                    if calibrating_until.is_none() && co2_history.push(ppm) {
+                        if let Err(err) = settings.save_co2_history(&co2_history.points()) {
+                            warn!("CO2 history save failed: {:?}", err);
+                        }
+                    }
+                    let next_interval =
+                        co2_history.adaptive_interval(co2_interval_min, co2_interval_max);
+                    if next_interval.as_secs() != co2_interval.as_secs() {
+                        debug!("CO2 poll interval now {} ms", next_interval.as_millis());
+                    }
+                    co2_interval = next_interval;
+                    // Warmup and out-of-range readings never get here; calibration
+                    // output is skipped like for the history.
+                    if calibrating_until.is_none() && co2_peak.update(ppm) {
+                        render_needed = true;
+                    }
+                    if calibrating_until.is_none()
+                        && occupancy.update(ppm, co2_history.slope_ppm_per_min())
+                    {
+                        render_needed = true;
+                        let state = if occupancy.occupied() { "occupied" } else { "empty" };
+                        info!("Occupancy (from CO2 trend): {}", state);
+                        event_log::push(Level::Info, &format!("Room {}", state));
+                    }
                 }
                 Err(err) => {
-                    error!("MH-Z19B read error: {:?}", err);
+                    error!("{} read error: {:?}", co2.name(), err);
+                    event_log::push(Level::Error, &format!("{} read: {}", co2.name(), err));
+                    json_event!(Level::Error, "co2_read_error", "sensor" => co2.name(), "error" => err.to_string());
                     if !last_co2_error || last_co2_display.is_some() {
                         render_needed = true;
                         last_co2_display = None;
                         last_co2_error = true;
                     }
                     co2_value = None;
+                    co2_smoothed = None;
+                    co2_raw = None;
+                    telemetry.mark_stale(TelemetryField::Co2);
                     co2_error = true;
-                    mhz_error_count = mhz_error_count.saturating_add(1);
-                    if mhz_error_count >= MHZ_ERR_REINIT_THRESHOLD {
+                    co2_error_count = co2_error_count.saturating_add(1);
+                    if co2_error_count >= CO2_ERR_RECOVER_THRESHOLD {
                         error!(
-                            "MH-Z19B consecutive errors reached {}, reinitializing UART",
-                            MHZ_ERR_REINIT_THRESHOLD
+                            "{} consecutive errors reached {}, recovering",
+                            co2.name(),
+                            CO2_ERR_RECOVER_THRESHOLD
                         );
-                        if let Err(err) = mhz19b.reinit_uart() {
-                            error!("MH-Z19B UART reinit failed: {:?}", err);
+                        event_log::push(Level::Warn, &format!("{} recover", co2.name()));
+                        json_event!(Level::Warn, "co2_recover", "sensor" => co2.name(), "errors" => CO2_ERR_RECOVER_THRESHOLD);
+                        if let Err(err) = co2.recover() {
+                            error!("{} recover failed: {:?}", co2.name(), err);
                         }
-                        mhz_error_count = 0;
+                        co2_error_count = 0;
                     }
                 }
             }
-            last_mhz_read = Instant::now();
+            #[cfg(feature = "debug-frames")]
+            if let (Some(mqtt), Some(frame)) = (mqtt.as_mut(), co2.take_raw_frame()) {
+                if let Err(err) = mqtt.publish_co2_frame(&frame) {
+                    warn!("MQTT raw frame publish failed: {:?}", err);
+                }
+            }
+            last_co2_read = Instant::now();
         }
 
-        if last_ota_check.elapsed() >= OTA_CHECK_INTERVAL {
+        if let Some(relay) = relay.as_mut() {
+            // Calibration output is not a real reading; hold the relay meanwhile.
+            match relay.update(co2_value.filter(|_| calibrating_until.is_none())) {
+                Ok(Some(on)) => {
+                    let msg = format!("Relay {} ({})", if on { "on" } else { "off" }, relay.mode().as_str());
+                    info!("{}", msg);
+                    event_log::push(Level::Info, &msg);
+                    if let Some(mqtt) = mqtt.as_mut() {
+                        if let Err(err) = mqtt.publish_relay_state(on) {
+                            warn!("MQTT relay state publish failed: {:?}", err);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => error!("Relay switch failed: {:?}", err),
+            }
+        }
+
+        if co2_peak.peak() != saved_co2_peak
+            && last_co2_peak_save.map_or(true, |at| at.elapsed() >= CO2_PEAK_SAVE_INTERVAL)
+        {
+            match settings.set_co2_peak(co2_peak.peak()) {
+                Ok(()) => saved_co2_peak = co2_peak.peak(),
+                Err(err) => warn!("CO2 peak save failed: {:?}", err),
+            }
+            last_co2_peak_save = Some(Instant::now());
+        }
+
+        if let Some((new_temp, raw_humidity)) = env_sample {
+            let new_humidity = raw_humidity.clamp(0.0, 100.0).round() as u8;
+            let smoothed_temp = temp_ema.update(new_temp);
+            let smoothed_humidity = humidity_ema.update(raw_humidity.clamp(0.0, 100.0));
+            let new_temp_display = (smoothed_temp * 10.0).round() as i32;
+            let new_humidity_display = smoothed_humidity.round() as u8;
+            if Some(new_temp_display) != last_temp_display
+                || Some(new_humidity_display) != last_humidity_display
+            {
+                render_needed = true;
+                last_temp_display = Some(new_temp_display);
+                last_humidity_display = Some(new_humidity_display);
+            }
+            temperature_c = Some(new_temp);
+            humidity_pct = Some(new_humidity);
+            telemetry.set_temp(new_temp);
+            telemetry.set_humidity(new_humidity);
+            sensors_read.temp_hum = true;
+            last_env_ok = Some(Instant::now());
+            temp_hum_stats.update(new_temp, raw_humidity.clamp(0.0, 100.0));
+            if mold_risk.update(raw_humidity) {
+                render_needed = true;
+                if mold_risk.active() {
+                    warn!("Mold risk: humidity above {}% for too long", MOLD_RISK_RH_PCT);
+                    event_log::push(Level::Warn, "Mold risk: sustained high humidity");
+                } else {
+                    event_log::push(Level::Info, "Mold risk cleared");
+                }
+            }
+        }
+
+        if !DEMO_MODE && last_ota_check.elapsed() >= OTA_CHECK_INTERVAL {
             if let Some(wifi) = wifi.as_mut() {
                 if let Err(err) = check_and_update(wifi) {
                     error!("OTA check failed: {:?}", err);
+                    event_log::push(Level::Error, "OTA check failed");
+                    json_event!(Level::Error, "ota_error", "error" => err.to_string());
                 }
             }
             last_ota_check = Instant::now();
         }
 
         if last_battery_read.elapsed() >= battery_interval {
-            match battery.read_voltage() {
-                Ok(voltage) => {
+            #[cfg(not(feature = "demo-mode"))]
+            let battery_result = battery.read(&mut i2c);
+            #[cfg(feature = "demo-mode")]
+            let battery_result = Some(BatteryReading {
+                voltage_v: demo.sample().battery_v,
+                soc_pct: None,
+            });
+            match battery_result {
+                Some(BatteryReading {
+                    voltage_v: voltage,
+                    soc_pct,
+                }) => {
                     let display_cv = (voltage * 100.0).round() as i32;
                     if last_battery_display != Some(display_cv) {
                         render_needed = true;
                         last_battery_display = Some(display_cv);
                     }
                     battery_v = Some(voltage);
+                    telemetry.set_battery(voltage, soc_pct);
+                    if critical_battery.update(voltage) {
+                        shutdown_low_battery(&mut lcd, &mut mqtt, &mut frame, theme.theme(), voltage);
+                    }
+                }
+                // Shown as "--" and published as null until a read succeeds.
+                None => {
+                    if last_battery_display.is_some() {
+                        render_needed = true;
+                        last_battery_display = None;
+                    }
+                    battery_v = None;
+                    telemetry.mark_stale(TelemetryField::Battery);
                 }
-                Err(err) => error!("Battery read error: {:?}", err),
             }
             last_battery_read = Instant::now();
         }
 
-        if last_mqtt_publish.elapsed() >= MQTT_PUBLISH_INTERVAL {
+        let quiet = quiet_hours.is_some_and(|q| q.is_active_now());
+        let manual_hold = manual_brightness_at.is_some_and(|at| at.elapsed() < AMBIENT_MANUAL_HOLD);
+        if !quiet
+            && !manual_hold
+            && !dimming_in_progress
+            && last_ambient_read.elapsed() >= AMBIENT_INTERVAL
+        {
+            if let Some(sensor) = ambient.as_mut() {
+                match sensor.read_brightness() {
+                    Ok(percent) => {
+                        // Small deadband so ADC noise does not flicker the backlight.
+                        if percent.abs_diff(active_brightness) >= 2 {
+                            lcd.set_brightness(percent)?;
+                            active_brightness = percent;
+                            dimmed_brightness = percent;
+                        }
+                    }
+                    Err(err) => {
+                        warn!("Ambient light read failed, using manual brightness: {:?}", err);
+                        ambient = None;
+                    }
+                }
+            }
+            last_ambient_read = Instant::now();
+        }
+
+        if last_mqtt_publish.elapsed() >= STATUS_PUBLISH_INTERVAL {
             if let Some(mqtt) = mqtt.as_mut() {
-                let telemetry = MqttTelemetry {
-                    co2_ppm: co2_value,
-                    temp_c: temperature_c,
-                    humidity_pct,
-                    battery_v,
-                };
-                if let Err(err) = mqtt.publish_status(&telemetry) {
-                    warn!("MQTT publish failed: {:?}", err);
+                telemetry.uptime_s = Some(diagnostics::uptime_secs());
+                telemetry.touch_ok = Some(touch_health.available());
+                telemetry.temp_hum_source = th_source.map(TempHumSource::as_str);
+                telemetry.temp_range = temp_hum_stats.temp_range();
+                telemetry.humidity_range = temp_hum_stats.humidity_range();
+                telemetry.mold_risk = th_source.map(|_| mold_risk.active());
+                telemetry.co2_peak_ppm = co2_peak.peak();
+                telemetry.occupied = presence.co2.then(|| occupancy.occupied());
+                let mut snapshot = telemetry.clone();
+                if calibrating_until.is_some() {
+                    // Null while calibrating: the sensor output is not meaningful yet.
+                    snapshot.mark_stale(TelemetryField::Co2);
+                }
+                if publish_gate.should_publish(&snapshot) {
+                    if let Err(err) = mqtt.publish_status(&snapshot) {
+                        warn!("MQTT publish failed: {:?}", err);
+                    }
+                }
+                let env_ok = last_env_ok.is_some_and(|at| at.elapsed() < env_stale_after);
+                for (group, ok) in [(SensorGroup::Co2, co2_value.is_some()), (SensorGroup::TempHum, env_ok)] {
+                    if let Err(err) = mqtt.set_sensor_available(group, ok) {
+                        warn!("MQTT availability publish failed: {:?}", err);
+                    }
+                }
+                // Initial state and re-announce after a reconnect; switches publish at once.
+                if let Some(relay) = relay.as_ref() {
+                    if let Err(err) = mqtt.publish_relay_state(relay.is_on()) {
+                        warn!("MQTT relay state publish failed: {:?}", err);
+                    }
                 }
             }
             last_mqtt_publish = Instant::now();
         }
 
         let irq_pending = touch_take_pending();
-        let should_read_touch = irq_pending || touch_active;
-        let touch_in_co2 = if should_read_touch {
-            match read_touch(&mut i2c) {
+        if touch_health.maintain(&mut i2c) {
+            info!("Touch controller responding again, touch enabled");
+            event_log::push(Level::Info, "Touch available");
+        }
+        let should_read_touch = touch_health.available() && (irq_pending || touch_active);
+        let touching = if should_read_touch {
+            let result = read_touch(&mut i2c);
+            match &result {
+                Ok(_) => touch_health.record_ok(),
+                Err(_) => {
+                    if touch_health.record_error() {
+                        event_log::push(Level::Warn, "Touch unavailable");
+                    }
+                }
+            }
+            match result {
                 Ok(Some((x, y))) => {
+                    let touch_started = !touch_active;
+                    let woke_display = dimming_in_progress;
                     touch_active = true;
                     // cancel display dimming/brightening if touch detected
                     if dimming_in_progress {
                         log::info!(
                             "Touch detected - restoring brightness to {}%",
-                            DEFAULT_BRIGHTNESS
+                            active_brightness
                         );
                         dimming_in_progress = false;
                         if dimmed_brightness == 0 {
                             render_needed = true;
                         }
-                        lcd.set_brightness(DEFAULT_BRIGHTNESS)?;
-                        dimmed_brightness = DEFAULT_BRIGHTNESS;
+                        lcd.set_brightness(active_brightness)?;
+                        dimmed_brightness = active_brightness;
                     }
                     last_touch = Instant::now();
 
                     let pt = touch_to_view(x, y);
-                    co2_rect.contains(pt)
+                    if touch_started {
+                        touch_press = Some(TouchPress {
+                            at: Instant::now(),
+                            in_co2: co2_rect.contains(pt)
+                                && !show_log_page
+                                && layout == Layout::Cards,
+                            woke_display,
+                            held: false,
+                        });
+                    }
+                    true
                 }
                 Ok(None) => {
                     touch_active = false;
@@ -314,58 +1098,332 @@ fn run() -> Result<()> {
             false
         };
 
-        if let Some(until) = zero_feedback_until {
+        if let Some(until) = calibrating_until {
             if Instant::now() >= until {
-                zero_feedback_until = None;
+                info!("Zero calibration lockout cleared");
+                calibrating_until = None;
+                // Pre-calibration readings must not hold the median back.
+                co2_smoother.clear();
             }
         }
 
-        if touch_in_co2 {
-            if co2_hold_start.is_none() {
-                co2_hold_start = Some(Instant::now());
+        // Long-press on the CO2 card: zero calibration; elsewhere on the main
+        // page: toggle the data hold. A tap outside the CO2 card (or anywhere on
+        // the log page) flips pages on release; in the big layout it shows the
+        // next reading instead, and the log page is left to the button.
+        match (touching, touch_press.as_mut()) {
+            (true, Some(press)) if !press.held && press.at.elapsed() >= hold_duration => {
+                press.held = true;
+                if press.in_co2 {
+                    if calibrating_until.is_some() {
+                        info!("Touch zero calibration ignored, calibration in progress");
+                    } else {
+                        event_log::push(Level::Info, "Touch zero calibration");
+                        match co2.calibrate_zero(&mut i2c) {
+                            Ok(()) => {
+                                calibrating_until = Some(Instant::now() + CALIBRATION_LOCKOUT);
+                                render_needed = true;
+                            }
+                            Err(err) => {
+                                error!("{} zero calibration failed: {:?}", co2.name(), err);
+                            }
+                        }
+                    }
+                } else if !show_log_page {
+                    display_hold = !display_hold;
+                    info!("Display hold {}", if display_hold { "on" } else { "off" });
+                    render_needed = true;
+                }
             }
-            if !co2_hold_triggered {
-                if let Some(start) = co2_hold_start {
-                    if start.elapsed() >= hold_duration {
-                        co2_hold_triggered = true;
-                        if let Err(err) = mhz19b.calibrate_zero() {
-                            error!("MH-Z19B zero calibration failed: {:?}", err);
+            (true, _) => {}
+            (false, _) => {
+                if let Some(press) = touch_press.take() {
+                    if !press.held && !press.woke_display && !press.in_co2 {
+                        if layout == Layout::Big && !show_log_page {
+                            big_page = big_page.next(SensorPresence {
+                                sht31: th_source.is_some(),
+                                ..presence
+                            });
+                            big_page_at = Instant::now();
+                        } else {
+                            show_log_page = !show_log_page;
+                        }
+                        render_needed = true;
+                    }
+                }
+            }
+        }
+
+        if let Some(event) = button.as_mut().and_then(|b| b.poll()) {
+            last_touch = Instant::now();
+            if dimming_in_progress {
+                dimming_in_progress = false;
+                if dimmed_brightness == 0 {
+                    render_needed = true;
+                }
+                lcd.set_brightness(active_brightness)?;
+                dimmed_brightness = active_brightness;
+            }
+            match event {
+                ButtonEvent::Press => {
+                    show_log_page = !show_log_page;
+                    render_needed = true;
+                }
+                ButtonEvent::LongPress if calibrating_until.is_some() => {
+                    info!("Button zero calibration ignored, calibration in progress");
+                }
+                ButtonEvent::LongPress => {
+                    event_log::push(Level::Info, "Button zero calibration");
+                    match co2.calibrate_zero(&mut i2c) {
+                        Ok(()) => {
+                            calibrating_until = Some(Instant::now() + CALIBRATION_LOCKOUT);
+                            render_needed = true;
+                        }
+                        Err(err) => {
+                            error!("{} zero calibration failed: {:?}", co2.name(), err);
                         }
-                        zero_feedback_until = Some(Instant::now() + zero_feedback_duration);
                     }
                 }
             }
-        } else {
-            co2_hold_start = None;
-            co2_hold_triggered = false;
+        }
+
+        // Night mode: backlight off outside of a short touch wake; sensors/MQTT keep running.
+        if quiet && last_touch.elapsed() >= QUIET_WAKE_DURATION && dimmed_brightness != 0 {
+            lcd.set_brightness_immediate(0)?;
+            dimmed_brightness = 0;
+            dimming_in_progress = true;
         }
 
         if last_touch.elapsed() >= DISPLAY_OFF_TIMEOUT && !dimming_in_progress {
             dimming_in_progress = true;
         }
 
-        let zero_mode = zero_feedback_until.is_some();
-        if zero_mode != last_zero_mode {
+        let calibrating = calibrating_until.is_some();
+        if calibrating != last_calibrating {
             render_needed = true;
-            last_zero_mode = zero_mode;
+            last_calibrating = calibrating;
+        }
+
+        if layout == Layout::Big && !show_log_page && big_page_at.elapsed() >= BIG_PAGE_INTERVAL {
+            let next = big_page.next(SensorPresence {
+                sht31: th_source.is_some(),
+                ..presence
+            });
+            if next != big_page {
+                big_page = next;
+                render_needed = true;
+            }
+            big_page_at = Instant::now();
         }
 
-        if dimmed_brightness != 0 && render_needed {
-            render_ui_mock1(
-                &mut frame,
-                temperature_c,
-                humidity_pct,
-                co2_value,
-                co2_error,
-                zero_mode,
-                battery_v,
-            )?;
-            lcd.flush_full(&frame)?;
-        render_needed = false;
+        let log_revision = event_log::revision();
+        if log_revision != last_log_revision {
+            if show_log_page {
+                render_needed = true;
+            }
+            last_log_revision = log_revision;
         }
 
-        thread::sleep(SLEEP_INTERFVAL);
+        if let Some(led) = status_led.as_mut() {
+            // Dark during quiet hours, like the backlight.
+            let quality = co2_value
+                .filter(|_| !co2_error && !quiet && calibrating_until.is_none())
+                .and(co2_quality.current());
+            if let Err(err) = led.show(quality) {
+                warn!("Status LED update failed: {:?}", err);
+            }
+        }
+
+        // What the screens show; the LCD passes the eased CO2 median.
+        let ui_state = |co2_ppm| UiState {
+            temperature_c: temp_ema.value(),
+            humidity_pct: last_humidity_display,
+            co2_ppm,
+            co2_error,
+            calibrating,
+            battery_v,
+            // The temp/humidity cards follow the active source, not just the SHT31.
+            presence: SensorPresence {
+                sht31: th_source.is_some(),
+                ..presence
+            },
+            co2_alarm_ppm,
+            co2_trend: co2_history.trend(),
+            co2_quality: co2_quality.current(),
+            temp_range: temp_hum_stats.temp_range(),
+            humidity_range: temp_hum_stats.humidity_range(),
+            mold_risk: mold_risk.active(),
+            occupied: occupancy.occupied(),
+            co2_warmup: co2_warming_up.then_some((co2_warmup_elapsed, co2_warmup)),
+            co2_peak: co2_peak.peak(),
+            temp_unit,
+            temp_decimals,
+            sensors_read,
+            hold: false,
+        };
+
+        if dimmed_brightness != 0 && render_needed && last_render.elapsed() >= display_interval {
+            if show_log_page {
+                render_log_page(&mut frame, &event_log::recent(log_page_lines()), theme.theme())?;
+            } else {
+                shown_co2 = match (shown_co2, co2_smoothed) {
+                    (Some(current), Some(target)) if SMOOTH_DISPLAY => {
+                        Some(ease_toward(current, target, CO2_EASE_FRACTION))
+                    }
+                    (_, target) => target,
+                };
+                let ui = if display_hold {
+                    *held_ui.get_or_insert_with(|| UiState {
+                        hold: true,
+                        ..ui_state(shown_co2)
+                    })
+                } else {
+                    held_ui = None;
+                    ui_state(shown_co2)
+                };
+                match layout {
+                    Layout::Cards => render_ui_mock1(&mut frame, &ui, theme.theme())?,
+                    Layout::Big => render_big_number(&mut frame, &ui, big_page, theme.theme())?,
+                }
+            }
+            match lcd.flush_full(&frame) {
+                // Keep redrawing until the eased CO2 value settles on the reading.
+                Ok(()) => render_needed = !show_log_page && !display_hold && shown_co2 != co2_smoothed,
+                // A bus glitch only costs this frame; retry on the next cycle.
+                Err(err @ (LcdError::Spi(_) | LcdError::Gpio(_))) => {
+                    warn!("LCD flush failed, retrying: {}", err);
+                    event_log::push(Level::Warn, "LCD flush failed");
+                }
+                Err(err) => return Err(err.into()),
+            }
+            last_render = Instant::now();
+        }
+
+        // Refreshes only every few minutes and keeps showing through night mode.
+        #[cfg(feature = "epaper")]
+        if let Some(panel) = epaper.as_mut() {
+            if panel.due() {
+                render_epaper(&mut epaper_frame, &ui_state(co2_smoothed))?;
+                if let Err(err) = panel.show(&epaper_frame) {
+                    warn!("E-paper refresh failed: {:?}", err);
+                }
+            }
+            if let Err(err) = panel.poll() {
+                warn!("E-paper sleep failed: {:?}", err);
+            }
+        }
+
+        watchdog::feed();
+        let fast_path = touch_active || last_touch.elapsed() < FAST_LOOP_HOLD;
+        thread::sleep(if fast_path {
+            FAST_SLEEP_INTERVAL.min(sleep_interval)
+        } else {
+            sleep_interval
+        });
+    }
+}
+
+// A touch from first contact to release, for tap vs. long-press handling.
+struct TouchPress {
+    at: Instant,
+    // Started on the CO2 card of the main page.
+    in_co2: bool,
+    // The touch only woke the dimmed display; a tap does nothing else.
+    woke_display: bool,
+    // The long-press action already ran.
+    held: bool,
+}
+
+// Where a command came from, so its result is reported back there.
+#[derive(Debug, Clone, Copy)]
+enum CommandOrigin {
+    Mqtt,
+    #[cfg(feature = "console")]
+    Console,
+}
+
+fn send_ack(
+    mqtt: &mut Option<mqtt::MqttClient>,
+    origin: CommandOrigin,
+    name: &str,
+    result: &Result<(), String>,
+) {
+    match origin {
+        CommandOrigin::Mqtt => {
+            if let Some(mqtt) = mqtt.as_mut() {
+                if let Err(err) = mqtt.publish_ack(name, result) {
+                    warn!("MQTT ack publish failed: {:?}", err);
+                }
+            }
+        }
+        #[cfg(feature = "console")]
+        CommandOrigin::Console => match result {
+            Ok(()) => info!("{}: ok", name),
+            Err(reason) => warn!("{}: error: {}", name, reason),
+        },
+    }
+}
+
+// Clean landing on a flat battery instead of a brownout reset loop: announce
+// offline, explain on screen, power the panel down and deep sleep. The timer
+// wakes the device to look again; a still-flat battery ends up back here.
+fn shutdown_low_battery(
+    lcd: &mut St7789<'static, esp_idf_hal::ledc::TIMER0>,
+    mqtt: &mut Option<mqtt::MqttClient>,
+    frame: &mut [Rgb565],
+    theme: &Theme,
+    voltage: f32,
+) -> ! {
+    error!("Battery critical ({:.2} V), shutting down", voltage);
+    json_event!(Level::Error, "low_battery_shutdown", "battery_v" => voltage);
+    if let Some(mqtt) = mqtt.as_mut() {
+        if let Err(err) = mqtt.publish_offline() {
+            warn!("MQTT offline publish failed: {:?}", err);
+        }
+    }
+    let shown = render_notice(frame, "LOW BATTERY", "Shutting down", theme)
+        .and_then(|()| Ok(lcd.flush_full(frame)?));
+    if let Err(err) = shown {
+        warn!("Low battery screen failed: {:?}", err);
+    }
+    // Long enough to read, and for the MQTT task to send the offline message.
+    thread::sleep(Duration::from_secs(3));
+    if let Err(err) = lcd
+        .set_brightness_immediate(0)
+        .and_then(|()| lcd.sleep())
+    {
+        warn!("LCD power down failed: {}", err);
     }
+    unsafe {
+        sys::esp_sleep_enable_timer_wakeup(LOW_BATTERY_WAKE_INTERVAL.as_micros() as u64);
+        sys::esp_deep_sleep_start();
+    }
+}
+
+// Map the configured source onto what was detected at boot; an explicit choice
+// that isn't fitted falls back to the most accurate available sensor.
+fn resolve_temp_hum_source(
+    wanted: TempHumSource,
+    presence: SensorPresence,
+    co2_has_temp_humidity: bool,
+) -> Option<TempHumSource> {
+    let sht31 = presence.sht31;
+    let scd4x = presence.co2 && co2_has_temp_humidity;
+    match wanted {
+        TempHumSource::Sht31 if sht31 => Some(TempHumSource::Sht31),
+        TempHumSource::Scd4x if scd4x => Some(TempHumSource::Scd4x),
+        _ if sht31 => Some(TempHumSource::Sht31),
+        _ if scd4x => Some(TempHumSource::Scd4x),
+        _ => None,
+    }
+}
+
+fn interval_ms(env: Option<&str>, default_ms: u64) -> Duration {
+    let ms = env
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default_ms);
+    Duration::from_millis(ms)
 }
 
 fn touch_to_view(x: u16, y: u16) -> Point {
@@ -1,144 +1,214 @@
 use core::fmt;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use esp_idf_hal::delay::{TickType, BLOCK};
+use esp_idf_hal::delay::FreeRtos;
 use esp_idf_hal::gpio::{InputPin, OutputPin};
+use esp_idf_hal::i2c::I2cDriver;
 use esp_idf_hal::peripheral::Peripheral;
 use esp_idf_hal::prelude::*;
-use esp_idf_hal::uart::{UartConfig, UartDriver};
-use log::{debug, error};
+use esp_idf_hal::sys::EspError;
+use esp_idf_hal::uart::config::{DataBits, StopBits};
+use esp_idf_hal::uart::{SerialError, UartConfig, UartDriver};
+use log::{info, warn};
+
+use crate::co2::{Co2Reading, Co2Source};
+use crate::sensor_hal::mhz19b as hal;
+use crate::sensor_proto::mhz19b as proto;
 
 pub const MHZ19B_BAUD: u32 = 9_600;
+// UART framing as "<data bits><N|E|O><stop bits>", e.g. "8N1" (genuine sensors)
+// or "8E1"; some clones differ. Stop bits 1, 1.5 (written "15") or 2.
+const FRAMING_ENV: Option<&str> = option_env!("MHZ19B_FRAMING");
+// Per-attempt reply timeout and number of query attempts per read
+// (build-time overridable via MHZ19B_TIMEOUT_MS / MHZ19B_READ_ATTEMPTS).
+const READ_TIMEOUT_MS_DEFAULT: u32 = 2000;
+const READ_TIMEOUT_MS_ENV: Option<&str> = option_env!("MHZ19B_TIMEOUT_MS");
+const READ_ATTEMPTS_DEFAULT: u32 = 3;
+const READ_ATTEMPTS_ENV: Option<&str> = option_env!("MHZ19B_READ_ATTEMPTS");
+// Frames dropped after a state-changing command (default
+// proto::DISCARD_FRAMES_DEFAULT).
+const DISCARD_FRAMES_ENV: Option<&str> = option_env!("MHZ19B_DISCARD_FRAMES");
+// Driver ring buffers. The hardware RX FIFO is 128 bytes and ESP-IDF requires
+// a larger software buffer; 256 holds many 9-byte replies plus line noise, so
+// a reply that lands while Wi-Fi keeps the CPU busy is kept whole until the
+// driver polls for it (stale bytes are drained before each command, so old
+// frames never pile up). TX stays unbuffered: 9-byte commands fit the
+// hardware FIFO and every command is followed by a flush (`wait_tx_done`) anyway.
+const UART_RX_BUFFER: usize = 256;
+const UART_TX_BUFFER: usize = 0;
+// Datasheet preheat time; readings before it drift heavily.
+const WARMUP_S_DEFAULT: u64 = 180;
+const WARMUP_S_ENV: Option<&str> = option_env!("MHZ19B_WARMUP_S");
+
+// Errors from `Co2Source::read` and friends (see sensor_hal::mhz19b::Error).
+pub type MhzError = hal::Error<SerialError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
 
-#[derive(Debug)]
-pub enum MhzError {
-    Timeout,
-    Frame,
-    Checksum,
-    Uart(esp_idf_hal::sys::EspError),
+// Data bits / parity / stop bits of the sensor UART.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartFraming {
+    pub data_bits: u8,
+    pub parity: Parity,
+    // In half bits: 2 = 1, 3 = 1.5, 4 = 2.
+    pub stop_half_bits: u8,
 }
 
-impl fmt::Display for MhzError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Timeout => write!(f, "timeout waiting for MH-Z19B frame"),
-            Self::Frame => write!(f, "invalid MH-Z19B frame header"),
-            Self::Checksum => write!(f, "MH-Z19B checksum mismatch"),
-            Self::Uart(err) => write!(f, "UART error: {err}"),
+impl Default for UartFraming {
+    fn default() -> Self {
+        Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_half_bits: 2,
         }
     }
 }
 
-impl std::error::Error for MhzError {}
+impl UartFraming {
+    pub fn from_env() -> Self {
+        match FRAMING_ENV.map(|v| (v, Self::parse(v))) {
+            Some((_, Some(framing))) => framing,
+            Some((value, None)) => {
+                warn!("MHZ19B_FRAMING={:?} not understood, using 8N1", value);
+                Self::default()
+            }
+            None => Self::default(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_uppercase();
+        let mut chars = value.chars();
+        let data_bits = chars.next()?.to_digit(10)? as u8;
+        let parity = match chars.next()? {
+            'N' => Parity::None,
+            'E' => Parity::Even,
+            'O' => Parity::Odd,
+            _ => return None,
+        };
+        let stop_half_bits = match chars.as_str() {
+            "1" => 2,
+            "15" | "1.5" => 3,
+            "2" => 4,
+            _ => return None,
+        };
+        (5..=8).contains(&data_bits).then_some(Self {
+            data_bits,
+            parity,
+            stop_half_bits,
+        })
+    }
 
+    fn apply(self, config: UartConfig) -> UartConfig {
+        let config = config
+            .data_bits(match self.data_bits {
+                5 => DataBits::DataBits5,
+                6 => DataBits::DataBits6,
+                7 => DataBits::DataBits7,
+                _ => DataBits::DataBits8,
+            })
+            .stop_bits(match self.stop_half_bits {
+                3 => StopBits::STOP1P5,
+                4 => StopBits::STOP2,
+                _ => StopBits::STOP1,
+            });
+        match self.parity {
+            Parity::None => config.parity_none(),
+            Parity::Even => config.parity_even(),
+            Parity::Odd => config.parity_odd(),
+        }
+    }
+}
+
+impl fmt::Display for UartFraming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
+        let stop = match self.stop_half_bits {
+            3 => "1.5",
+            4 => "2",
+            _ => "1",
+        };
+        write!(f, "{}{}{}", self.data_bits, parity, stop)
+    }
+}
+
+// The protocol (flush, resync, settling discard, floor, retries) lives in
+// sensor_hal::mhz19b, shared with the host tests; this owns the UART.
 pub struct Mhz19b<'a> {
     uart: UartDriver<'a>,
+    driver: hal::Mhz19b,
 }
 
 impl<'a> Mhz19b<'a> {
     pub fn new(uart: UartDriver<'a>) -> Self {
-        Self { uart }
+        Self {
+            uart,
+            driver: hal::Mhz19b::new(discard_frames()),
+        }
     }
+}
 
-    pub fn read_ppm(&mut self, timeout_ms: u64) -> Result<u16, MhzError> {
-        let (ppm, _frame) = self.read_ppm_with_frame(timeout_ms)?;
-        Ok(ppm)
+impl Co2Source for Mhz19b<'_> {
+    fn name(&self) -> &'static str {
+        "MH-Z19B"
     }
 
-    pub fn read_ppm_with_frame(&mut self, timeout_ms: u64) -> Result<(u16, [u8; 9]), MhzError> {
-        let frame = self.read_frame(timeout_ms)?;
-        let ppm = (u16::from(frame[2]) << 8) | u16::from(frame[3]);
-        Ok((ppm, frame))
+    fn read(&mut self, _i2c: &mut I2cDriver<'_>) -> anyhow::Result<Co2Reading> {
+        let ppm = self
+            .driver
+            .read(&mut self.uart, &mut FreeRtos, read_timeout_ms(), read_attempts())?;
+        Ok(Co2Reading {
+            ppm,
+            temperature_c: None,
+            humidity_pct: None,
+        })
     }
 
-    pub fn calibrate_zero(&mut self) -> Result<(), MhzError> {
-        let mut cmd = [0xFFu8, 0x01, 0x87, 0, 0, 0, 0, 0, 0];
-        cmd[8] = checksum(&cmd[1..8]);
-        self.uart.write(&cmd).map_err(MhzError::Uart)?;
-        self.uart.wait_tx_done(BLOCK).map_err(MhzError::Uart)?;
-        Ok(())
+    fn calibrate_zero(&mut self, _i2c: &mut I2cDriver<'_>) -> anyhow::Result<()> {
+        Ok(self.driver.calibrate_zero(&mut self.uart)?)
     }
 
-    pub fn set_abc(&mut self, enabled: bool) -> Result<(), MhzError> {
-        // ABC (automatic baseline correction) enable/disable command.
-        let abc = if enabled { 0xA0 } else { 0x00 };
-        let mut cmd = [0xFFu8, 0x01, 0x79, abc, 0, 0, 0, 0, 0];
-        cmd[8] = checksum(&cmd[1..8]);
-        self.uart.write(&cmd).map_err(MhzError::Uart)?;
-        self.uart.wait_tx_done(BLOCK).map_err(MhzError::Uart)?;
-        Ok(())
+    fn set_abc(&mut self, _i2c: &mut I2cDriver<'_>, enabled: bool) -> anyhow::Result<()> {
+        Ok(self.driver.set_abc(&mut self.uart, enabled)?)
     }
 
-    pub fn reinit_uart(&mut self) -> Result<(), MhzError> {
-        self.uart.clear_rx().map_err(MhzError::Uart)?;
-        self.uart
-            .change_baudrate(MHZ19B_BAUD.Hz())
-            .map_err(MhzError::Uart)?;
-        Ok(())
+    fn warmup(&self) -> Duration {
+        Duration::from_secs(warmup_secs())
     }
 
-    pub fn uart_mut(&mut self) -> &mut UartDriver<'a> {
-        &mut self.uart
+    fn take_raw_frame(&mut self) -> Option<Vec<u8>> {
+        self.driver.take_raw_frame().map(|frame| frame.to_vec())
     }
 
-    fn read_frame(&mut self, timeout_ms: u64) -> Result<[u8; 9], MhzError> {
-        let mut cmd = [0xFFu8, 0x01, 0x86, 0, 0, 0, 0, 0, 0];
-        cmd[8] = checksum(&cmd[1..8]);
-
-        self.uart.write(&cmd).map_err(MhzError::Uart)?;
-        self.uart.wait_tx_done(BLOCK).map_err(MhzError::Uart)?;
-
-        let mut buf = [0u8; 9];
-        let mut received = 0usize;
-        let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(1));
-
-        while received < buf.len() && Instant::now() < deadline {
-            let remaining = deadline.saturating_duration_since(Instant::now());
-            let timeout = TickType::new_millis(remaining.as_millis() as u64).ticks();
-            let n = self
-                .uart
-                .read(&mut buf[received..], timeout)
-                .map_err(MhzError::Uart)?;
-            if n == 0 {
-                continue;
-            }
-            received += n;
-        }
-
-        if received < buf.len() {
-            error!(
-                "MH-Z19B timeout: got {}/9 bytes: {:02X?}",
-                received,
-                &buf[..received]
-            );
-            return Err(MhzError::Timeout);
-        }
-
-        debug!("MH-Z19B frame: {:02X?}", buf);
-        if buf[0] != 0xFF || buf[1] != 0x86 {
-            error!("MH-Z19B frame header mismatch: {:02X?}", buf);
-            return Err(MhzError::Frame);
-        }
-
-        let expected = checksum(&buf[1..8]);
-        if buf[8] != expected {
-            error!(
-                "MH-Z19B checksum mismatch: expected {:02X}, got {:02X}, frame {:02X?}",
-                expected, buf[8], buf
-            );
-            return Err(MhzError::Checksum);
-        }
-
-        Ok(buf)
+    fn recover(&mut self) -> anyhow::Result<()> {
+        self.uart.clear_rx()?;
+        self.uart.change_baudrate(MHZ19B_BAUD.Hz())?;
+        self.driver.reset_raw_log();
+        Ok(())
     }
 }
 
 pub fn init_mhz19b<'d>(
-    uart: impl Peripheral<P = esp_idf_hal::uart::UART0> + 'd,
+    uart: impl Peripheral<P = impl esp_idf_hal::uart::Uart> + 'd,
     tx: impl Peripheral<P = impl OutputPin> + 'd,
     rx: impl Peripheral<P = impl InputPin> + 'd,
-) -> Result<Mhz19b<'d>, MhzError> {
-    let uart_cfg = UartConfig::new().baudrate(MHZ19B_BAUD.Hz());
+    framing: UartFraming,
+) -> Result<Mhz19b<'d>, EspError> {
+    let uart_cfg = framing.apply(
+        UartConfig::new()
+            .baudrate(MHZ19B_BAUD.Hz())
+            .rx_fifo_size(UART_RX_BUFFER)
+            .tx_fifo_size(UART_TX_BUFFER),
+    );
     let uart = UartDriver::new(
         uart,
         tx,
@@ -146,13 +216,35 @@ pub fn init_mhz19b<'d>(
         None::<esp_idf_hal::gpio::AnyIOPin>,
         None::<esp_idf_hal::gpio::AnyIOPin>,
         &uart_cfg,
-    )
-    .map_err(MhzError::Uart)?;
+    )?;
+    info!("MH-Z19B UART {} @ {} baud", framing, MHZ19B_BAUD);
 
     Ok(Mhz19b::new(uart))
 }
 
-fn checksum(bytes: &[u8]) -> u8 {
-    let sum: u16 = bytes.iter().map(|b| *b as u16).sum();
-    (0xFFu16 - (sum & 0xFF) + 1) as u8
+fn read_timeout_ms() -> u32 {
+    READ_TIMEOUT_MS_ENV
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(READ_TIMEOUT_MS_DEFAULT)
 }
+
+fn read_attempts() -> u32 {
+    READ_ATTEMPTS_ENV
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|v| (1..=10).contains(v))
+        .unwrap_or(READ_ATTEMPTS_DEFAULT)
+}
+
+fn warmup_secs() -> u64 {
+    WARMUP_S_ENV
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(WARMUP_S_DEFAULT)
+}
+
+fn discard_frames() -> u8 {
+    DISCARD_FRAMES_ENV
+        .and_then(|v| v.trim().parse::<u8>().ok())
+        .unwrap_or(proto::DISCARD_FRAMES_DEFAULT)
+}
+
@@ -1,14 +1,29 @@
 use std::sync::mpsc::{self, Receiver};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 // embedded-svc defines the MQTT traits and event payloads used across platforms.
 use embedded_svc::mqtt::client::{EventPayload, QoS};
 // esp-idf-svc provides the ESP-IDF backed MQTT client implementation and config.
 use esp_idf_svc::mqtt::client::{EspMqttClient, LwtConfiguration, MqttClientConfiguration};
-use log::{info, warn};
+use log::{info, warn, Level};
 
+use crate::command::{parse_command, Command};
+use crate::diagnostics;
+use crate::event_log;
+use crate::json_log::json_event;
+use crate::ota;
+use crate::pins::RELAY_GPIO;
+use crate::options::{
+    Layout, RelayMode, TempHumSource, TempUnit, ThemePreset, CO2_ALARM_MAX, CO2_ALARM_MIN,
+    CO2_ALARM_STEP, CO2_SMOOTHING_MAX, CO2_SMOOTHING_MIN,
+};
+use crate::schedule::unix_time;
+use crate::telemetry::{
+    telemetry_fields, telemetry_payload, Telemetry, ENTITY_BATTERY, ENTITY_CO2, ENTITY_HUMIDITY,
+};
+use crate::telemetry_compact::{self, COMPACT_FORMAT};
 use crate::wifi::ensure_connected;
 
 const MQTT_HOST: &str = match option_env!("MQTT_HOST") {
@@ -25,53 +40,519 @@ const MQTT_PASS: Option<&str> = match option_env!("MQTT_PASS") {
     Some(v) if !v.is_empty() => Some(v),
     _ => Some("esp32-co"),
 };
-const MQTT_CLIENT_ID: &str = match option_env!("MQTT_CLIENT_ID") {
+pub const MQTT_CLIENT_ID: &str = match option_env!("MQTT_CLIENT_ID") {
     Some(v) => v,
     None => "c6-demo",
 };
+// Device identity (HA device_id) and, unless overridden, the state/cmd topic base.
 const MQTT_PREFIX: &str = match option_env!("MQTT_PREFIX") {
     Some(v) => v,
     None => "c6-demo",
 };
+// Base for status/cmd/availability topics, e.g. "sensors/office/air".
+const MQTT_STATE_PREFIX: &str = match option_env!("MQTT_STATE_PREFIX") {
+    Some(v) if !v.is_empty() => v,
+    _ => MQTT_PREFIX,
+};
+// HomeAssistant discovery root.
+const MQTT_DISCOVERY_PREFIX: &str = match option_env!("MQTT_DISCOVERY_PREFIX") {
+    Some(v) if !v.is_empty() => v,
+    _ => "homeassistant",
+};
+// Status publishes: QoS 0/1/2 and whether the broker retains the last sample.
+const MQTT_STATUS_QOS_ENV: Option<&str> = option_env!("MQTT_STATUS_QOS");
+const MQTT_STATUS_RETAIN: bool = match option_env!("MQTT_STATUS_RETAIN") {
+    Some(v) => !matches!(v.as_bytes(), b"0" | b"false" | b"off"),
+    None => true,
+};
+// Telemetry layout: one JSON blob on `<prefix>/status` (default), or one
+// retained topic per field under `<prefix>/state/<key>` carrying either the
+// plain value ("split") or `{"value":..,"ts":..}` ("timestamped"; `ts` is left
+// out until SNTP has synced).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PublishMode {
+    Json,
+    Split,
+    Timestamped,
+}
+const PUBLISH_MODE: PublishMode = match option_env!("MQTT_PUBLISH_MODE") {
+    Some(v) => match v.as_bytes() {
+        b"split" => PublishMode::Split,
+        b"timestamped" => PublishMode::Timestamped,
+        _ => PublishMode::Json,
+    },
+    None => PublishMode::Json,
+};
+// With a compact format set (see telemetry_compact.rs), skip the JSON/per-field
+// status entirely; HA entities then stay unknown, so only for links where
+// bytes matter more than discovery.
+const MQTT_COMPACT_ONLY: bool = match option_env!("MQTT_COMPACT_ONLY") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+// Availability delivery: the LWT and every "online" use the same QoS/retain so
+// a retained "offline" is always replaced (QoS 0/1/2, default 1; retain default on).
+const MQTT_LWT_QOS_ENV: Option<&str> = option_env!("MQTT_LWT_QOS");
+const MQTT_LWT_RETAIN: bool = match option_env!("MQTT_LWT_RETAIN") {
+    Some(v) => !matches!(v.as_bytes(), b"0" | b"false" | b"off"),
+    None => true,
+};
 const OTA_BUILD: Option<&str> = option_env!("OTA_BUILD");
 const SW_VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEVICE_MODEL: &str = "ESP32-C6 Touch LCD 1.47";
+const DEVICE_MANUFACTURER: &str = "Espressif";
+
+pub const STATUS_PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+// HA marks status-driven sensors unavailable after this many missed publishes.
+const EXPIRE_AFTER_PUBLISHES: u64 = 3;
+// Publish-on-change (see PublishOnChange): the status goes out at least every
+// MQTT_HEARTBEAT_INTERVALS publish intervals, and in between only when a
+// reading moved by more than its MQTT_CHANGE_* threshold. The default of 1
+// publishes every interval.
+const HEARTBEAT_INTERVALS_DEFAULT: u32 = 1;
+const HEARTBEAT_INTERVALS_ENV: Option<&str> = option_env!("MQTT_HEARTBEAT_INTERVALS");
+const CHANGE_CO2_PPM_DEFAULT: u16 = 20;
+const CHANGE_CO2_PPM_ENV: Option<&str> = option_env!("MQTT_CHANGE_CO2_PPM");
+const CHANGE_TEMP_C_DEFAULT: f32 = 0.2;
+const CHANGE_TEMP_C_ENV: Option<&str> = option_env!("MQTT_CHANGE_TEMP_C");
+const CHANGE_HUMIDITY_PCT_DEFAULT: u8 = 1;
+const CHANGE_HUMIDITY_PCT_ENV: Option<&str> = option_env!("MQTT_CHANGE_HUMIDITY_PCT");
+const CHANGE_BATTERY_V_DEFAULT: f32 = 0.05;
+const CHANGE_BATTERY_V_ENV: Option<&str> = option_env!("MQTT_CHANGE_BATTERY_V");
+
+// Discovery configs are sent back-to-back at connect; pace them so the ESP-IDF
+// outbox doesn't overflow, and retry a config the client refused to enqueue.
+const DISCOVERY_PUBLISH_GAP: Duration = Duration::from_millis(20);
+const DISCOVERY_PUBLISH_ATTEMPTS: u32 = 3;
+const DISCOVERY_RETRY_DELAY: Duration = Duration::from_millis(200);
 
-const PAYLOAD_ONLINE: &str = "online";
-const PAYLOAD_OFFLINE: &str = "offline";
+// Keepalive ping interval and per-operation network timeout, in seconds. The
+// broker publishes the LWT ("offline") after ~1.5x keepalive without traffic, so
+// this also bounds how long a silently dead device still shows as available.
+const MQTT_KEEPALIVE_S_DEFAULT: u64 = 30;
+const MQTT_KEEPALIVE_S_MIN: u64 = 5;
+const MQTT_KEEPALIVE_S_ENV: Option<&str> = option_env!("MQTT_KEEPALIVE_S");
+const MQTT_NETWORK_TIMEOUT_S_DEFAULT: u64 = 10;
+const MQTT_NETWORK_TIMEOUT_S_ENV: Option<&str> = option_env!("MQTT_NETWORK_TIMEOUT_S");
+
+// Retry the broker quickly after a drop so "online" overwrites the LWT before
+// HA has shown the device unavailable for long (ESP-IDF default is 10s).
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+// Drops shorter than this are logged as a blip rather than a disconnect.
+const OFFLINE_GRACE: Duration = Duration::from_secs(30);
+
+// Availability payloads, for brokers/integrations that expect e.g. "1"/"0".
+const PAYLOAD_ONLINE: &str = match option_env!("MQTT_PAYLOAD_ONLINE") {
+    Some(v) if !v.is_empty() => v,
+    _ => "online",
+};
+const PAYLOAD_OFFLINE: &str = match option_env!("MQTT_PAYLOAD_OFFLINE") {
+    Some(v) if !v.is_empty() => v,
+    _ => "offline",
+};
 
-#[derive(Debug)]
-pub enum Command {
-    ZeroCalibrate,
-    SetAbc(bool),
-    SetBrightness(u8),
-    Reboot,
+// Current runtime settings, published (retained) to `<prefix>/settings` once
+// after boot, on the `settings` command and after every setter command (see
+// Command::changes_settings). The ABC switch and the number entities read
+// their state from it, so HomeAssistant shows what the device actually uses,
+// also after either side restarts.
+#[derive(Debug, Clone)]
+pub struct SettingsReport {
+    pub brightness: u8,
+    pub abc: bool,
+    pub co2_alarm_ppm: u16,
+    pub co2_smoothing: u8,
+    // Current (adaptive) CO2 poll interval and the temp/humidity interval.
+    pub co2_interval_ms: u64,
+    pub env_interval_ms: u64,
+    pub theme: ThemePreset,
+    pub layout: Layout,
+    pub lcd_invert: bool,
+    pub temp_unit: TempUnit,
+    pub temp_decimals: u8,
+    pub temp_hum_source: TempHumSource,
+    // None without a relay fitted.
+    pub relay: Option<RelayMode>,
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct Telemetry {
-    pub co2_ppm: Option<u16>,
-    pub temp_c: Option<f32>,
-    pub humidity_pct: Option<u8>,
-    pub battery_v: Option<f32>,
+// Sensors with their own availability topic, so HA can mark e.g. only the CO2
+// entity unavailable while temperature keeps updating.
+#[derive(Debug, Clone, Copy)]
+pub enum SensorGroup {
+    Co2,
+    TempHum,
 }
 
 struct Topics {
     availability: String,
+    co2_availability: String,
+    temp_hum_availability: String,
     status: String,
+    // Base of the per-field topics used by the split/timestamped publish modes.
+    state: String,
     cmd: String,
+    ack: String,
+    reset_reason: String,
+    // Compact telemetry (CSV or MessagePack), only with MQTT_COMPACT_FORMAT.
+    compact: String,
+    // Retained count of abnormal resets (see Settings::crash_count).
+    crash_count: String,
+    // Retained "ON"/"OFF" of the optional relay output.
+    relay: String,
+    // Retained JSON of the current settings (see SettingsReport).
+    settings: String,
+    // Hex dump of each raw CO2 sensor reply (debug-frames builds only).
+    #[cfg(feature = "debug-frames")]
+    co2_raw: String,
+    device: String,
+    discovery: String,
+}
+
+impl Topics {
+    fn field(&self, key: &str) -> String {
+        format!("{}/{key}", self.state)
+    }
+}
+
+fn heartbeat_intervals() -> u32 {
+    HEARTBEAT_INTERVALS_ENV
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(HEARTBEAT_INTERVALS_DEFAULT)
+        .max(1)
+}
+
+// Decides, once per publish interval, whether the status is worth sending.
+// Compared fields: CO2, temperature, humidity and battery voltage against
+// their thresholds (strictly more than the threshold counts); any value
+// appearing or going null, and changes of battery %, mold risk, CO2 peak or
+// the temp/humidity source. Uptime and the daily ranges alone never trigger
+// a publish.
+pub struct PublishOnChange {
+    heartbeat: u32,
+    co2_ppm: u16,
+    temp_c: f32,
+    humidity_pct: u8,
+    battery_v: f32,
+    last: Option<Telemetry>,
+    skipped: u32,
+}
+
+impl PublishOnChange {
+    pub fn from_env() -> Self {
+        Self {
+            heartbeat: heartbeat_intervals(),
+            co2_ppm: CHANGE_CO2_PPM_ENV
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(CHANGE_CO2_PPM_DEFAULT),
+            temp_c: CHANGE_TEMP_C_ENV
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(CHANGE_TEMP_C_DEFAULT),
+            humidity_pct: CHANGE_HUMIDITY_PCT_ENV
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(CHANGE_HUMIDITY_PCT_DEFAULT),
+            battery_v: CHANGE_BATTERY_V_ENV
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(CHANGE_BATTERY_V_DEFAULT),
+            last: None,
+            skipped: 0,
+        }
+    }
+
+    // True if `telemetry` should be published now; it then becomes the
+    // reference for the next comparison.
+    pub fn should_publish(&mut self, telemetry: &Telemetry) -> bool {
+        // Compare what would go out, i.e. with stale fields already null.
+        let next = telemetry.fresh(Instant::now());
+        let publish = self.skipped + 1 >= self.heartbeat
+            || self.last.as_ref().map_or(true, |last| self.changed(last, &next));
+        if publish {
+            self.last = Some(next);
+            self.skipped = 0;
+        } else {
+            self.skipped += 1;
+        }
+        publish
+    }
+
+    fn changed(&self, last: &Telemetry, next: &Telemetry) -> bool {
+        fn moved<T: PartialEq>(a: Option<T>, b: Option<T>, beyond: impl Fn(T, T) -> bool) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => beyond(a, b),
+                (a, b) => a != b,
+            }
+        }
+        moved(last.co2_ppm, next.co2_ppm, |a, b| a.abs_diff(b) > self.co2_ppm)
+            || moved(last.temp_c, next.temp_c, |a, b| (a - b).abs() > self.temp_c)
+            || moved(last.humidity_pct, next.humidity_pct, |a, b| {
+                a.abs_diff(b) > self.humidity_pct
+            })
+            || moved(last.battery_v, next.battery_v, |a, b| (a - b).abs() > self.battery_v)
+            || last.battery_pct != next.battery_pct
+            || last.mold_risk != next.mold_risk
+            || last.occupied != next.occupied
+            || last.touch_ok != next.touch_ok
+            || last.co2_peak_ppm != next.co2_peak_ppm
+            || last.temp_hum_source != next.temp_hum_source
+    }
 }
 
 pub struct MqttClient {
     client: EspMqttClient<'static>,
     cmd_rx: Receiver<Command>,
+    conn_rx: Receiver<bool>,
     topics: Topics,
+    last_telemetry: Option<Telemetry>,
+    status_qos: QoS,
+    status_retain: bool,
+    lwt_qos: QoS,
+    // Start of the current outage, if disconnected, and whether it outlasted
+    // OFFLINE_GRACE and was logged.
+    disconnected_at: Option<Instant>,
+    disconnect_logged: bool,
+    // Kept to re-send discovery after a reconnect.
+    mac: Option<String>,
+    // Last published per-sensor availability (Co2, TempHum); None = not sent yet.
+    sensor_available: [Option<bool>; 2],
+    // Last published relay state; None = not sent yet.
+    relay_state: Option<bool>,
+    // Unit of the temperature fields and the HA temperature entity.
+    temp_unit: TempUnit,
+    // A fuel gauge reports state of charge; without one there is no entity.
+    battery_soc: bool,
 }
 
 impl MqttClient {
     pub fn publish_status(&mut self, telemetry: &Telemetry) -> Result<()> {
-        let payload = telemetry_payload(telemetry);
+        let telemetry = &telemetry.fresh(Instant::now());
+        if telemetry.has_any() {
+            self.last_telemetry = Some(telemetry.clone());
+        }
+        if let Some(format) = COMPACT_FORMAT {
+            let payload = telemetry_compact::encode(format, telemetry);
+            self.client.publish(
+                &self.topics.compact,
+                self.status_qos,
+                self.status_retain,
+                &payload,
+            )?;
+            if MQTT_COMPACT_ONLY {
+                return Ok(());
+            }
+        }
+        if PUBLISH_MODE == PublishMode::Json {
+            let payload = telemetry_payload(telemetry, self.temp_unit);
+            self.client.publish(
+                &self.topics.status,
+                self.status_qos,
+                self.status_retain,
+                payload.as_bytes(),
+            )?;
+            return Ok(());
+        }
+
+        // Per-field topics are always retained so the recorder sees the last
+        // value (and its timestamp) even across gaps.
+        let ts = unix_time();
+        for (key, value) in telemetry_fields(telemetry, self.temp_unit) {
+            let payload = match (PUBLISH_MODE, ts) {
+                (PublishMode::Timestamped, Some(ts)) => format!(r#"{{"value":{value},"ts":{ts}}}"#),
+                (PublishMode::Timestamped, None) => format!(r#"{{"value":{value}}}"#),
+                // HA's MQTT sensor maps "None" to unknown.
+                _ if value == "null" => "None".to_string(),
+                _ => value.trim_matches('"').to_string(),
+            };
+            self.client.publish(
+                &self.topics.field(key),
+                self.status_qos,
+                true,
+                payload.as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Handle connection events from the MQTT event thread. After a reconnect,
+    // announce "online" first (the broker may just have sent our LWT), then
+    // restore the subscription and re-send the last good status so
+    // HomeAssistant entities populate without waiting a full cycle.
+    pub fn poll(&mut self) -> Result<()> {
+        let mut reconnected = false;
+        while let Ok(connected) = self.conn_rx.try_recv() {
+            if connected {
+                reconnected = true;
+            } else {
+                warn!("MQTT disconnected");
+                json_event!(Level::Warn, "mqtt_disconnect");
+                self.disconnected_at.get_or_insert_with(Instant::now);
+            }
+        }
+        if !reconnected {
+            let past_grace = self
+                .disconnected_at
+                .is_some_and(|at| at.elapsed() >= OFFLINE_GRACE);
+            if past_grace && !self.disconnect_logged {
+                // A real outage, not a blip: worth a line in the on-device log.
+                event_log::push(Level::Warn, "MQTT disconnected");
+                self.disconnect_logged = true;
+            }
+            return Ok(());
+        }
+
+        self.client.publish(
+            &self.topics.availability,
+            self.lwt_qos,
+            MQTT_LWT_RETAIN,
+            PAYLOAD_ONLINE.as_bytes(),
+        )?;
+        self.disconnect_logged = false;
+        match self.disconnected_at.take() {
+            Some(at) if at.elapsed() < OFFLINE_GRACE => {
+                info!("MQTT reconnected after {}s blip", at.elapsed().as_secs());
+                event_log::push(Level::Info, "MQTT blip");
+                json_event!(Level::Info, "mqtt_reconnect", "offline_s" => at.elapsed().as_secs());
+            }
+            outage => {
+                info!("MQTT reconnected");
+                event_log::push(Level::Info, "MQTT reconnected");
+                json_event!(
+                    Level::Info,
+                    "mqtt_reconnect",
+                    "offline_s" => outage.map(|at| at.elapsed().as_secs()),
+                );
+            }
+        }
+        self.client.subscribe(&self.topics.cmd, QoS::AtLeastOnce)?;
+        // The broker may have lost retained configs (restart without persistence).
+        publish_discovery(
+            &mut self.client,
+            &self.topics,
+            self.mac.as_deref(),
+            self.temp_unit,
+            self.battery_soc,
+        )?;
+        if let Some(telemetry) = self.last_telemetry.clone() {
+            self.publish_status(&telemetry)?;
+        }
+        // Re-announce per-sensor availability and the relay on the next update.
+        self.sensor_available = [None; 2];
+        self.relay_state = None;
+        Ok(())
+    }
+
+    // Publish (retained) a sensor group's availability when it changes.
+    pub fn set_sensor_available(&mut self, group: SensorGroup, available: bool) -> Result<()> {
+        let (slot, topic) = match group {
+            SensorGroup::Co2 => (0, &self.topics.co2_availability),
+            SensorGroup::TempHum => (1, &self.topics.temp_hum_availability),
+        };
+        if self.sensor_available[slot] == Some(available) {
+            return Ok(());
+        }
+        let payload = if available { PAYLOAD_ONLINE } else { PAYLOAD_OFFLINE };
         self.client
-            .publish(&self.topics.status, QoS::AtMostOnce, false, payload.as_bytes())?;
+            .publish(topic, QoS::AtLeastOnce, true, payload.as_bytes())?;
+        self.sensor_available[slot] = Some(available);
+        Ok(())
+    }
+
+    // Switch the temperature unit: HA only picks up a new unit_of_measurement
+    // from discovery, so re-send it, then the last status in the new unit.
+    pub fn set_temp_unit(&mut self, unit: TempUnit) -> Result<()> {
+        if unit == self.temp_unit {
+            return Ok(());
+        }
+        self.temp_unit = unit;
+        publish_discovery(
+            &mut self.client,
+            &self.topics,
+            self.mac.as_deref(),
+            unit,
+            self.battery_soc,
+        )?;
+        if let Some(telemetry) = self.last_telemetry.clone() {
+            self.publish_status(&telemetry)?;
+        }
+        Ok(())
+    }
+
+    // Publish a raw sensor reply as space-separated hex, e.g. "FF 86 02 60 ...".
+    // Not retained: only useful while someone is watching.
+    #[cfg(feature = "debug-frames")]
+    pub fn publish_co2_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let hex: Vec<String> = frame.iter().map(|b| format!("{:02X}", b)).collect();
+        self.client
+            .publish(&self.topics.co2_raw, QoS::AtMostOnce, false, hex.join(" ").as_bytes())?;
+        Ok(())
+    }
+
+    // Publish (retained) the relay state when it changes.
+    pub fn publish_relay_state(&mut self, on: bool) -> Result<()> {
+        if self.relay_state == Some(on) {
+            return Ok(());
+        }
+        let payload = if on { "ON" } else { "OFF" };
+        self.client
+            .publish(&self.topics.relay, QoS::AtLeastOnce, true, payload.as_bytes())?;
+        self.relay_state = Some(on);
+        Ok(())
+    }
+
+    // Publish (retained) the crash count; sent after connecting and after a
+    // reset_crashes command.
+    pub fn publish_crash_count(&mut self, count: u32) -> Result<()> {
+        self.client.publish(
+            &self.topics.crash_count,
+            QoS::AtLeastOnce,
+            true,
+            count.to_string().as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    // What the LWT would say, sent ahead of a deliberate shutdown.
+    pub fn publish_offline(&mut self) -> Result<()> {
+        self.client.publish(
+            &self.topics.availability,
+            self.lwt_qos,
+            MQTT_LWT_RETAIN,
+            PAYLOAD_OFFLINE.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn publish_settings(&mut self, report: &SettingsReport) -> Result<()> {
+        let relay = report
+            .relay
+            .map_or("null".to_string(), |mode| format!(r#""{}""#, mode.as_str()));
+        let payload = format!(
+            r#"{{"brightness":{},"abc":{},"co2_alarm":{},"co2_smoothing":{},"co2_interval_ms":{},"env_interval_ms":{},"theme":"{}","layout":"{}","lcd_invert":{},"temp_unit":"{}","temp_decimals":{},"temp_hum_source":"{}","relay":{}}}"#,
+            report.brightness,
+            report.abc,
+            report.co2_alarm_ppm,
+            report.co2_smoothing,
+            report.co2_interval_ms,
+            report.env_interval_ms,
+            report.theme.as_str(),
+            report.layout.as_str(),
+            report.lcd_invert,
+            report.temp_unit.as_str(),
+            report.temp_decimals,
+            report.temp_hum_source.as_str(),
+            relay
+        );
+        self.client
+            .publish(&self.topics.settings, QoS::AtLeastOnce, true, payload.as_bytes())?;
+        Ok(())
+    }
+
+    // Publish "<command>:ok" or "<command>:error:<reason>" after a command ran.
+    pub fn publish_ack(&mut self, command: &str, result: &Result<(), String>) -> Result<()> {
+        let payload = match result {
+            Ok(()) => format!("{command}:ok"),
+            Err(reason) => format!("{command}:error:{reason}"),
+        };
+        self.client
+            .publish(&self.topics.ack, QoS::AtLeastOnce, false, payload.as_bytes())?;
         Ok(())
     }
 
@@ -82,14 +563,29 @@ impl MqttClient {
 
 pub fn init_mqtt(
     wifi: &mut esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>,
+    temp_unit: TempUnit,
+    battery_soc: bool,
 ) -> Result<MqttClient> {
     // Ensure Wi-Fi is connected before starting the MQTT client.
     ensure_connected(wifi)?;
 
     let topics = Topics {
-        availability: format!("{}/availability", MQTT_PREFIX),
-        status: format!("{}/status", MQTT_PREFIX),
-        cmd: format!("{}/cmd", MQTT_PREFIX),
+        availability: format!("{}/availability", MQTT_STATE_PREFIX),
+        co2_availability: format!("{}/co2/availability", MQTT_STATE_PREFIX),
+        temp_hum_availability: format!("{}/temp_hum/availability", MQTT_STATE_PREFIX),
+        status: format!("{}/status", MQTT_STATE_PREFIX),
+        state: format!("{}/state", MQTT_STATE_PREFIX),
+        cmd: format!("{}/cmd", MQTT_STATE_PREFIX),
+        ack: format!("{}/cmd/ack", MQTT_STATE_PREFIX),
+        reset_reason: format!("{}/reset_reason", MQTT_STATE_PREFIX),
+        crash_count: format!("{}/crash_count", MQTT_STATE_PREFIX),
+        compact: format!("{}/status/compact", MQTT_STATE_PREFIX),
+        relay: format!("{}/relay", MQTT_STATE_PREFIX),
+        settings: format!("{}/settings", MQTT_STATE_PREFIX),
+        #[cfg(feature = "debug-frames")]
+        co2_raw: format!("{}/co2/raw", MQTT_STATE_PREFIX),
+        device: format!("{}/device", MQTT_STATE_PREFIX),
+        discovery: MQTT_DISCOVERY_PREFIX.trim_end_matches('/').to_string(),
     };
 
     let port = MQTT_PORT_ENV
@@ -101,13 +597,15 @@ pub fn init_mqtt(
     conf.client_id = Some(MQTT_CLIENT_ID);
     conf.username = MQTT_USER;
     conf.password = MQTT_PASS;
-    conf.keep_alive_interval = Some(Duration::from_secs(30));
-    conf.network_timeout = Duration::from_secs(5);
+    conf.keep_alive_interval = Some(keepalive());
+    conf.network_timeout = network_timeout();
+    conf.reconnect_timeout = Some(RECONNECT_DELAY);
+    let lwt_qos = parse_qos(MQTT_LWT_QOS_ENV.or(Some("1")));
     conf.lwt = Some(LwtConfiguration {
         topic: &topics.availability,
         payload: PAYLOAD_OFFLINE.as_bytes(),
-        qos: QoS::AtLeastOnce,
-        retain: true,
+        qos: lwt_qos,
+        retain: MQTT_LWT_RETAIN,
     });
 
     // Create the client plus a connection event iterator.
@@ -156,150 +654,405 @@ pub fn init_mqtt(
     client.subscribe(&topics.cmd, QoS::AtLeastOnce)?;
     client.publish(
         &topics.availability,
-        QoS::AtLeastOnce,
-        true,
+        lwt_qos,
+        MQTT_LWT_RETAIN,
         PAYLOAD_ONLINE.as_bytes(),
     )?;
     // Publish HomeAssistant discovery configs so entities show up automatically.
-    publish_discovery(&mut client, &topics)?;
+    let mac = sta_mac(wifi);
+    publish_discovery(&mut client, &topics, mac.as_deref(), temp_unit, battery_soc)?;
+    // Consolidated device info for non-HA integrations (retained, once per boot).
+    let device_info = device_info_payload(wifi);
+    client.publish(&topics.device, QoS::AtLeastOnce, true, device_info.as_bytes())?;
+    // Reset reason is fixed for this boot, so publish it once (retained).
+    client.publish(
+        &topics.reset_reason,
+        QoS::AtLeastOnce,
+        true,
+        diagnostics::reset_reason().as_bytes(),
+    )?;
 
     info!("MQTT connected to {}", url);
     Ok(MqttClient {
         client,
         cmd_rx,
+        conn_rx,
         topics,
+        last_telemetry: None,
+        status_qos: parse_qos(MQTT_STATUS_QOS_ENV),
+        status_retain: MQTT_STATUS_RETAIN,
+        lwt_qos,
+        disconnected_at: None,
+        disconnect_logged: false,
+        mac,
+        sensor_available: [None; 2],
+        relay_state: None,
+        temp_unit,
+        battery_soc,
     })
 }
 
-fn parse_command(payload: &[u8]) -> Option<Command> {
-    let text = String::from_utf8_lossy(payload);
-    let text = text.trim().to_ascii_lowercase();
-    if text.is_empty() {
-        return None;
-    }
-    if text == "zero" || text == "zero_calibrate" {
-        return Some(Command::ZeroCalibrate);
-    }
-    if text == "reboot" {
-        return Some(Command::Reboot);
+fn keepalive() -> Duration {
+    let secs = MQTT_KEEPALIVE_S_ENV
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(MQTT_KEEPALIVE_S_DEFAULT);
+    if secs < MQTT_KEEPALIVE_S_MIN {
+        warn!("MQTT_KEEPALIVE_S={} too short, using {}s", secs, MQTT_KEEPALIVE_S_MIN);
     }
-    if let Some(value) = text.strip_prefix("abc=") {
-        return parse_on_off(value).map(Command::SetAbc);
-    }
-    if let Some(value) = text.strip_prefix("abc:") {
-        return parse_on_off(value).map(Command::SetAbc);
-    }
-    if let Some(value) = text.strip_prefix("brightness=") {
-        return parse_percent(value).map(Command::SetBrightness);
+    Duration::from_secs(secs.max(MQTT_KEEPALIVE_S_MIN))
+}
+
+fn network_timeout() -> Duration {
+    let secs = MQTT_NETWORK_TIMEOUT_S_ENV
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(MQTT_NETWORK_TIMEOUT_S_DEFAULT);
+    Duration::from_secs(secs)
+}
+
+fn parse_qos(value: Option<&str>) -> QoS {
+    match value.map(str::trim) {
+        Some("1") => QoS::AtLeastOnce,
+        Some("2") => QoS::ExactlyOnce,
+        _ => QoS::AtMostOnce,
     }
-    if let Some(value) = text.strip_prefix("brightness:") {
-        return parse_percent(value).map(Command::SetBrightness);
+}
+
+// State topic and Jinja expression for a telemetry field in the active publish mode.
+// Discovery gets no `last_reset`: HA only accepts it on `state_class: total`
+// sensors, and these are all `measurement`, so `ts` stays in the payload for
+// other consumers of the per-field topics.
+fn field_source(topics: &Topics, key: &str) -> (String, String) {
+    match PUBLISH_MODE {
+        PublishMode::Json => (topics.status.clone(), format!("value_json.{key}")),
+        PublishMode::Split => (topics.field(key), "value".to_string()),
+        PublishMode::Timestamped => (topics.field(key), "value_json.value".to_string()),
     }
-    None
 }
 
-fn parse_on_off(value: &str) -> Option<bool> {
-    match value.trim() {
-        "1" | "on" | "true" => Some(true),
-        "0" | "off" | "false" => Some(false),
-        _ => None,
+// Discovery state topic + value_template for a numeric/text telemetry sensor.
+fn field_sensor(topics: &Topics, key: &str) -> (String, String) {
+    let (topic, expr) = field_source(topics, key);
+    (topic, format!("{{{{ {expr} }}}}"))
+}
+
+// Compiled into the image, so it is always the running partition's version.
+fn sw_version() -> String {
+    match OTA_BUILD {
+        Some(build) => format!("{SW_VERSION}+{build}"),
+        None => SW_VERSION.to_string(),
     }
 }
 
-fn parse_percent(value: &str) -> Option<u8> {
-    let raw = value.trim().parse::<u8>().ok()?;
-    Some(raw.min(100))
+pub fn sta_mac(
+    wifi: &esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>,
+) -> Option<String> {
+    let m = wifi.wifi().sta_netif().get_mac().ok()?;
+    Some(format!(
+        "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        m[0], m[1], m[2], m[3], m[4], m[5]
+    ))
 }
 
-fn telemetry_payload(t: &Telemetry) -> String {
-    let co2 = t
-        .co2_ppm
-        .map(|v| v.to_string())
-        .unwrap_or_else(|| "null".to_string());
-    let temp = t
-        .temp_c
-        .map(|v| format!("{:.1}", v))
-        .unwrap_or_else(|| "null".to_string());
-    let hum = t
-        .humidity_pct
-        .map(|v| v.to_string())
-        .unwrap_or_else(|| "null".to_string());
-    let bat = t
-        .battery_v
-        .map(|v| format!("{:.2}", v))
+fn device_info_payload(
+    wifi: &esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>,
+) -> String {
+    let mac = sta_mac(wifi)
+        .map(|m| format!(r#""{m}""#))
         .unwrap_or_else(|| "null".to_string());
+    let ip = wifi
+        .wifi()
+        .sta_netif()
+        .get_ip_info()
+        .map(|info| format!(r#""{}""#, info.ip))
+        .unwrap_or_else(|_| "null".to_string());
+    let sw_version = sw_version();
+    let (partition, rolled_back_from) = match ota::firmware_slots() {
+        Ok(slots) => (
+            format!(r#""{}""#, slots.running),
+            slots
+                .rolled_back_from
+                .map(|(label, version)| format!(r#"{{"partition":"{label}","version":"{version}"}}"#))
+                .unwrap_or_else(|| "null".to_string()),
+        ),
+        Err(err) => {
+            warn!("OTA partition info unavailable: {:?}", err);
+            ("null".to_string(), "null".to_string())
+        }
+    };
 
     format!(
-        r#"{{"co2_ppm":{co2},"temp_c":{temp},"humidity_pct":{hum},"battery_v":{bat}}}"#
+        r#"{{"model":"{DEVICE_MODEL}","manufacturer":"{DEVICE_MANUFACTURER}","sw_version":"{sw_version}","partition":{partition},"rolled_back_from":{rolled_back_from},"mac":{mac},"ip":{ip},"client_id":"{MQTT_CLIENT_ID}"}}"#
     )
 }
 
-fn publish_discovery(client: &mut EspMqttClient<'static>, topics: &Topics) -> Result<()> {
+fn publish_discovery(
+    client: &mut EspMqttClient<'static>,
+    topics: &Topics,
+    mac: Option<&str>,
+    temp_unit: TempUnit,
+    battery_soc: bool,
+) -> Result<()> {
     let device_id = MQTT_PREFIX.replace('-', "_");
     let device_name = "C6 Demo";
-    let sw_version = match OTA_BUILD {
-        Some(build) => format!("{SW_VERSION}+{build}"),
-        None => SW_VERSION.to_string(),
-    };
+    let sw_version = sw_version();
+    // MAC-based connection lets HA dedupe the device across id changes.
+    let connections = mac
+        .map(|mac| format!(r#","connections":[["mac","{mac}"]]"#))
+        .unwrap_or_default();
+
+    // Counted in heartbeats: with publish-on-change, unchanged readings are
+    // only re-sent that often.
+    let expire_after = STATUS_PUBLISH_INTERVAL.as_secs()
+        * u64::from(heartbeat_intervals())
+        * EXPIRE_AFTER_PUBLISHES;
 
+    let mut failures = DiscoveryFailures::default();
     let device = format!(
-        r#""device":{{"identifiers":["{device_id}"],"name":"{device_name}","model":"ESP32-C6 Touch LCD 1.47","manufacturer":"Espressif","sw_version":"{sw_version}"}}"#
+        r#""device":{{"identifiers":["{device_id}"],"name":"{device_name}","model":"{DEVICE_MODEL}","manufacturer":"{DEVICE_MANUFACTURER}","sw_version":"{sw_version}"{connections}}}"#
     );
 
-    // CO2 sensor entity: uses value_template to pull co2_ppm from the JSON status payload.
-    publish_sensor_config(
+    // CO2 sensor entity: value_template pulls co2_ppm from the telemetry (see PublishMode).
+    if ENTITY_CO2 {
+        let (state_topic, template) = field_sensor(topics, "co2_ppm");
+        failures.record(publish_sensor_config(
+            client,
+            &device_id,
+            "co2",
+            "C6 CO2",
+            topics,
+            &state_topic,
+            &template,
+            Some("ppm"),
+            Some("carbon_dioxide"),
+            Some("measurement"),
+            Some(expire_after),
+            Some(&topics.co2_availability),
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "sensor", &device_id, "co2"));
+    }
+    // Peak CO2 since the last reset (ppm); no state_class, it is not a sample.
+    if ENTITY_CO2 {
+        let (state_topic, template) = field_sensor(topics, "co2_peak_ppm");
+        failures.record(publish_sensor_config(
+            client,
+            &device_id,
+            "co2_peak",
+            "C6 CO2 Peak",
+            topics,
+            &state_topic,
+            &template,
+            Some("ppm"),
+            Some("carbon_dioxide"),
+            None,
+            None,
+            None,
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "sensor", &device_id, "co2_peak"));
+    }
+    // Temperature sensor entity (°C or °F, see temp_unit) from the telemetry's
+    // `temp` key; `temp_c` stays °C for other consumers.
+    let (state_topic, template) = field_sensor(topics, "temp");
+    failures.record(publish_sensor_config(
         client,
         &device_id,
-        "co2",
-        "C6 CO2",
+        "temperature",
+        "C6 Temperature",
         topics,
-        r#"{{ value_json.co2_ppm }}"#,
-        Some("ppm"),
-        Some("carbon_dioxide"),
+        &state_topic,
+        &template,
+        Some(temp_unit.symbol()),
+        Some("temperature"),
         Some("measurement"),
+        Some(expire_after),
+        Some(&topics.temp_hum_availability),
         &device,
-    )?;
-    // Temperature sensor entity (°C) from JSON status payload.
-    publish_sensor_config(
+    ));
+    // Humidity sensor entity (%) from the telemetry.
+    if ENTITY_HUMIDITY {
+        let (state_topic, template) = field_sensor(topics, "humidity_pct");
+        failures.record(publish_sensor_config(
+            client,
+            &device_id,
+            "humidity",
+            "C6 Humidity",
+            topics,
+            &state_topic,
+            &template,
+            Some("%"),
+            Some("humidity"),
+            Some("measurement"),
+            Some(expire_after),
+            Some(&topics.temp_hum_availability),
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "sensor", &device_id, "humidity"));
+    }
+    // Battery voltage sensor entity (V) from the telemetry.
+    if ENTITY_BATTERY {
+        let (state_topic, template) = field_sensor(topics, "battery_v");
+        failures.record(publish_sensor_config(
+            client,
+            &device_id,
+            "battery",
+            "C6 Battery",
+            topics,
+            &state_topic,
+            &template,
+            Some("V"),
+            Some("voltage"),
+            Some("measurement"),
+            Some(expire_after),
+            None,
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "sensor", &device_id, "battery"));
+    }
+    // Battery state of charge entity (%), only with a fuel gauge.
+    if ENTITY_BATTERY && battery_soc {
+        let (state_topic, template) = field_sensor(topics, "battery_pct");
+        failures.record(publish_sensor_config(
+            client,
+            &device_id,
+            "battery_level",
+            "C6 Battery Level",
+            topics,
+            &state_topic,
+            &template,
+            Some("%"),
+            Some("battery"),
+            Some("measurement"),
+            Some(expire_after),
+            None,
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "sensor", &device_id, "battery_level"));
+    }
+    // Uptime sensor entity (s) from the telemetry.
+    let (state_topic, template) = field_sensor(topics, "uptime_s");
+    failures.record(publish_sensor_config(
         client,
         &device_id,
-        "temperature",
-        "C6 Temperature",
+        "uptime",
+        "C6 Uptime",
         topics,
-        r#"{{ value_json.temp_c }}"#,
-        Some("°C"),
-        Some("temperature"),
-        Some("measurement"),
+        &state_topic,
+        &template,
+        Some("s"),
+        Some("duration"),
+        Some("total_increasing"),
+        Some(expire_after),
+        None,
         &device,
-    )?;
-    // Humidity sensor entity (%) from JSON status payload.
-    publish_sensor_config(
+    ));
+    // Reset reason sensor entity: plain retained string published once per boot.
+    failures.record(publish_sensor_config(
         client,
         &device_id,
-        "humidity",
-        "C6 Humidity",
+        "reset_reason",
+        "C6 Reset Reason",
         topics,
-        r#"{{ value_json.humidity_pct }}"#,
-        Some("%"),
-        Some("humidity"),
-        Some("measurement"),
+        &topics.reset_reason,
+        r#"{{ value }}"#,
+        None,
+        None,
+        None,
+        None,
+        None,
         &device,
-    )?;
-    // Battery voltage sensor entity (V) from JSON status payload.
-    publish_sensor_config(
+    ));
+    // Crash count sensor entity: retained counter, reset by the button below.
+    failures.record(publish_sensor_config(
         client,
         &device_id,
-        "battery",
-        "C6 Battery",
+        "crash_count",
+        "C6 Crash Count",
         topics,
-        r#"{{ value_json.battery_v }}"#,
-        Some("V"),
-        Some("voltage"),
-        Some("measurement"),
+        &topics.crash_count,
+        r#"{{ value }}"#,
+        None,
+        None,
+        Some("total_increasing"),
+        None,
+        None,
         &device,
-    )?;
+    ));
+    // Active temperature/humidity source from the telemetry.
+    let (state_topic, template) = field_sensor(topics, "temp_hum_source");
+    failures.record(publish_sensor_config(
+        client,
+        &device_id,
+        "temp_hum_source",
+        "C6 Temp/Humidity Source",
+        topics,
+        &state_topic,
+        &template,
+        None,
+        None,
+        None,
+        Some(expire_after),
+        None,
+        &device,
+    ));
+    // Binary sensor: sustained high humidity (mold risk) from the telemetry.
+    // Split mode delivers "true"/"false" as text, hence the string compare.
+    let (state_topic, expr) = field_source(topics, "mold_risk");
+    failures.record(publish_binary_sensor_config(
+        client,
+        &device_id,
+        "mold_risk",
+        "C6 Mold Risk",
+        topics,
+        &state_topic,
+        &format!("{{{{ 'ON' if ({expr} | string | lower) == 'true' else 'OFF' }}}}"),
+        Some("problem"),
+        Some(&topics.temp_hum_availability),
+        &device,
+    ));
+    // Binary sensor: occupancy inferred from the CO2 trend, same string
+    // compare as mold_risk.
+    if ENTITY_CO2 {
+        let (state_topic, expr) = field_source(topics, "occupied");
+        failures.record(publish_binary_sensor_config(
+            client,
+            &device_id,
+            "occupancy",
+            "C6 Occupancy",
+            topics,
+            &state_topic,
+            &format!("{{{{ 'ON' if ({expr} | string | lower) == 'true' else 'OFF' }}}}"),
+            Some("occupancy"),
+            Some(&topics.co2_availability),
+            &device,
+        ));
+    } else {
+        failures.record(clear_config(client, topics, "binary_sensor", &device_id, "occupancy"));
+    }
+    // Last command result sensor entity from <prefix>/cmd/ack.
+    failures.record(publish_sensor_config(
+        client,
+        &device_id,
+        "last_command",
+        "C6 Last Command",
+        topics,
+        &topics.ack,
+        r#"{{ value }}"#,
+        None,
+        None,
+        None,
+        None,
+        None,
+        &device,
+    ));
     // Button entity: publishes "zero_calibrate" to <prefix>/cmd when pressed.
-    publish_button_config(
+    failures.record(publish_button_config(
         client,
         &device_id,
         "zero_calibrate",
@@ -307,9 +1060,29 @@ fn publish_discovery(client: &mut EspMqttClient<'static>, topics: &Topics) -> Re
         topics,
         "zero_calibrate",
         &device,
-    )?;
+    ));
+    // Button entity: publishes "reset_peak" to <prefix>/cmd when pressed.
+    failures.record(publish_button_config(
+        client,
+        &device_id,
+        "reset_peak",
+        "C6 Reset CO2 Peak",
+        topics,
+        "reset_peak",
+        &device,
+    ));
+    // Button entity: publishes "reset_crashes" to <prefix>/cmd when pressed.
+    failures.record(publish_button_config(
+        client,
+        &device_id,
+        "reset_crashes",
+        "C6 Reset Crash Count",
+        topics,
+        "reset_crashes",
+        &device,
+    ));
     // Button entity: publishes "reboot" to <prefix>/cmd when pressed.
-    publish_button_config(
+    failures.record(publish_button_config(
         client,
         &device_id,
         "reboot",
@@ -317,9 +1090,20 @@ fn publish_discovery(client: &mut EspMqttClient<'static>, topics: &Topics) -> Re
         topics,
         "reboot",
         &device,
-    )?;
-    // Switch entity (optimistic): publishes "abc:on"/"abc:off" to <prefix>/cmd.
-    publish_switch_config(
+    ));
+    // Button entity: publishes "factory_reset" (must be pressed twice within 10s).
+    #[cfg(feature = "dangerous-commands")]
+    failures.record(publish_button_config(
+        client,
+        &device_id,
+        "factory_reset",
+        "C6 Factory Reset",
+        topics,
+        "factory_reset",
+        &device,
+    ));
+    // Switch entity: publishes "abc:on"/"abc:off" to <prefix>/cmd, state from <prefix>/settings.
+    failures.record(publish_switch_config(
         client,
         &device_id,
         "abc",
@@ -327,22 +1111,149 @@ fn publish_discovery(client: &mut EspMqttClient<'static>, topics: &Topics) -> Re
         topics,
         "abc:on",
         "abc:off",
+        &topics.settings,
+        Some("{{ 'ON' if value_json.abc else 'OFF' }}"),
         &device,
-    )?;
-    // Number entity (optimistic slider 0..100): publishes "brightness:<value>" to <prefix>/cmd.
-    publish_number_config(
+    ));
+    if RELAY_GPIO.is_some() {
+        // Switch entity with state: "relay:on"/"relay:off" force the relay (manual
+        // override), the "auto" button hands it back to the CO2 thresholds.
+        failures.record(publish_switch_config(
+            client,
+            &device_id,
+            "relay",
+            "C6 Fan Relay",
+            topics,
+            "relay:on",
+            "relay:off",
+            &topics.relay,
+            None,
+            &device,
+        ));
+        failures.record(publish_button_config(
+            client,
+            &device_id,
+            "relay_auto",
+            "C6 Fan Relay Auto",
+            topics,
+            "relay:auto",
+            &device,
+        ));
+    }
+    // Number entities (sliders) publish "<command>:<value>" to <prefix>/cmd and
+    // read their state from the same key in <prefix>/settings.
+    failures.record(publish_number_config(
         client,
         &device_id,
         "brightness",
         "C6 Brightness",
         topics,
+        "brightness",
+        "%",
         0,
         100,
         1,
         &device,
-    )?;
+    ));
+    failures.record(publish_number_config(
+        client,
+        &device_id,
+        "co2_alarm",
+        "C6 CO2 Alarm",
+        topics,
+        "co2_alarm",
+        "ppm",
+        CO2_ALARM_MIN as i32,
+        CO2_ALARM_MAX as i32,
+        CO2_ALARM_STEP as i32,
+        &device,
+    ));
+    failures.record(publish_number_config(
+        client,
+        &device_id,
+        "co2_smoothing",
+        "C6 CO2 Smoothing",
+        topics,
+        "co2_smoothing",
+        "readings",
+        CO2_SMOOTHING_MIN as i32,
+        CO2_SMOOTHING_MAX as i32,
+        1,
+        &device,
+    ));
+
+    failures.into_result()
+}
+
+// Device availability (LWT-backed) plus an optional per-sensor topic; HA
+// treats the entity as available only when all listed topics say so.
+fn availability_json(topics: &Topics, sensor_topic: Option<&str>) -> String {
+    let entry = |topic: &str| {
+        format!(
+            r#"{{"topic":"{topic}","payload_available":"{PAYLOAD_ONLINE}","payload_not_available":"{PAYLOAD_OFFLINE}"}}"#
+        )
+    };
+    let mut entries = entry(&topics.availability);
+    if let Some(topic) = sensor_topic {
+        entries.push(',');
+        entries.push_str(&entry(topic));
+    }
+    format!(r#""availability":[{entries}],"availability_mode":"all""#)
+}
+
+// Discovery configs that still failed after their retries. A failure does not
+// stop the pass: every config after it would be missing in HA as well.
+#[derive(Default)]
+struct DiscoveryFailures(Vec<String>);
+
+impl DiscoveryFailures {
+    fn record(&mut self, result: Result<()>) {
+        if let Err(err) = result {
+            self.0.push(err.to_string());
+        }
+    }
 
-    Ok(())
+    fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} discovery config(s) failed: {}",
+            self.0.len(),
+            self.0.join("; ")
+        ))
+    }
+}
+
+// Retained discovery publish with pacing and retries (see DISCOVERY_PUBLISH_GAP).
+fn publish_config(client: &mut EspMqttClient<'static>, topic: &str, payload: &str) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        match client.publish(topic, QoS::AtLeastOnce, true, payload.as_bytes()) {
+            Ok(_) => {
+                thread::sleep(DISCOVERY_PUBLISH_GAP);
+                return Ok(());
+            }
+            Err(err) if attempt < DISCOVERY_PUBLISH_ATTEMPTS => {
+                warn!("MQTT discovery publish to {} failed (attempt {}): {:?}", topic, attempt, err);
+                thread::sleep(DISCOVERY_RETRY_DELAY * attempt);
+                attempt += 1;
+            }
+            Err(err) => return Err(anyhow!("{}: {:?}", topic, err)),
+        }
+    }
+}
+
+// An empty retained config makes HA remove the entity.
+fn clear_config(
+    client: &mut EspMqttClient<'static>,
+    topics: &Topics,
+    component: &str,
+    device_id: &str,
+    key: &str,
+) -> Result<()> {
+    let topic = format!("{}/{component}/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, "")
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -352,19 +1263,19 @@ fn publish_sensor_config(
     key: &str,
     name: &str,
     topics: &Topics,
+    state_topic: &str,
     value_template: &str,
     unit: Option<&str>,
     device_class: Option<&str>,
     state_class: Option<&str>,
+    expire_after: Option<u64>,
+    sensor_availability: Option<&str>,
     device: &str,
 ) -> Result<()> {
     // HomeAssistant MQTT sensor discovery payload.
+    let availability = availability_json(topics, sensor_availability);
     let mut payload = format!(
-        r#"{{"name":"{name}","state_topic":"{state_topic}","value_template":"{value_template}","availability_topic":"{availability_topic}","payload_available":"{online}","payload_not_available":"{offline}","unique_id":"{device_id}-{key}","#,
-        state_topic = topics.status,
-        availability_topic = topics.availability,
-        online = PAYLOAD_ONLINE,
-        offline = PAYLOAD_OFFLINE,
+        r#"{{"name":"{name}","state_topic":"{state_topic}","value_template":"{value_template}",{availability},"unique_id":"{device_id}-{key}","#,
     );
 
     if let Some(unit) = unit {
@@ -376,12 +1287,42 @@ fn publish_sensor_config(
     if let Some(state_class) = state_class {
         payload.push_str(&format!(r#""state_class":"{state_class}","#));
     }
+    if let Some(expire_after) = expire_after {
+        payload.push_str(&format!(r#""expire_after":{expire_after},"#));
+    }
     payload.push_str(device);
     payload.push('}');
 
-    let topic = format!("homeassistant/sensor/{device_id}/{key}/config");
-    client.publish(&topic, QoS::AtLeastOnce, true, payload.as_bytes())?;
-    Ok(())
+    let topic = format!("{}/sensor/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, &payload)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn publish_binary_sensor_config(
+    client: &mut EspMqttClient<'static>,
+    device_id: &str,
+    key: &str,
+    name: &str,
+    topics: &Topics,
+    state_topic: &str,
+    value_template: &str,
+    device_class: Option<&str>,
+    sensor_availability: Option<&str>,
+    device: &str,
+) -> Result<()> {
+    // HomeAssistant MQTT binary_sensor discovery payload (template yields ON/OFF).
+    let availability = availability_json(topics, sensor_availability);
+    let mut payload = format!(
+        r#"{{"name":"{name}","state_topic":"{state_topic}","value_template":"{value_template}",{availability},"unique_id":"{device_id}-{key}","#,
+    );
+    if let Some(device_class) = device_class {
+        payload.push_str(&format!(r#""device_class":"{device_class}","#));
+    }
+    payload.push_str(device);
+    payload.push('}');
+
+    let topic = format!("{}/binary_sensor/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, &payload)
 }
 
 fn publish_button_config(
@@ -395,18 +1336,16 @@ fn publish_button_config(
 ) -> Result<()> {
     // HomeAssistant MQTT button discovery payload (stateless action).
     let payload = format!(
-        r#"{{"name":"{name}","command_topic":"{command_topic}","payload_press":"{payload_press}","availability_topic":"{availability_topic}","payload_available":"{online}","payload_not_available":"{offline}","unique_id":"{device_id}-{key}",{device}}}"#,
+        r#"{{"name":"{name}","command_topic":"{command_topic}","payload_press":"{payload_press}",{availability},"unique_id":"{device_id}-{key}",{device}}}"#,
         command_topic = topics.cmd,
-        availability_topic = topics.availability,
-        online = PAYLOAD_ONLINE,
-        offline = PAYLOAD_OFFLINE,
+        availability = availability_json(topics, None),
     );
 
-    let topic = format!("homeassistant/button/{device_id}/{key}/config");
-    client.publish(&topic, QoS::AtLeastOnce, true, payload.as_bytes())?;
-    Ok(())
+    let topic = format!("{}/button/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, &payload)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn publish_switch_config(
     client: &mut EspMqttClient<'static>,
     device_id: &str,
@@ -415,43 +1354,50 @@ fn publish_switch_config(
     topics: &Topics,
     payload_on: &str,
     payload_off: &str,
+    state_topic: &str,
+    value_template: Option<&str>,
     device: &str,
 ) -> Result<()> {
-    // HomeAssistant MQTT switch discovery payload (optimistic, no state topic).
+    // HomeAssistant MQTT switch discovery payload. The retained state topic
+    // carries "ON"/"OFF", or JSON that `value_template` maps to them.
+    let template = value_template
+        .map_or(String::new(), |template| format!(r#","value_template":"{template}""#));
+    let state = format!(
+        r#""state_topic":"{state_topic}"{template},"state_on":"ON","state_off":"OFF","optimistic":false"#
+    );
     let payload = format!(
-        r#"{{"name":"{name}","command_topic":"{command_topic}","payload_on":"{payload_on}","payload_off":"{payload_off}","optimistic":true,"availability_topic":"{availability_topic}","payload_available":"{online}","payload_not_available":"{offline}","unique_id":"{device_id}-{key}",{device}}}"#,
+        r#"{{"name":"{name}","command_topic":"{command_topic}","payload_on":"{payload_on}","payload_off":"{payload_off}",{state},{availability},"unique_id":"{device_id}-{key}",{device}}}"#,
         command_topic = topics.cmd,
-        availability_topic = topics.availability,
-        online = PAYLOAD_ONLINE,
-        offline = PAYLOAD_OFFLINE,
+        availability = availability_json(topics, None),
     );
 
-    let topic = format!("homeassistant/switch/{device_id}/{key}/config");
-    client.publish(&topic, QoS::AtLeastOnce, true, payload.as_bytes())?;
-    Ok(())
+    let topic = format!("{}/switch/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, &payload)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn publish_number_config(
     client: &mut EspMqttClient<'static>,
     device_id: &str,
     key: &str,
     name: &str,
     topics: &Topics,
+    command: &str,
+    unit: &str,
     min: i32,
     max: i32,
     step: i32,
     device: &str,
 ) -> Result<()> {
-    // HomeAssistant MQTT number discovery payload (optimistic slider).
+    // HomeAssistant MQTT number discovery payload (slider). The current value
+    // is the `command` key of the retained settings JSON.
     let payload = format!(
-        r#"{{"name":"{name}","command_topic":"{command_topic}","command_template":"brightness:{{{{ value }}}}","min":{min},"max":{max},"step":{step},"mode":"slider","unit_of_measurement":"%","optimistic":true,"availability_topic":"{availability_topic}","payload_available":"{online}","payload_not_available":"{offline}","unique_id":"{device_id}-{key}",{device}}}"#,
+        r#"{{"name":"{name}","command_topic":"{command_topic}","command_template":"{command}:{{{{ value }}}}","state_topic":"{state_topic}","value_template":"{{{{ value_json.{command} }}}}","min":{min},"max":{max},"step":{step},"mode":"slider","unit_of_measurement":"{unit}","optimistic":false,{availability},"unique_id":"{device_id}-{key}",{device}}}"#,
         command_topic = topics.cmd,
-        availability_topic = topics.availability,
-        online = PAYLOAD_ONLINE,
-        offline = PAYLOAD_OFFLINE,
+        state_topic = topics.settings,
+        availability = availability_json(topics, None),
     );
 
-    let topic = format!("homeassistant/number/{device_id}/{key}/config");
-    client.publish(&topic, QoS::AtLeastOnce, true, payload.as_bytes())?;
-    Ok(())
+    let topic = format!("{}/number/{device_id}/{key}/config", topics.discovery);
+    publish_config(client, &topic, &payload)
 }
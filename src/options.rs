@@ -0,0 +1,199 @@
+// Values of the user-selectable options (theme, layout, temperature unit,
+// relay mode, temp/humidity source) with their command/NVS text forms, and the
+// bounds of the numeric settings. Free of esp-idf and graphics so the MQTT
+// command parser and telemetry payload can be tested on the host
+// (tools/driver_tests); display.rs, relay.rs and settings.rs add the rest.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemePreset {
+    Dark,
+    HighContrast,
+    Amber,
+}
+
+// Build-time default theme name (dark/high_contrast/amber).
+const THEME_ENV: Option<&str> = option_env!("THEME");
+
+impl ThemePreset {
+    pub fn from_env() -> Self {
+        THEME_ENV.and_then(Self::parse).unwrap_or(Self::Dark)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "dark" => Some(Self::Dark),
+            "high_contrast" | "contrast" => Some(Self::HighContrast),
+            "amber" => Some(Self::Amber),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::HighContrast => "high_contrast",
+            Self::Amber => "amber",
+        }
+    }
+}
+
+// Build-time default temperature unit ("c"/"f"); overridable at runtime.
+const TEMP_UNIT_ENV: Option<&str> = option_env!("TEMP_UNIT");
+
+// Unit for the temperature card and the MQTT temperature fields. Readings stay
+// in °C internally and are converted only for presentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+impl TempUnit {
+    pub fn from_env() -> Self {
+        TEMP_UNIT_ENV.and_then(Self::parse).unwrap_or(Self::Celsius)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "c" | "celsius" => Some(Self::Celsius),
+            "f" | "fahrenheit" => Some(Self::Fahrenheit),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Celsius => "c",
+            Self::Fahrenheit => "f",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Celsius => "°C",
+            Self::Fahrenheit => "°F",
+        }
+    }
+
+    pub fn convert(self, celsius: f32) -> f32 {
+        match self {
+            Self::Celsius => celsius,
+            Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    // "22.4°C" with one decimal, "22°C" with none.
+    pub fn format(self, celsius: f32, decimals: u8) -> String {
+        format!(
+            "{:.*}{}",
+            usize::from(decimals),
+            self.convert(celsius),
+            self.symbol()
+        )
+    }
+}
+
+// Build-time default main screen layout ("cards"/"big"); overridable at runtime.
+const LAYOUT_ENV: Option<&str> = option_env!("LAYOUT");
+
+// Main screen layout: the three cards, or one reading at a time in a large
+// font for reading across a room (see render_big_number).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Cards,
+    Big,
+}
+
+impl Layout {
+    pub fn from_env() -> Self {
+        LAYOUT_ENV.and_then(Self::parse).unwrap_or(Self::Cards)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "cards" => Some(Self::Cards),
+            "big" => Some(Self::Big),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cards => "cards",
+            Self::Big => "big",
+        }
+    }
+}
+
+// Set by the `relay:on|off|auto` command; not persisted, boots in Auto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayMode {
+    Auto,
+    On,
+    Off,
+}
+
+impl RelayMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auto" => Some(Self::Auto),
+            "1" | "on" | "true" => Some(Self::On),
+            "0" | "off" | "false" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::On => "on",
+            Self::Off => "off",
+        }
+    }
+}
+
+pub const CO2_ALARM_MIN: u16 = 600;
+pub const CO2_ALARM_MAX: u16 = 3000;
+pub const CO2_ALARM_STEP: u16 = 50;
+pub const CO2_ALARM_DEFAULT: u16 = 1500;
+
+// Readings in the displayed CO2 median (see filter::SmoothedCo2).
+pub const CO2_SMOOTHING_MIN: u8 = 1;
+pub const CO2_SMOOTHING_MAX: u8 = 15;
+pub const CO2_SMOOTHING_DEFAULT: u8 = 3;
+
+// Which sensor feeds temperature/humidity to the display and telemetry.
+// `Auto` picks the most accurate one present (SHT31, then SCD4x).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempHumSource {
+    Auto,
+    Sht31,
+    Scd4x,
+}
+
+impl TempHumSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Sht31 => "sht31",
+            Self::Scd4x => "scd4x",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auto" => Some(Self::Auto),
+            "sht31" => Some(Self::Sht31),
+            "scd4x" => Some(Self::Scd4x),
+            _ => None,
+        }
+    }
+}
+
+pub fn clamp_co2_alarm(ppm: u16) -> u16 {
+    ppm.clamp(CO2_ALARM_MIN, CO2_ALARM_MAX)
+}
+
+pub fn clamp_co2_smoothing(window: u8) -> u8 {
+    window.clamp(CO2_SMOOTHING_MIN, CO2_SMOOTHING_MAX)
+}
@@ -1,14 +1,20 @@
 use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
 use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use embedded_svc::http::{client::Client as HttpClient, Method};
 use embedded_svc::utils::io;
 use esp_idf_svc::http::client::EspHttpConnection;
-use esp_idf_svc::ota::EspOta;
+use esp_idf_svc::ota::{EspOta, Slot, SlotState};
 use esp_idf_svc::sys::esp_restart;
-use log::info;
+use log::{info, warn, Level};
 
+use crate::event_log;
+use crate::json_log::json_event;
+use crate::mqtt::{sta_mac, MQTT_CLIENT_ID};
+use crate::watchdog::LongOperation;
 use crate::wifi::ensure_connected;
 
 const OTA_BASE_URL: &str = match option_env!("OTA_BASE_URL") {
@@ -16,8 +22,17 @@ const OTA_BASE_URL: &str = match option_env!("OTA_BASE_URL") {
     None => "http://192.168.1.18:8000/firmware",
 };
 const OTA_LATEST_FILE: &str = "latest.txt";
+// Optional per-device targets, tried before latest.txt; see docs/OTA.md.
+const OTA_MANIFEST_FILE: &str = match option_env!("OTA_MANIFEST_FILE") {
+    Some(v) => v,
+    None => "manifest.json",
+};
+const OTA_MANIFEST_MAX_LEN: usize = 4096;
+const OTA_MANIFEST_DEFAULT_KEY: &str = "default";
 const OTA_FILE_PREFIX: &str = "c6-co";
 const OTA_FILE_EXT: &str = ".bin";
+const OTA_LATEST_MAX_LEN: usize = 64;
+const OTA_LOG_BODY_MAX: usize = 80;
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const OTA_BUILD: Option<&str> = option_env!("OTA_BUILD");
 
@@ -29,14 +44,101 @@ pub fn mark_app_valid() -> Result<()> {
     Ok(())
 }
 
+// Which app partition is running and whether the bootloader rolled back from
+// an update. The reported sw_version is compiled into the image, so it always
+// belongs to the running partition; this adds where it runs from.
+pub struct FirmwareSlots {
+    pub running: String,
+    // Label and app version of the rejected image, if there was a rollback.
+    pub rolled_back_from: Option<(String, String)>,
+}
+
+pub fn firmware_slots() -> Result<FirmwareSlots> {
+    let ota = EspOta::new()?;
+    let running = ota.get_running_slot()?;
+    let rolled_back_from = ota
+        .get_last_invalid_slot()?
+        .map(|slot| (slot.label.to_string(), slot_version(&slot)));
+    Ok(FirmwareSlots {
+        running: running.label.to_string(),
+        rolled_back_from,
+    })
+}
+
+// Log the running and the other (previous) app partition with the version in
+// each image header. Call before mark_app_valid so a fresh OTA image still
+// shows as unverified.
+pub fn log_firmware_slots() -> Result<()> {
+    let ota = EspOta::new()?;
+    let running = ota.get_running_slot()?;
+    info!(
+        "Firmware running from {} ({}): {} build {}",
+        running.label,
+        slot_state(running.state),
+        slot_version(&running),
+        OTA_BUILD.unwrap_or("none")
+    );
+    match ota.get_update_slot() {
+        Ok(previous) => info!(
+            "Firmware in {} ({}): {}",
+            previous.label,
+            slot_state(previous.state),
+            slot_version(&previous)
+        ),
+        Err(err) => info!("No other OTA partition: {:?}", err),
+    }
+    if let Some(invalid) = ota.get_last_invalid_slot()? {
+        warn!(
+            "OTA rollback: image {} in {} was rejected, running {} from {}",
+            slot_version(&invalid),
+            invalid.label,
+            slot_version(&running),
+            running.label
+        );
+        event_log::push(Level::Warn, &format!("OTA rolled back from {}", invalid.label));
+        json_event!(
+            Level::Warn,
+            "ota_rollback",
+            "rejected" => invalid.label.as_str(),
+            "rejected_version" => slot_version(&invalid),
+            "running" => running.label.as_str()
+        );
+    }
+    Ok(())
+}
+
+fn slot_version(slot: &Slot) -> String {
+    slot.firmware
+        .as_ref()
+        .map_or_else(|| "unknown".to_string(), |info| info.version.to_string())
+}
+
+fn slot_state(state: SlotState) -> &'static str {
+    match state {
+        SlotState::Factory => "factory",
+        SlotState::Valid => "valid",
+        SlotState::Invalid => "invalid",
+        SlotState::Unverified => "unverified",
+        SlotState::Unknown => "unknown",
+    }
+}
+
 pub fn check_and_update(wifi: &mut esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::wifi::EspWifi<'static>>) -> Result<()> {
     info!("OTA check: {}", OTA_BASE_URL);
     ensure_connected(wifi)?;
 
     let mut client = HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
 
-    let latest_name = fetch_latest_filename(&mut client)?;
-    info!("OTA latest file: {}", latest_name.trim());
+    let mac = sta_mac(wifi);
+    let latest_name = match fetch_manifest_target(&mut client, mac.as_deref())? {
+        ManifestTarget::File(name) => name,
+        ManifestTarget::NoEntry => {
+            info!("OTA manifest has no entry for this device, skipping");
+            return Ok(());
+        }
+        ManifestTarget::NoManifest => fetch_latest_filename(&mut client)?,
+    };
+    info!("OTA latest file: {}", latest_name);
     let latest_build = parse_build_from_filename(&latest_name)
         .ok_or_else(|| anyhow!("invalid OTA filename: {latest_name}"))?;
     let current_build = parse_current_build()
@@ -47,6 +149,7 @@ pub fn check_and_update(wifi: &mut esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::
             "OTA up-to-date: current build {} (latest {})",
             current_build, latest_build
         );
+        json_event!(Level::Info, "ota_check", "current_build" => current_build, "latest_build" => latest_build);
         return Ok(());
     }
 
@@ -62,6 +165,118 @@ pub fn check_and_update(wifi: &mut esp_idf_svc::wifi::BlockingWifi<esp_idf_svc::
     Ok(())
 }
 
+enum ManifestTarget {
+    File(String),
+    // A manifest exists but neither this device nor "default" is listed.
+    NoEntry,
+    // No manifest on the server (404): use latest.txt.
+    NoManifest,
+}
+
+// The manifest maps devices to firmware files, most specific key first:
+// station MAC ("aa:bb:cc:dd:ee:ff", any case), then MQTT_CLIENT_ID, then
+// "default".
+fn fetch_manifest_target(
+    client: &mut HttpClient<EspHttpConnection>,
+    mac: Option<&str>,
+) -> Result<ManifestTarget> {
+    let url = format!("{}/{}", OTA_BASE_URL.trim_end_matches('/'), OTA_MANIFEST_FILE);
+    let request = client.request(Method::Get, &url, &[])?;
+    let mut response = request.submit()?;
+
+    match response.status() {
+        200 => {}
+        404 => return Ok(ManifestTarget::NoManifest),
+        status => return Err(anyhow!("OTA manifest request failed: {}", status)),
+    }
+
+    let mut buf = vec![0u8; OTA_MANIFEST_MAX_LEN];
+    let size = io::try_read_full(&mut response, &mut buf).map_err(|e| e.0)?;
+    if size == buf.len() {
+        return Err(anyhow!("OTA manifest too large"));
+    }
+    let text = std::str::from_utf8(&buf[..size]).map_err(|_| anyhow!("OTA manifest not utf-8"))?;
+    let entries =
+        parse_manifest(text).map_err(|reason| anyhow!("OTA manifest rejected: {reason}"))?;
+
+    let lookup = |wanted: &str| {
+        entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(wanted))
+            .map(|(key, file)| (key.as_str(), file.as_str()))
+    };
+    let entry = mac
+        .and_then(|mac| lookup(mac))
+        .or_else(|| lookup(MQTT_CLIENT_ID))
+        .or_else(|| lookup(OTA_MANIFEST_DEFAULT_KEY));
+    let Some((key, file)) = entry else {
+        return Ok(ManifestTarget::NoEntry);
+    };
+    info!("OTA manifest entry {:?}: {}", key, file);
+    let name = validate_latest_filename(file.as_bytes())
+        .map_err(|reason| anyhow!("OTA manifest entry {key:?} rejected: {reason}"))?;
+    Ok(ManifestTarget::File(name))
+}
+
+// Flat JSON object with string values only, e.g.
+// {"default": "c6-co0008.bin", "aa:bb:cc:dd:ee:ff": "c6-co0009.bin"}.
+fn parse_manifest(text: &str) -> Result<Vec<(String, String)>, &'static str> {
+    let mut chars = text.trim().chars().peekable();
+    let mut entries = Vec::new();
+    if chars.next() != Some('{') {
+        return Err("not an object");
+    }
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_json_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            if chars.next() != Some(':') {
+                return Err("expected ':'");
+            }
+            skip_whitespace(&mut chars);
+            let value = parse_json_string(&mut chars)?;
+            entries.push((key, value));
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'"),
+            }
+        }
+    }
+    if chars.next().is_some() {
+        return Err("trailing data");
+    }
+    Ok(entries)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+// Keys and filenames never need more than the simple escapes.
+fn parse_json_string(chars: &mut Peekable<Chars<'_>>) -> Result<String, &'static str> {
+    if chars.next() != Some('"') {
+        return Err("expected string");
+    }
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some(c @ ('"' | '\\' | '/')) => out.push(c),
+                _ => return Err("unsupported escape"),
+            },
+            Some(c) => out.push(c),
+            None => return Err("unterminated string"),
+        }
+    }
+}
+
 fn fetch_latest_filename(client: &mut HttpClient<EspHttpConnection>) -> Result<String> {
     let url = format!("{}/{}", OTA_BASE_URL.trim_end_matches('/'), OTA_LATEST_FILE);
     let request = client.request(Method::Get, &url, &[])?;
@@ -73,21 +288,66 @@ fn fetch_latest_filename(client: &mut HttpClient<EspHttpConnection>) -> Result<S
 
     let mut buf = [0u8; 256];
     let size = io::try_read_full(&mut response, &mut buf).map_err(|e| e.0)?;
-    let text = std::str::from_utf8(&buf[..size])?;
-    Ok(text.trim().to_string())
+    let body = &buf[..size];
+    if size == buf.len() {
+        log_rejected_body("oversized", body);
+        return Err(anyhow!("OTA latest.txt too large"));
+    }
+    validate_latest_filename(body).map_err(|reason| {
+        log_rejected_body(reason, body);
+        anyhow!("OTA latest.txt rejected: {reason}")
+    })
+}
+
+// Accept exactly one line of the form `c6-co<digits>.bin`; anything else
+// (e.g. an HTML error/redirect page) is rejected before building a URL.
+fn validate_latest_filename(body: &[u8]) -> Result<String, &'static str> {
+    let text = std::str::from_utf8(body).map_err(|_| "not utf-8")?;
+    let name = text.trim();
+    if name.is_empty() {
+        return Err("empty");
+    }
+    if name.lines().count() > 1 {
+        return Err("multi-line");
+    }
+    if name.len() > OTA_LATEST_MAX_LEN {
+        return Err("too long");
+    }
+    if name.contains('/')
+        || !name.ends_with(OTA_FILE_EXT)
+        || parse_build_from_filename(name).is_none()
+    {
+        return Err("unexpected filename");
+    }
+    Ok(name.to_string())
+}
+
+fn log_rejected_body(reason: &str, body: &[u8]) {
+    let end = body.len().min(OTA_LOG_BODY_MAX);
+    warn!(
+        "OTA latest.txt rejected ({}): {:?}",
+        reason,
+        String::from_utf8_lossy(&body[..end])
+    );
 }
 
 fn perform_update(client: &mut HttpClient<EspHttpConnection>, url: &str) -> Result<()> {
     info!("OTA download start: {}", url);
+    event_log::push(Level::Info, "OTA download start");
+    json_event!(Level::Info, "ota_download", "url" => url);
+    let op = LongOperation::start("OTA download");
     let request = client.request(Method::Get, url, &[])?;
     let mut response = request.submit()?;
+    op.feed();
 
     if response.status() != 200 {
         return Err(anyhow!("OTA firmware request failed: {}", response.status()));
     }
 
     let mut ota = EspOta::new()?;
+    // Erases the target slot, which alone can take a few seconds.
     let mut update = ota.initiate_update()?;
+    op.feed();
     let mut buf = [0u8; 1024];
 
     loop {
@@ -96,10 +356,12 @@ fn perform_update(client: &mut HttpClient<EspHttpConnection>, url: &str) -> Resu
             break;
         }
         update.write(&buf[..n])?;
+        op.feed();
     }
 
     update.complete()?;
     info!("OTA update complete, rebooting...");
+    json_event!(Level::Info, "ota_complete", "url" => url);
     unsafe { esp_restart() };
 }
 
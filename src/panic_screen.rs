@@ -0,0 +1,197 @@
+use core::convert::Infallible;
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::{IntoStorage, Rgb565};
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use esp_idf_sys as sys;
+
+use crate::pins;
+use crate::st7789::{LCD_VIEW_H, LCD_VIEW_W, LCD_X_GAP, LCD_Y_GAP};
+
+// How long the panic screen stays up before rebooting.
+const PANIC_REBOOT_DELAY: Duration = Duration::from_secs(5);
+const LINE_H: usize = 12;
+const CHAR_W: usize = 6;
+const COLOR_BG: Rgb565 = Rgb565::new(12, 0, 0);
+const COLOR_TEXT: Rgb565 = Rgb565::WHITE;
+
+// Raw handle of the LCD's SPI device, registered once the panel is initialized.
+// The panic path talks to the panel through ESP-IDF directly instead of going
+// through `St7789`, which lives on the main task and may be mid-transaction.
+static LCD_SPI: AtomicPtr<c_void> = AtomicPtr::new(core::ptr::null_mut());
+
+pub fn register_lcd(handle: sys::spi_device_handle_t) {
+    LCD_SPI.store(handle as *mut c_void, Ordering::Release);
+}
+
+// Chain onto the default hook (serial backtrace), then show the message on the
+// LCD and reboot so field units recover on their own.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        show(&info.to_string());
+        thread::sleep(PANIC_REBOOT_DELAY);
+        unsafe { sys::esp_restart() };
+    }));
+}
+
+fn show(message: &str) {
+    let dev = LCD_SPI.load(Ordering::Acquire) as sys::spi_device_handle_t;
+    if dev.is_null() {
+        return;
+    }
+    let lcd = RawLcd { dev };
+    lcd.backlight_on();
+
+    let bg = COLOR_BG.into_storage().to_be_bytes();
+    let row: Vec<u8> = bg.iter().copied().cycle().take(LCD_VIEW_W * 2).collect();
+    lcd.set_window(0, 0, LCD_VIEW_W as u16 - 1, LCD_VIEW_H as u16 - 1);
+    lcd.write_cmd(0x2C);
+    for _ in 0..LCD_VIEW_H {
+        lcd.write_data(&row);
+    }
+
+    let cols = LCD_VIEW_W / CHAR_W - 1;
+    let max_lines = LCD_VIEW_H / LINE_H - 1;
+    let lines = std::iter::once("PANIC - rebooting")
+        .chain(message.lines().flat_map(|line| wrap(line, cols)))
+        .take(max_lines);
+    let mut line_buf = LineBuf::new();
+    for (i, line) in lines.enumerate() {
+        line_buf.clear_to(COLOR_BG);
+        let style = MonoTextStyle::new(&FONT_6X10, COLOR_TEXT);
+        let _ = Text::with_baseline(line, Point::new(CHAR_W as i32, 1), style, Baseline::Top)
+            .draw(&mut line_buf);
+        let y0 = (LINE_H / 2 + i * LINE_H) as u16;
+        lcd.set_window(0, y0, LCD_VIEW_W as u16 - 1, y0 + LINE_H as u16 - 1);
+        lcd.write_cmd(0x2C);
+        lcd.write_data(&line_buf.bytes);
+    }
+}
+
+fn wrap(line: &str, cols: usize) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let split = rest
+            .char_indices()
+            .nth(cols)
+            .map_or(rest.len(), |(idx, _)| idx);
+        let (head, tail) = rest.split_at(split);
+        rest = tail;
+        Some(head)
+    })
+}
+
+struct RawLcd {
+    dev: sys::spi_device_handle_t,
+}
+
+impl RawLcd {
+    fn backlight_on(&self) {
+        // Backlight is LEDC channel 0 (8-bit duty, see st7789::init_lcd); night
+        // mode may have left it at 0.
+        unsafe {
+            sys::ledc_set_duty(
+                sys::ledc_mode_t_LEDC_LOW_SPEED_MODE,
+                sys::ledc_channel_t_LEDC_CHANNEL_0,
+                255,
+            );
+            sys::ledc_update_duty(
+                sys::ledc_mode_t_LEDC_LOW_SPEED_MODE,
+                sys::ledc_channel_t_LEDC_CHANNEL_0,
+            );
+        }
+    }
+
+    fn write_cmd(&self, cmd: u8) {
+        unsafe { sys::gpio_set_level(pins::LCD_DC_GPIO, 0) };
+        self.transmit(&[cmd]);
+    }
+
+    fn write_data(&self, data: &[u8]) {
+        unsafe { sys::gpio_set_level(pins::LCD_DC_GPIO, 1) };
+        for chunk in data.chunks(4096) {
+            self.transmit(chunk);
+        }
+    }
+
+    fn set_window(&self, x0: u16, y0: u16, x1: u16, y1: u16) {
+        // Same offsets as `St7789::set_window`.
+        let (x0, x1) = (x0 + LCD_X_GAP, x1 + LCD_X_GAP);
+        let (y0, y1) = (y0 + LCD_Y_GAP, y1 + LCD_Y_GAP);
+        self.write_cmd(0x2A);
+        self.write_data(&[(x0 >> 8) as u8, x0 as u8, (x1 >> 8) as u8, x1 as u8]);
+        self.write_cmd(0x2B);
+        self.write_data(&[(y0 >> 8) as u8, y0 as u8, (y1 >> 8) as u8, y1 as u8]);
+    }
+
+    fn transmit(&self, bytes: &[u8]) {
+        // Polling transmit: no interrupts or queued transactions needed, which
+        // keeps this usable from inside a panic.
+        let mut trans = sys::spi_transaction_t {
+            length: bytes.len() * 8,
+            ..Default::default()
+        };
+        trans.__bindgen_anon_1.tx_buffer = bytes.as_ptr() as *const c_void;
+        unsafe { sys::spi_device_polling_transmit(self.dev, &mut trans) };
+    }
+}
+
+// One text row of the panic screen, rendered off-screen and sent in one burst.
+struct LineBuf {
+    bytes: Vec<u8>,
+}
+
+impl LineBuf {
+    fn new() -> Self {
+        Self {
+            bytes: vec![0u8; LCD_VIEW_W * LINE_H * 2],
+        }
+    }
+
+    fn clear_to(&mut self, color: Rgb565) {
+        let [hi, lo] = color.into_storage().to_be_bytes();
+        for px in self.bytes.chunks_exact_mut(2) {
+            px[0] = hi;
+            px[1] = lo;
+        }
+    }
+}
+
+impl OriginDimensions for LineBuf {
+    fn size(&self) -> Size {
+        Size::new(LCD_VIEW_W as u32, LINE_H as u32)
+    }
+}
+
+impl DrawTarget for LineBuf {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x as usize, point.y as usize);
+            if point.x < 0 || point.y < 0 || x >= LCD_VIEW_W || y >= LINE_H {
+                continue;
+            }
+            let [hi, lo] = color.into_storage().to_be_bytes();
+            let idx = 2 * (y * LCD_VIEW_W + x);
+            self.bytes[idx] = hi;
+            self.bytes[idx + 1] = lo;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+// Single authoritative GPIO map for the ESP32-C6 Touch LCD 1.47" board.
+// Porting to a differently-wired board should only require editing this file.
+
+// LCD (SPI2).
+pub const LCD_CLK_GPIO: i32 = 1;   // SPI CLK (Clock)
+pub const LCD_MOSI_GPIO: i32 = 2;  // SPI MOSI (Master Out, Slave In)
+pub const LCD_CS_GPIO: i32 = 14;   // SPI CS (Chip Select)
+pub const LCD_DC_GPIO: i32 = 15;   // Data/Command control
+pub const LCD_RST_GPIO: i32 = 22;  // Reset
+pub const LCD_BL_GPIO: i32 = 23;   // Backlight PWM
+
+// Touch controller + SHT31 (shared I2C0 bus).
+pub const I2C_SDA_GPIO: i32 = 18;
+pub const I2C_SCL_GPIO: i32 = 19;
+pub const TP_RST_GPIO: i32 = 20;
+pub const TP_INT_GPIO: i32 = 21;
+
+// Optional momentary "page" button (active-low, internal pull-up), e.g. Some(9).
+pub const BUTTON_GPIO: Option<i32> = None;
+
+// Optional air-quality status LED: a WS2812 data pin (driven by RMT) or a
+// common-cathode RGB LED on three LEDC PWM pins. None = no LED fitted.
+#[allow(dead_code)]
+pub enum StatusLedPins {
+    Ws2812 { data: i32 },
+    Rgb { r: i32, g: i32, b: i32 },
+}
+pub const STATUS_LED: Option<StatusLedPins> = None;
+
+// Optional relay output (fan/ventilation), switched by CO2 in `relay.rs`, e.g. Some(10).
+// Most opto-isolated relay boards are active-low.
+pub const RELAY_GPIO: Option<i32> = None;
+pub const RELAY_ACTIVE_HIGH: bool = true;
+
+// Optional SSD1680 e-paper mirror (`epaper` feature), sharing the LCD's SPI
+// clock/data lines, e.g. Some(EpaperPins { cs: 4, dc: 5, rst: 6, busy: 7 }).
+pub struct EpaperPins {
+    pub cs: i32,
+    pub dc: i32,
+    pub rst: i32,
+    pub busy: i32,
+}
+pub const EPAPER: Option<EpaperPins> = None;
+
+// MH-Z19B, on UART0 unless built with MHZ19B_UART=1.
+pub const MHZ_TX_GPIO: i32 = 16;
+pub const MHZ_RX_GPIO: i32 = 17;
+
+// Peripheral instances, picked in `Board::init`. The ESP32-C6 has one I2C
+// controller (I2C0: touch, SHT31, SCD4x) and one general-purpose SPI host
+// (SPI2: LCD, e-paper), so only the sensor UART can move between revisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartPort {
+    Uart0,
+    Uart1,
+}
+
+impl UartPort {
+    pub fn number(self) -> u8 {
+        match self {
+            Self::Uart0 => 0,
+            Self::Uart1 => 1,
+        }
+    }
+}
+
+pub const MHZ_UART: UartPort = match option_env!("MHZ19B_UART") {
+    Some(v) => match v.as_bytes() {
+        b"1" | b"uart1" => UartPort::Uart1,
+        _ => UartPort::Uart0,
+    },
+    None => UartPort::Uart0,
+};
+
+// Optional ambient light LDR (AMBIENT_LIGHT=1) is on GPIO3 (ADC1), see `ambient.rs`.
+// Battery divider is on GPIO0 (ADC1). The ADC channel type is tied to the pin,
+// so it is selected by type in `board.rs`/`battery.rs` rather than by number.
+
+// Every GPIO claimed by the firmware, named after the constant above (the ADC
+// pins are fixed by type, see the note above). Unfitted optional parts are None.
+const GPIO_ASSIGNMENTS: [(&str, Option<i32>); 23] = [
+    ("LCD_CLK_GPIO", Some(LCD_CLK_GPIO)),
+    ("LCD_MOSI_GPIO", Some(LCD_MOSI_GPIO)),
+    ("LCD_CS_GPIO", Some(LCD_CS_GPIO)),
+    ("LCD_DC_GPIO", Some(LCD_DC_GPIO)),
+    ("LCD_RST_GPIO", Some(LCD_RST_GPIO)),
+    ("LCD_BL_GPIO", Some(LCD_BL_GPIO)),
+    ("I2C_SDA_GPIO", Some(I2C_SDA_GPIO)),
+    ("I2C_SCL_GPIO", Some(I2C_SCL_GPIO)),
+    ("TP_RST_GPIO", Some(TP_RST_GPIO)),
+    ("TP_INT_GPIO", Some(TP_INT_GPIO)),
+    ("BUTTON_GPIO", BUTTON_GPIO),
+    ("STATUS_LED", status_led_pin(0)),
+    ("STATUS_LED", status_led_pin(1)),
+    ("STATUS_LED", status_led_pin(2)),
+    ("RELAY_GPIO", RELAY_GPIO),
+    ("EPAPER", epaper_pin(0)),
+    ("EPAPER", epaper_pin(1)),
+    ("EPAPER", epaper_pin(2)),
+    ("EPAPER", epaper_pin(3)),
+    ("MHZ_TX_GPIO", Some(MHZ_TX_GPIO)),
+    ("MHZ_RX_GPIO", Some(MHZ_RX_GPIO)),
+    ("battery ADC (GPIO0)", Some(0)),
+    ("ambient LDR ADC (GPIO3)", ambient_pin()),
+];
+
+// Data pin (WS2812) or the r/g/b pins (RGB) of the optional status LED.
+const fn status_led_pin(index: usize) -> Option<i32> {
+    match (&STATUS_LED, index) {
+        (Some(StatusLedPins::Ws2812 { data }), 0) => Some(*data),
+        (Some(StatusLedPins::Rgb { r, .. }), 0) => Some(*r),
+        (Some(StatusLedPins::Rgb { g, .. }), 1) => Some(*g),
+        (Some(StatusLedPins::Rgb { b, .. }), 2) => Some(*b),
+        _ => None,
+    }
+}
+
+// cs/dc/rst/busy of the optional e-paper panel.
+const fn epaper_pin(index: usize) -> Option<i32> {
+    match (&EPAPER, index) {
+        (Some(pins), 0) => Some(pins.cs),
+        (Some(pins), 1) => Some(pins.dc),
+        (Some(pins), 2) => Some(pins.rst),
+        (Some(pins), 3) => Some(pins.busy),
+        _ => None,
+    }
+}
+
+const fn ambient_pin() -> Option<i32> {
+    if crate::ambient::AMBIENT_LIGHT_ENABLED {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+// Name of the first assignment whose GPIO is already used by an earlier one.
+const fn duplicate_gpio(assignments: &[(&'static str, Option<i32>)]) -> Option<&'static str> {
+    let mut i = 0;
+    while i < assignments.len() {
+        if let Some(pin) = assignments[i].1 {
+            let mut j = 0;
+            while j < i {
+                if let Some(other) = assignments[j].1 {
+                    if other == pin {
+                        return Some(assignments[i].0);
+                    }
+                }
+                j += 1;
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+// Build fails if two entries above share a GPIO; the panic message names the
+// later one.
+const _: () = if let Some(name) = duplicate_gpio(&GPIO_ASSIGNMENTS) {
+    panic!("{}", name)
+};
@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use esp_idf_sys as sys;
+use log::warn;
+
+use crate::options::RelayMode;
+
+// Auto mode: switch on at or above RELAY_ON_PPM, off at or below RELAY_OFF_PPM.
+const RELAY_ON_PPM_DEFAULT: u16 = 1200;
+const RELAY_OFF_PPM_DEFAULT: u16 = 900;
+const RELAY_ON_PPM_ENV: Option<&str> = option_env!("RELAY_ON_PPM");
+const RELAY_OFF_PPM_ENV: Option<&str> = option_env!("RELAY_OFF_PPM");
+// Minimum time between two switches, in either direction and in every mode,
+// so a fan relay is not chattered on and off.
+const RELAY_MIN_DWELL_S_DEFAULT: u64 = 60;
+const RELAY_MIN_DWELL_S_ENV: Option<&str> = option_env!("RELAY_MIN_DWELL_S");
+
+// Relay output (e.g. a ventilation fan) on a plain GPIO, switched by CO2 with
+// hysteresis. Starts off.
+pub struct Relay {
+    gpio: i32,
+    active_high: bool,
+    on_ppm: u16,
+    off_ppm: u16,
+    min_dwell: Duration,
+    mode: RelayMode,
+    on: bool,
+    switched_at: Option<Instant>,
+}
+
+impl Relay {
+    pub fn new(gpio: i32, active_high: bool) -> Result<Self> {
+        let env_u16 = |env: Option<&str>| env.and_then(|v| v.trim().parse::<u16>().ok());
+        let mut on_ppm = env_u16(RELAY_ON_PPM_ENV).unwrap_or(RELAY_ON_PPM_DEFAULT);
+        let mut off_ppm = env_u16(RELAY_OFF_PPM_ENV).unwrap_or(RELAY_OFF_PPM_DEFAULT);
+        if off_ppm >= on_ppm {
+            warn!(
+                "RELAY_OFF_PPM ({}) must be below RELAY_ON_PPM ({}), using {}/{}",
+                off_ppm, on_ppm, RELAY_ON_PPM_DEFAULT, RELAY_OFF_PPM_DEFAULT
+            );
+            on_ppm = RELAY_ON_PPM_DEFAULT;
+            off_ppm = RELAY_OFF_PPM_DEFAULT;
+        }
+        let min_dwell = RELAY_MIN_DWELL_S_ENV
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(RELAY_MIN_DWELL_S_DEFAULT);
+
+        let mut relay = Self {
+            gpio,
+            active_high,
+            on_ppm,
+            off_ppm,
+            min_dwell: Duration::from_secs(min_dwell),
+            mode: RelayMode::Auto,
+            on: false,
+            switched_at: None,
+        };
+        unsafe {
+            sys::EspError::convert(sys::gpio_reset_pin(gpio))?;
+            sys::EspError::convert(sys::gpio_set_direction(
+                gpio,
+                sys::gpio_mode_t_GPIO_MODE_OUTPUT,
+            ))?;
+        }
+        relay.write(false)?;
+        Ok(relay)
+    }
+
+    pub fn mode(&self) -> RelayMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RelayMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    // Apply the mode (and, in Auto, the thresholds) to the latest CO2 reading.
+    // Without a reading the relay keeps its state. Returns the new state when
+    // it switched; a change due inside the dwell time waits for a later call.
+    pub fn update(&mut self, co2_ppm: Option<u16>) -> Result<Option<bool>> {
+        let wanted = match (self.mode, co2_ppm) {
+            (RelayMode::On, _) => true,
+            (RelayMode::Off, _) => false,
+            (RelayMode::Auto, Some(ppm)) if ppm >= self.on_ppm => true,
+            (RelayMode::Auto, Some(ppm)) if ppm <= self.off_ppm => false,
+            (RelayMode::Auto, _) => self.on,
+        };
+        if wanted == self.on
+            || self
+                .switched_at
+                .is_some_and(|at| at.elapsed() < self.min_dwell)
+        {
+            return Ok(None);
+        }
+        self.write(wanted)?;
+        self.switched_at = Some(Instant::now());
+        Ok(Some(wanted))
+    }
+
+    fn write(&mut self, on: bool) -> Result<()> {
+        let level = u32::from(on == self.active_high);
+        sys::EspError::convert(unsafe { sys::gpio_set_level(self.gpio, level) })?;
+        self.on = on;
+        Ok(())
+    }
+}
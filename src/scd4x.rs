@@ -0,0 +1,183 @@
+use core::fmt;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use esp_idf_hal::delay::BLOCK;
+use esp_idf_hal::i2c::I2cDriver;
+
+use crate::co2::{Co2Reading, Co2Source};
+use crate::i2c_retry::retry_corrupt;
+use crate::sensor_proto::sht31::{crc8, raw_to_celsius, raw_to_humidity};
+use crate::watchdog::LongOperation;
+
+// Datasheet commands (16-bit, big endian).
+const CMD_START_PERIODIC: u16 = 0x21B1;
+const CMD_READ_MEASUREMENT: u16 = 0xEC05;
+const CMD_STOP_PERIODIC: u16 = 0x3F86;
+const CMD_DATA_READY: u16 = 0xE4B8;
+const CMD_FORCED_RECALIBRATION: u16 = 0x362F;
+const CMD_SET_ASC_ENABLED: u16 = 0x2416;
+// Time the sensor needs after stop_periodic_measurement before accepting commands.
+const STOP_PERIODIC_DELAY: Duration = Duration::from_millis(500);
+const FRC_DELAY: Duration = Duration::from_millis(400);
+const CMD_EXEC_DELAY: Duration = Duration::from_millis(1);
+// Fresh-air reference used for "zero" calibration, matching the MH-Z19B's 400 ppm.
+const FRC_TARGET_PPM: u16 = 400;
+
+#[derive(Debug)]
+pub enum ScdError {
+    I2c(esp_idf_hal::sys::EspError),
+    Crc,
+    NotReady,
+    Calibration,
+}
+
+impl fmt::Display for ScdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I2c(err) => write!(f, "I2C error: {err}"),
+            Self::Crc => write!(f, "SCD4x CRC mismatch"),
+            Self::NotReady => write!(f, "SCD4x has no measurement yet"),
+            Self::Calibration => write!(f, "SCD4x forced recalibration failed"),
+        }
+    }
+}
+
+impl std::error::Error for ScdError {}
+
+pub struct Scd4x {
+    addr: u8,
+    // The sensor only produces a sample every 5 s; between samples the last
+    // one is returned so faster polling doesn't surface as read errors.
+    last: Option<Co2Reading>,
+}
+
+impl Scd4x {
+    pub const DEFAULT_ADDR: u8 = 0x62;
+
+    pub fn new(addr: u8) -> Self {
+        Self { addr, last: None }
+    }
+
+    pub fn new_default() -> Self {
+        Self::new(Self::DEFAULT_ADDR)
+    }
+
+    // Probe for the sensor and start periodic measurement; errors mean "not fitted".
+    pub fn start(&mut self, i2c: &mut I2cDriver<'_>) -> Result<(), ScdError> {
+        // A previous boot may have left it measuring; stop first so the start is accepted.
+        self.write_cmd(i2c, CMD_STOP_PERIODIC)?;
+        thread::sleep(STOP_PERIODIC_DELAY);
+        self.write_cmd(i2c, CMD_START_PERIODIC)
+    }
+
+    pub fn read_measurement(&mut self, i2c: &mut I2cDriver<'_>) -> Result<Co2Reading, ScdError> {
+        let [ready] = retry_corrupt(
+            "SCD4x data ready",
+            || self.read_words::<1>(i2c, CMD_DATA_READY),
+            |err| matches!(err, ScdError::Crc),
+        )?;
+        if ready & 0x07FF == 0 {
+            return self.last.ok_or(ScdError::NotReady);
+        }
+
+        let [co2, raw_temp, raw_rh] = self.read_words::<3>(i2c, CMD_READ_MEASUREMENT)?;
+        let reading = Co2Reading {
+            ppm: co2,
+            // Same conversion and word CRC as the SHT31.
+            temperature_c: Some(raw_to_celsius(raw_temp)),
+            humidity_pct: Some(raw_to_humidity(raw_rh)),
+        };
+        self.last = Some(reading);
+        Ok(reading)
+    }
+
+    pub fn forced_recalibration(&mut self, i2c: &mut I2cDriver<'_>, target_ppm: u16) -> Result<(), ScdError> {
+        let op = LongOperation::start("SCD4x recalibration");
+        self.with_periodic_stopped(i2c, |scd, i2c| {
+            scd.write_cmd_with_arg(i2c, CMD_FORCED_RECALIBRATION, target_ppm)?;
+            op.sleep(FRC_DELAY);
+            let [correction] = scd.read_words_no_cmd::<1>(i2c)?;
+            if correction == 0xFFFF {
+                return Err(ScdError::Calibration);
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_asc(&mut self, i2c: &mut I2cDriver<'_>, enabled: bool) -> Result<(), ScdError> {
+        self.with_periodic_stopped(i2c, |scd, i2c| {
+            scd.write_cmd_with_arg(i2c, CMD_SET_ASC_ENABLED, u16::from(enabled))
+        })
+    }
+
+    fn with_periodic_stopped<T>(
+        &mut self,
+        i2c: &mut I2cDriver<'_>,
+        f: impl FnOnce(&mut Self, &mut I2cDriver<'_>) -> Result<T, ScdError>,
+    ) -> Result<T, ScdError> {
+        self.write_cmd(i2c, CMD_STOP_PERIODIC)?;
+        thread::sleep(STOP_PERIODIC_DELAY);
+        let result = f(self, i2c);
+        // Always resume measuring, even if the command itself failed.
+        self.write_cmd(i2c, CMD_START_PERIODIC)?;
+        self.last = None;
+        result
+    }
+
+    fn write_cmd(&self, i2c: &mut I2cDriver<'_>, cmd: u16) -> Result<(), ScdError> {
+        i2c.write(self.addr, &cmd.to_be_bytes(), BLOCK)
+            .map_err(ScdError::I2c)
+    }
+
+    fn write_cmd_with_arg(&self, i2c: &mut I2cDriver<'_>, cmd: u16, arg: u16) -> Result<(), ScdError> {
+        let [c0, c1] = cmd.to_be_bytes();
+        let [a0, a1] = arg.to_be_bytes();
+        let buf = [c0, c1, a0, a1, crc8(&[a0, a1])];
+        i2c.write(self.addr, &buf, BLOCK).map_err(ScdError::I2c)
+    }
+
+    fn read_words<const N: usize>(&self, i2c: &mut I2cDriver<'_>, cmd: u16) -> Result<[u16; N], ScdError> {
+        self.write_cmd(i2c, cmd)?;
+        thread::sleep(CMD_EXEC_DELAY);
+        self.read_words_no_cmd(i2c)
+    }
+
+    fn read_words_no_cmd<const N: usize>(&self, i2c: &mut I2cDriver<'_>) -> Result<[u16; N], ScdError> {
+        // Each word is followed by its CRC byte; 3 words max (read_measurement).
+        let mut data = [0u8; 9];
+        let data = &mut data[..N * 3];
+        i2c.read(self.addr, data, BLOCK).map_err(ScdError::I2c)?;
+        let mut words = [0u16; N];
+        for (word, chunk) in words.iter_mut().zip(data.chunks_exact(3)) {
+            if crc8(&chunk[0..2]) != chunk[2] {
+                return Err(ScdError::Crc);
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
+
+impl Co2Source for Scd4x {
+    fn name(&self) -> &'static str {
+        "SCD4x"
+    }
+
+    fn read(&mut self, i2c: &mut I2cDriver<'_>) -> Result<Co2Reading> {
+        Ok(self.read_measurement(i2c)?)
+    }
+
+    fn calibrate_zero(&mut self, i2c: &mut I2cDriver<'_>) -> Result<()> {
+        Ok(self.forced_recalibration(i2c, FRC_TARGET_PPM)?)
+    }
+
+    fn set_abc(&mut self, i2c: &mut I2cDriver<'_>, enabled: bool) -> Result<()> {
+        Ok(self.set_asc(i2c, enabled)?)
+    }
+
+    fn reports_temp_humidity(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,99 @@
+use esp_idf_sys as sys;
+use log::warn;
+
+// Quiet hours as "HH:MM-HH:MM" local time (e.g. "23:00-07:00"); unset disables.
+const QUIET_HOURS: Option<&str> = option_env!("QUIET_HOURS");
+// POSIX TZ string used for local time, e.g. "CET-1CEST,M3.5.0,M10.5.0/3".
+const TZ: &str = match option_env!("TZ") {
+    Some(v) => v,
+    None => "UTC0",
+};
+// Anything earlier than this means SNTP has not set the clock yet.
+const MIN_SYNCED_YEAR: i32 = 2024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    start_min: u16,
+    end_min: u16,
+}
+
+impl QuietHours {
+    pub fn from_env() -> Option<Self> {
+        let spec = QUIET_HOURS?.trim();
+        if spec.is_empty() {
+            return None;
+        }
+        let parsed = spec
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_hhmm(start)?, parse_hhmm(end)?)));
+        match parsed {
+            Some((start_min, end_min)) => Some(Self { start_min, end_min }),
+            None => {
+                warn!("Invalid QUIET_HOURS {:?}, night mode disabled", spec);
+                None
+            }
+        }
+    }
+
+    // Window may wrap past midnight (start > end).
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        if self.start_min <= self.end_min {
+            (self.start_min..self.end_min).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start_min || minute_of_day < self.end_min
+        }
+    }
+
+    // False while the clock is not synced, so the display stays always-on.
+    pub fn is_active_now(&self) -> bool {
+        local_minute_of_day().is_some_and(|m| self.contains(m))
+    }
+}
+
+pub fn init_timezone() {
+    std::env::set_var("TZ", TZ);
+    unsafe { sys::tzset() };
+}
+
+pub fn local_minute_of_day() -> Option<u16> {
+    let tm = local_tm()?;
+    Some((tm.tm_hour * 60 + tm.tm_min) as u16)
+}
+
+// Identifies the local calendar day (changes at local midnight).
+pub fn local_day() -> Option<i32> {
+    let tm = local_tm()?;
+    Some((tm.tm_year + 1900) * 1000 + tm.tm_yday)
+}
+
+// Seconds since the Unix epoch; None until SNTP has set the clock.
+pub fn unix_time() -> Option<i64> {
+    local_tm()?;
+    let mut now: sys::time_t = 0;
+    unsafe { sys::time(&mut now) };
+    Some(now as i64)
+}
+
+// None until SNTP has set the clock.
+fn local_tm() -> Option<sys::tm> {
+    let mut now: sys::time_t = 0;
+    let mut tm: sys::tm = unsafe { core::mem::zeroed() };
+    unsafe {
+        sys::time(&mut now);
+        sys::localtime_r(&now, &mut tm);
+    }
+    if tm.tm_year + 1900 < MIN_SYNCED_YEAR {
+        return None;
+    }
+    Some(tm)
+}
+
+fn parse_hhmm(text: &str) -> Option<u16> {
+    let (h, m) = text.trim().split_once(':')?;
+    let h = h.parse::<u16>().ok()?;
+    let m = m.parse::<u16>().ok()?;
+    if h > 23 || m > 59 {
+        return None;
+    }
+    Some(h * 60 + m)
+}
@@ -0,0 +1,117 @@
+use std::thread;
+use std::time::Duration;
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use esp_idf_hal::i2c::I2cDriver;
+use esp_idf_hal::ledc;
+use log::{error, info};
+
+use crate::battery::BatteryMonitor;
+use crate::co2::Co2Source;
+use crate::sht31::Sht31;
+use crate::st7789::{St7789, LCD_H, LCD_W};
+use crate::touch::{i2c_scan, TP_ADDR};
+
+// How long each full-screen colour stays up for the operator to inspect.
+const PATTERN_HOLD: Duration = Duration::from_millis(700);
+// Pass/fail screen before normal boot continues.
+const RESULT_HOLD: Duration = Duration::from_secs(5);
+// Without a BUTTON_GPIO there is nothing to hold, so the test is skipped
+// unless this is set (SELFTEST_WITHOUT_BUTTON=1); it then runs on every boot.
+pub const RUN_WITHOUT_BUTTON: bool = match option_env!("SELFTEST_WITHOUT_BUTTON") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+
+// One line of the summary; `detail` is the reading or the error.
+struct Check {
+    name: &'static str,
+    pass: bool,
+    detail: String,
+}
+
+impl Check {
+    fn new<T, E: core::fmt::Debug>(
+        name: &'static str,
+        result: Result<T, E>,
+        ok: impl FnOnce(T) -> String,
+    ) -> Self {
+        match result {
+            Ok(value) => Self {
+                name,
+                pass: true,
+                detail: ok(value),
+            },
+            Err(err) => Self {
+                name,
+                pass: false,
+                detail: format!("{:?}", err),
+            },
+        }
+    }
+}
+
+// Production self-test: exercises every peripheral once without Wi-Fi, logs a
+// PASS/FAIL line per subsystem and ends on a green (all passed) or red screen.
+// The LCD patterns need a human to look at them; only the SPI writes are checked.
+pub fn run(
+    lcd: &mut St7789<'static, ledc::TIMER0>,
+    i2c: &mut I2cDriver<'static>,
+    co2: &mut dyn Co2Source,
+    battery: &mut BatteryMonitor<'static>,
+    sht31: &Sht31,
+) -> bool {
+    info!("Self-test started");
+    let mut frame = vec![Rgb565::BLACK; LCD_W * LCD_H];
+    let mut checks = Vec::new();
+
+    let mut lcd_result = Ok(());
+    for color in [Rgb565::RED, Rgb565::GREEN, Rgb565::BLUE, Rgb565::WHITE] {
+        frame.fill(color);
+        lcd_result = lcd_result.and_then(|()| lcd.flush_full(&frame));
+        thread::sleep(PATTERN_HOLD);
+    }
+    checks.push(Check::new("LCD", lcd_result, |()| {
+        "R/G/B/W patterns sent".to_string()
+    }));
+
+    let found = i2c_scan(i2c);
+    let addresses = format!("{:02X?}", found);
+    for (name, addr) in [("I2C touch", TP_ADDR), ("I2C SHT31", Sht31::DEFAULT_ADDR)] {
+        checks.push(Check {
+            name,
+            pass: found.contains(&addr),
+            detail: format!("0x{:02X} in {}", addr, addresses),
+        });
+    }
+
+    checks.push(Check::new("SHT31", sht31.read(i2c), |r| {
+        format!("{:.1} C, {:.0} %", r.temperature_c, r.humidity_pct)
+    }));
+    let co2_name = co2.name();
+    checks.push(Check::new(co2_name, co2.read(i2c), |r| {
+        format!("{} ppm", r.ppm)
+    }));
+    let battery_result = battery.read(i2c).ok_or("read failed");
+    checks.push(Check::new("Battery", battery_result, |r| match r.soc_pct {
+        Some(pct) => format!("{:.2} V, {} %", r.voltage_v, pct),
+        None => format!("{:.2} V", r.voltage_v),
+    }));
+
+    let all_pass = checks.iter().all(|check| check.pass);
+    for check in &checks {
+        if check.pass {
+            info!("Self-test PASS {:<10} {}", check.name, check.detail);
+        } else {
+            error!("Self-test FAIL {:<10} {}", check.name, check.detail);
+        }
+    }
+    info!("Self-test {}", if all_pass { "PASSED" } else { "FAILED" });
+
+    frame.fill(if all_pass { Rgb565::GREEN } else { Rgb565::RED });
+    if let Err(err) = lcd.flush_full(&frame) {
+        error!("Self-test result screen failed: {}", err);
+    }
+    thread::sleep(RESULT_HOLD);
+    all_pass
+}
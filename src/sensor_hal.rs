@@ -0,0 +1,486 @@
+// SHT31 and MH-Z19B drivers over the generic embedded-hal 1.0 traits, built on
+// the frame and CRC work in sensor_proto.rs. The firmware runs them on
+// esp-idf-hal's `I2cDriver`, `UartDriver` and `FreeRtos` (sht31.rs and
+// mhz19b.rs only add the build-time config and the esp-idf glue), and
+// tools/driver_tests runs the same code against mock buses. Nothing here
+// depends on esp-idf.
+
+pub mod sht31 {
+    use embedded_hal::delay::DelayNs;
+    use embedded_hal::i2c::I2c;
+
+    use crate::i2c_retry::retry_corrupt;
+    use crate::sensor_proto::sht31::{self as proto, CrcError};
+
+    pub const DEFAULT_ADDR: u8 = 0x44;
+    // Plausible ranges; anything outside is treated as a corrupted read.
+    const TEMP_MIN_C: f32 = -40.0;
+    const TEMP_MAX_C: f32 = 85.0;
+    const HUMIDITY_MIN_PCT: f32 = 0.0;
+    const HUMIDITY_MAX_PCT: f32 = 100.0;
+    // The SHT31 shares the bus with the touch controller; retry transient NACKs
+    // with a short settle delay so a touch transaction cannot corrupt a read.
+    const WRITE_ATTEMPTS: usize = 3;
+    const WRITE_RETRY_DELAY_MS: u32 = 2;
+    // Fetch data (periodic mode).
+    const CMD_FETCH_DATA: [u8; 2] = [0xE0, 0x00];
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Repeatability {
+        Low,
+        Medium,
+        High,
+    }
+
+    impl Repeatability {
+        // Single-shot command (datasheet table 9), with or without clock stretching.
+        pub fn single_shot_cmd(self, clock_stretch: bool) -> [u8; 2] {
+            match (clock_stretch, self) {
+                (true, Self::High) => [0x2C, 0x06],
+                (true, Self::Medium) => [0x2C, 0x0D],
+                (true, Self::Low) => [0x2C, 0x10],
+                (false, Self::High) => [0x24, 0x00],
+                (false, Self::Medium) => [0x24, 0x0B],
+                (false, Self::Low) => [0x24, 0x16],
+            }
+        }
+
+        // Periodic acquisition at 1 measurement per second (datasheet table 10).
+        pub fn periodic_1mps_cmd(self) -> [u8; 2] {
+            match self {
+                Self::High => [0x21, 0x30],
+                Self::Medium => [0x21, 0x26],
+                Self::Low => [0x21, 0x2D],
+            }
+        }
+
+        // Worst-case measurement duration, rounded up.
+        pub fn max_duration_ms(self) -> u32 {
+            match self {
+                Self::High => 16,
+                Self::Medium => 7,
+                Self::Low => 5,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum Error<E> {
+        I2c(E),
+        Crc(CrcError),
+        Implausible(Measurement),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Measurement {
+        pub temperature_c: f32,
+        pub humidity_pct: f32,
+    }
+
+    impl Measurement {
+        pub fn validate<E>(self) -> Result<Self, Error<E>> {
+            let temp_ok = (TEMP_MIN_C..=TEMP_MAX_C).contains(&self.temperature_c);
+            let hum_ok = (HUMIDITY_MIN_PCT..=HUMIDITY_MAX_PCT).contains(&self.humidity_pct);
+            if temp_ok && hum_ok {
+                Ok(self)
+            } else {
+                Err(Error::Implausible(self))
+            }
+        }
+    }
+
+    pub struct Sht31 {
+        addr: u8,
+        repeatability: Repeatability,
+        // Single-shot reads with clock stretching: the sensor holds SCL low
+        // until the result is ready instead of the driver waiting out the
+        // worst case. The bus (its timeout) bounds how long that may take.
+        clock_stretch: bool,
+    }
+
+    impl Sht31 {
+        pub fn new(addr: u8) -> Self {
+            Self {
+                addr,
+                repeatability: Repeatability::High,
+                clock_stretch: false,
+            }
+        }
+
+        pub fn new_default() -> Self {
+            Self::new(DEFAULT_ADDR)
+        }
+
+        pub fn with_mode(mut self, repeatability: Repeatability, clock_stretch: bool) -> Self {
+            self.repeatability = repeatability;
+            self.clock_stretch = clock_stretch;
+            self
+        }
+
+        // One single-shot measurement; a CRC mismatch re-runs the whole
+        // measurement (see i2c_retry).
+        pub fn read<I: I2c>(
+            &self,
+            i2c: &mut I,
+            delay: &mut impl DelayNs,
+        ) -> Result<Measurement, Error<I::Error>> {
+            retry_corrupt(
+                "SHT31 measurement",
+                || self.read_single_shot(i2c, delay),
+                |err| matches!(err, Error::Crc(_)),
+            )
+        }
+
+        pub fn start_periodic<I: I2c>(
+            &self,
+            i2c: &mut I,
+            delay: &mut impl DelayNs,
+        ) -> Result<(), Error<I::Error>> {
+            self.write_cmd(i2c, delay, self.repeatability.periodic_1mps_cmd())
+        }
+
+        // The sensor NACKs the read if no new measurement is available yet.
+        // Not retried on CRC errors: the fetch clears the result, so the next
+        // period's reading is the retry.
+        pub fn fetch_periodic<I: I2c>(
+            &self,
+            i2c: &mut I,
+            delay: &mut impl DelayNs,
+        ) -> Result<Measurement, Error<I::Error>> {
+            self.write_cmd(i2c, delay, CMD_FETCH_DATA)?;
+            self.read_measurement(i2c)
+        }
+
+        fn read_single_shot<I: I2c>(
+            &self,
+            i2c: &mut I,
+            delay: &mut impl DelayNs,
+        ) -> Result<Measurement, Error<I::Error>> {
+            let cmd = self.repeatability.single_shot_cmd(self.clock_stretch);
+            self.write_cmd(i2c, delay, cmd)?;
+            if !self.clock_stretch {
+                delay.delay_ms(self.repeatability.max_duration_ms());
+            }
+            self.read_measurement(i2c)
+        }
+
+        fn write_cmd<I: I2c>(
+            &self,
+            i2c: &mut I,
+            delay: &mut impl DelayNs,
+            cmd: [u8; 2],
+        ) -> Result<(), Error<I::Error>> {
+            let mut attempt = 1;
+            loop {
+                match i2c.write(self.addr, &cmd) {
+                    Ok(()) => return Ok(()),
+                    Err(_) if attempt < WRITE_ATTEMPTS => {
+                        attempt += 1;
+                        delay.delay_ms(WRITE_RETRY_DELAY_MS);
+                    }
+                    Err(err) => return Err(Error::I2c(err)),
+                }
+            }
+        }
+
+        fn read_measurement<I: I2c>(&self, i2c: &mut I) -> Result<Measurement, Error<I::Error>> {
+            let mut data = [0u8; proto::MEASUREMENT_LEN];
+            i2c.read(self.addr, &mut data).map_err(Error::I2c)?;
+            let (temperature_c, humidity_pct) =
+                proto::parse_measurement(&data).map_err(Error::Crc)?;
+            Measurement {
+                temperature_c,
+                humidity_pct,
+            }
+            .validate()
+        }
+    }
+}
+
+pub mod mhz19b {
+    use core::fmt;
+
+    use embedded_hal::delay::DelayNs;
+    use embedded_hal_nb::nb;
+    use embedded_hal_nb::serial::{Read, Write};
+    use log::{debug, error, info, warn};
+
+    use crate::sensor_proto::mhz19b::{self as proto, FrameError, PendingDiscard};
+
+    // Idle time between polls of a serial port with no byte ready. One
+    // FreeRTOS tick at the default 100 Hz: esp-idf rounds shorter delays up to
+    // a tick, and the reply timeout is counted in these steps.
+    const POLL_INTERVAL_MS: u32 = 10;
+    // Upper bound on stale bytes dropped before a command, so a port that
+    // never runs dry (e.g. a floating RX line) cannot hang the flush.
+    const FLUSH_MAX_BYTES: usize = 256;
+
+    #[derive(Debug)]
+    pub enum Error<E> {
+        Serial(E),
+        Timeout,
+        Frame(FrameError),
+        // Valid frame dropped right after a state-changing command.
+        Settling,
+        // Valid frame below proto::PPM_FLOOR (sensor fault, not clean air).
+        OutOfRange(u16),
+    }
+
+    impl<E: fmt::Debug> fmt::Display for Error<E> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Serial(err) => write!(f, "UART error: {err:?}"),
+                Self::Timeout => write!(f, "timeout waiting for MH-Z19B frame"),
+                Self::Frame(FrameError::Header) => write!(f, "invalid MH-Z19B frame header"),
+                Self::Frame(FrameError::Checksum { .. }) => write!(f, "MH-Z19B checksum mismatch"),
+                Self::Settling => write!(f, "MH-Z19B settling after a command"),
+                Self::OutOfRange(ppm) => write!(
+                    f,
+                    "MH-Z19B reading {ppm} ppm below {} (sensor fault)",
+                    proto::PPM_FLOOR
+                ),
+            }
+        }
+    }
+
+    impl<E: fmt::Debug> std::error::Error for Error<E> {}
+
+    // The port itself is passed to each call, already configured by the caller
+    // (9600 8N1 for genuine sensors); the driver only keeps per-sensor state.
+    pub struct Mhz19b {
+        // Frames dropped after each state-changing command.
+        discard_frames: u8,
+        pending_discard: PendingDiscard,
+        // Last accepted reading, reported by `read` while frames are discarded.
+        last_ppm: Option<u16>,
+        // Last complete 9-byte reply, checksum good or bad (see `take_raw_frame`).
+        last_frame: Option<[u8; proto::FRAME_LEN]>,
+        // The raw bytes of the first reply (and the first after
+        // `reset_raw_log`) are logged once, to help pick the framing for
+        // clones that fail checksums.
+        raw_logged: bool,
+    }
+
+    impl Mhz19b {
+        pub fn new(discard_frames: u8) -> Self {
+            Self {
+                discard_frames,
+                pending_discard: PendingDiscard::default(),
+                last_ppm: None,
+                last_frame: None,
+                raw_logged: false,
+            }
+        }
+
+        pub fn new_default() -> Self {
+            Self::new(proto::DISCARD_FRAMES_DEFAULT)
+        }
+
+        // Valid frames still to drop before readings are returned again.
+        pub fn pending_discard(&self) -> u8 {
+            self.pending_discard.remaining()
+        }
+
+        // The reading the firmware uses: `read_ppm_retry`, and while frames
+        // are being discarded the previous reading; with none yet (e.g. at
+        // boot) it reads again until the discards are used up.
+        pub fn read<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+            delay: &mut impl DelayNs,
+            timeout_ms: u32,
+            attempts: u32,
+        ) -> Result<u16, Error<S::Error>> {
+            loop {
+                match self.read_ppm_retry(serial, delay, timeout_ms, attempts) {
+                    Err(Error::Settling) => {
+                        if let Some(ppm) = self.last_ppm {
+                            return Ok(ppm);
+                        }
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        // Re-issue the query on transient framing failures; stale bytes are
+        // flushed before every request, so each attempt starts clean.
+        pub fn read_ppm_retry<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+            delay: &mut impl DelayNs,
+            timeout_ms: u32,
+            attempts: u32,
+        ) -> Result<u16, Error<S::Error>> {
+            let mut attempt = 1;
+            loop {
+                match self.read_ppm(serial, delay, timeout_ms) {
+                    Err(err @ (Error::Timeout | Error::Frame(_))) if attempt < attempts => {
+                        warn!("MH-Z19B read attempt {}/{} failed: {}", attempt, attempts, err);
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        // Query the concentration and wait up to `timeout_ms` for the reply.
+        // Returns `Settling` (and consumes one pending discard) for frames
+        // that arrive right after `calibrate_zero` or `set_abc`, and
+        // `OutOfRange` for readings under the floor. Warmup is left to the caller.
+        pub fn read_ppm<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+            delay: &mut impl DelayNs,
+            timeout_ms: u32,
+        ) -> Result<u16, Error<S::Error>> {
+            let frame = self.exchange(
+                serial,
+                delay,
+                proto::command(proto::CMD_READ_PPM, [0; 5]),
+                timeout_ms,
+            )?;
+            match proto::validate_frame(&frame, proto::CMD_READ_PPM) {
+                Ok(()) => {}
+                Err(err @ FrameError::Header) => {
+                    error!("MH-Z19B frame header mismatch: {:02X?}", frame);
+                    return Err(Error::Frame(err));
+                }
+                Err(err @ FrameError::Checksum { expected, got }) => {
+                    error!(
+                        "MH-Z19B checksum mismatch: expected {:02X}, got {:02X}, frame {:02X?}",
+                        expected, got, frame
+                    );
+                    return Err(Error::Frame(err));
+                }
+            }
+            let ppm = proto::ppm(&frame);
+            if self.pending_discard.take() {
+                debug!(
+                    "MH-Z19B discarded {} ppm ({} more to drop)",
+                    ppm,
+                    self.pending_discard.remaining()
+                );
+                return Err(Error::Settling);
+            }
+            if !proto::ppm_in_range(ppm) {
+                warn!("MH-Z19B implausible reading {} ppm, treating as fault", ppm);
+                return Err(Error::OutOfRange(ppm));
+            }
+            self.last_ppm = Some(ppm);
+            Ok(ppm)
+        }
+
+        pub fn calibrate_zero<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+        ) -> Result<(), Error<S::Error>> {
+            Self::send(serial, &proto::command(proto::CMD_ZERO_POINT, [0; 5]))?;
+            self.pending_discard.arm(self.discard_frames);
+            Ok(())
+        }
+
+        // ABC (automatic baseline correction) enable/disable command.
+        pub fn set_abc<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+            enabled: bool,
+        ) -> Result<(), Error<S::Error>> {
+            let abc = if enabled { 0xA0 } else { 0x00 };
+            Self::send(serial, &proto::command(proto::CMD_ABC, [abc, 0, 0, 0, 0]))?;
+            self.pending_discard.arm(self.discard_frames);
+            Ok(())
+        }
+
+        // Last complete reply received since the previous call, valid or not.
+        pub fn take_raw_frame(&mut self) -> Option<[u8; proto::FRAME_LEN]> {
+            self.last_frame.take()
+        }
+
+        // Log the raw bytes of the next reply again (e.g. after a UART reset).
+        pub fn reset_raw_log(&mut self) {
+            self.raw_logged = false;
+        }
+
+        fn exchange<S: Read<u8> + Write<u8>>(
+            &mut self,
+            serial: &mut S,
+            delay: &mut impl DelayNs,
+            cmd: [u8; proto::FRAME_LEN],
+            timeout_ms: u32,
+        ) -> Result<[u8; proto::FRAME_LEN], Error<S::Error>> {
+            Self::send(serial, &cmd)?;
+
+            let mut buf = [0u8; proto::FRAME_LEN];
+            let mut received = 0usize;
+            let mut discarded = 0usize;
+            let mut waited_ms = 0u32;
+            let mut raw = Vec::new();
+            while received < buf.len() {
+                match serial.read() {
+                    Ok(byte) => {
+                        if !self.raw_logged {
+                            raw.push(byte);
+                        }
+                        buf[received] = byte;
+                        received += 1;
+                        let skipped = proto::realign(&mut buf, received);
+                        received -= skipped;
+                        discarded += skipped;
+                    }
+                    Err(nb::Error::WouldBlock) => {
+                        if waited_ms >= timeout_ms {
+                            break;
+                        }
+                        delay.delay_ms(POLL_INTERVAL_MS);
+                        waited_ms += POLL_INTERVAL_MS;
+                    }
+                    Err(nb::Error::Other(err)) => return Err(Error::Serial(err)),
+                }
+            }
+
+            if !self.raw_logged && !raw.is_empty() {
+                info!("MH-Z19B first raw bytes: {:02X?}", raw);
+                self.raw_logged = true;
+            }
+            if discarded > 0 {
+                warn!("MH-Z19B resync: discarded {} byte(s) before frame start", discarded);
+            }
+            if received < buf.len() {
+                if discarded > 0 && received == 0 {
+                    error!("MH-Z19B no frame header found in {} byte(s)", discarded);
+                    return Err(Error::Frame(FrameError::Header));
+                }
+                error!(
+                    "MH-Z19B timeout: got {}/9 bytes: {:02X?}",
+                    received,
+                    &buf[..received]
+                );
+                return Err(Error::Timeout);
+            }
+
+            debug!("MH-Z19B frame: {:02X?}", buf);
+            self.last_frame = Some(buf);
+            Ok(buf)
+        }
+
+        // Drop stale RX bytes (late replies, line noise), then write `cmd` and
+        // wait until it is sent.
+        fn send<S: Read<u8> + Write<u8>>(
+            serial: &mut S,
+            cmd: &[u8; proto::FRAME_LEN],
+        ) -> Result<(), Error<S::Error>> {
+            for _ in 0..FLUSH_MAX_BYTES {
+                match serial.read() {
+                    Ok(_) => {}
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(err)) => return Err(Error::Serial(err)),
+                }
+            }
+            for byte in cmd {
+                nb::block!(serial.write(*byte)).map_err(Error::Serial)?;
+            }
+            nb::block!(serial.flush()).map_err(Error::Serial)
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Pure frame/CRC handling for the MH-Z19B and SHT31. Only `core` is used here
+// (no esp-idf, no allocation), so the functions can be lifted into another
+// project or fed captured bytes on the host; the UART/I2C drivers in
+// `mhz19b.rs` and `sht31.rs` wrap them. `scd4x.rs` reuses the SHT31 CRC and
+// conversions (same Sensirion word format).
+
+pub mod mhz19b {
+    pub const FRAME_LEN: usize = 9;
+    pub const START: u8 = 0xFF;
+    pub const SENSOR_NUM: u8 = 0x01;
+    pub const CMD_READ_PPM: u8 = 0x86;
+    pub const CMD_ZERO_POINT: u8 = 0x87;
+    pub const CMD_ABC: u8 = 0x79;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum FrameError {
+        Header,
+        Checksum { expected: u8, got: u8 },
+    }
+
+    // Two's complement of the byte sum (bytes 1..=7 of a frame).
+    pub fn checksum(bytes: &[u8]) -> u8 {
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        0u8.wrapping_sub(sum)
+    }
+
+    // Build a 9-byte request frame with the checksum filled in.
+    pub fn command(cmd: u8, args: [u8; 5]) -> [u8; FRAME_LEN] {
+        let mut frame = [
+            START, SENSOR_NUM, cmd, args[0], args[1], args[2], args[3], args[4], 0,
+        ];
+        frame[8] = checksum(&frame[1..8]);
+        frame
+    }
+
+    // Check header and checksum of a reply to `cmd`.
+    pub fn validate_frame(frame: &[u8; FRAME_LEN], cmd: u8) -> Result<(), FrameError> {
+        if frame[0] != START || frame[1] != cmd {
+            return Err(FrameError::Header);
+        }
+        let expected = checksum(&frame[1..8]);
+        if frame[8] != expected {
+            return Err(FrameError::Checksum {
+                expected,
+                got: frame[8],
+            });
+        }
+        Ok(())
+    }
+
+    // CO2 concentration from a validated 0x86 reply.
+    pub fn ppm(frame: &[u8; FRAME_LEN]) -> u16 {
+        u16::from_be_bytes([frame[2], frame[3]])
+    }
+
+    // Lowest plausible reading (outdoor air). A faulty sensor can report 0 ppm
+    // with a valid checksum, which would otherwise look like perfect air.
+    pub const PPM_FLOOR: u16 = 400;
+
+    // False for readings the drivers report as a sensor fault.
+    pub fn ppm_in_range(ppm: u16) -> bool {
+        ppm >= PPM_FLOOR
+    }
+
+    // Frames dropped after a command that changes sensor state (ABC,
+    // calibration); the first replies afterwards can still reflect the old
+    // configuration.
+    pub const DISCARD_FRAMES_DEFAULT: u8 = 2;
+
+    // Valid frames still to drop after the last state-changing command. Only
+    // frames that arrived and validated count: a timed-out or corrupt read
+    // leaves it unchanged, and a new command re-arms it.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct PendingDiscard(u8);
+
+    impl PendingDiscard {
+        pub fn arm(&mut self, frames: u8) {
+            self.0 = frames;
+        }
+
+        pub fn remaining(self) -> u8 {
+            self.0
+        }
+
+        // For each valid frame: true (and one fewer left) if it is dropped.
+        pub fn take(&mut self) -> bool {
+            if self.0 == 0 {
+                return false;
+            }
+            self.0 -= 1;
+            true
+        }
+    }
+
+    // Optional linear temperature compensation of the reported ppm:
+    //   ppm_comp = ppm * (1 + COEFF * (temp_c - REF_C))
+    // COEFF is the fractional reading change per degree C away from the
+    // reference temperature the sensor was calibrated at. Tune per deployment
+    // against a reference instrument; with compensation disabled the raw value
+    // is returned.
+    pub const CO2_TEMP_COMP_ENABLED: bool = false;
+    pub const CO2_TEMP_COMP_COEFF_PER_C: f32 = 0.003;
+    pub const CO2_TEMP_COMP_REF_C: f32 = 25.0;
+
+    // `ppm` compensated with the constants above.
+    pub fn compensate_temperature(ppm: u16, temp_c: Option<f32>) -> u16 {
+        apply_temp_compensation(ppm, temp_c, CO2_TEMP_COMP_ENABLED)
+    }
+
+    // Unchanged while disabled or without a temperature.
+    pub fn apply_temp_compensation(ppm: u16, temp_c: Option<f32>, enabled: bool) -> u16 {
+        match temp_c.filter(|_| enabled) {
+            Some(temp_c) => {
+                let factor = 1.0 + CO2_TEMP_COMP_COEFF_PER_C * (temp_c - CO2_TEMP_COMP_REF_C);
+                // Rounds half up; the cast saturates, so a negative result becomes 0.
+                (ppm as f32 * factor + 0.5) as u16
+            }
+            None => ppm,
+        }
+    }
+
+    // Shift `buf[..received]` so it starts at a plausible reply header (0xFF 0x86,
+    // or a trailing 0xFF still waiting for its second byte). Returns how many
+    // leading bytes were dropped.
+    pub fn realign(buf: &mut [u8], received: usize) -> usize {
+        let start = (0..received)
+            .find(|&i| buf[i] == START && (i + 1 == received || buf[i + 1] == CMD_READ_PPM))
+            .unwrap_or(received);
+        if start > 0 {
+            buf.copy_within(start..received, 0);
+        }
+        start
+    }
+}
+
+pub mod sht31 {
+    pub const MEASUREMENT_LEN: usize = 6;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CrcError {
+        Temperature,
+        Humidity,
+    }
+
+    // CRC-8 with polynomial 0x31, init 0xFF (Sensirion standard).
+    pub fn crc8(bytes: &[u8]) -> u8 {
+        let mut crc = 0xFFu8;
+        for byte in bytes {
+            crc ^= *byte;
+            for _ in 0..8 {
+                if (crc & 0x80) != 0 {
+                    crc = (crc << 1) ^ 0x31;
+                } else {
+                    crc <<= 1;
+                }
+            }
+        }
+        crc
+    }
+
+    pub fn raw_to_celsius(raw: u16) -> f32 {
+        -45.0 + 175.0 * (raw as f32) / 65535.0
+    }
+
+    pub fn raw_to_humidity(raw: u16) -> f32 {
+        100.0 * (raw as f32) / 65535.0
+    }
+
+    // Check both word CRCs of a measurement and return the raw (temperature, humidity).
+    pub fn parse_raw(data: &[u8; MEASUREMENT_LEN]) -> Result<(u16, u16), CrcError> {
+        if crc8(&data[0..2]) != data[2] {
+            return Err(CrcError::Temperature);
+        }
+        if crc8(&data[3..5]) != data[5] {
+            return Err(CrcError::Humidity);
+        }
+        Ok((
+            u16::from_be_bytes([data[0], data[1]]),
+            u16::from_be_bytes([data[3], data[4]]),
+        ))
+    }
+
+    // Temperature (°C) and relative humidity (%) from a measurement, unvalidated.
+    pub fn parse_measurement(data: &[u8; MEASUREMENT_LEN]) -> Result<(f32, f32), CrcError> {
+        let (raw_temp, raw_rh) = parse_raw(data)?;
+        Ok((raw_to_celsius(raw_temp), raw_to_humidity(raw_rh)))
+    }
+}
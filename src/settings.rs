@@ -0,0 +1,327 @@
+use anyhow::Result;
+use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition};
+use log::{info, warn};
+
+use crate::options::{
+    clamp_co2_alarm, clamp_co2_smoothing, Layout, TempHumSource, TempUnit, ThemePreset,
+    CO2_ALARM_DEFAULT, CO2_SMOOTHING_DEFAULT,
+};
+
+const NVS_NAMESPACE: &str = "c6demo";
+const KEY_CO2_ALARM: &str = "co2_alarm";
+const KEY_CO2_HISTORY: &str = "co2_hist";
+const KEY_TEMP_HUM_SOURCE: &str = "th_source";
+const KEY_THEME: &str = "theme";
+const KEY_CO2_PEAK: &str = "co2_peak";
+const KEY_TEMP_UNIT: &str = "temp_unit";
+const KEY_CO2_SMOOTHING: &str = "co2_smooth";
+const KEY_TEMP_DECIMALS: &str = "temp_dec";
+const KEY_LAYOUT: &str = "layout";
+const KEY_CRASH_COUNT: &str = "crash_count";
+const KEY_LCD_INVERT: &str = "lcd_invert";
+// Every key stored in the namespace; factory reset removes all of them.
+const ALL_KEYS: &[&str] = &[
+    KEY_CO2_ALARM,
+    KEY_CO2_HISTORY,
+    KEY_TEMP_HUM_SOURCE,
+    KEY_THEME,
+    KEY_CO2_PEAK,
+    KEY_TEMP_UNIT,
+    KEY_CO2_SMOOTHING,
+    KEY_TEMP_DECIMALS,
+    KEY_LAYOUT,
+    KEY_CRASH_COUNT,
+    KEY_LCD_INVERT,
+];
+
+// Blob layout: [version, count, count * u16 LE]. Bump on any layout/interval
+// change so stale blobs from older firmware are discarded.
+const CO2_HISTORY_VERSION: u8 = 1;
+const CO2_HISTORY_MAX_POINTS: usize = 64;
+
+// NVS encoding of the temp/humidity source.
+impl TempHumSource {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Sht31,
+            2 => Self::Scd4x,
+            _ => Self::Auto,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Auto => 0,
+            Self::Sht31 => 1,
+            Self::Scd4x => 2,
+        }
+    }
+}
+
+// Runtime-tunable settings persisted in the default NVS partition.
+pub struct Settings {
+    nvs: Option<EspDefaultNvs>,
+}
+
+impl Settings {
+    pub fn new(partition: EspDefaultNvsPartition) -> Self {
+        let nvs = match EspDefaultNvs::new(partition, NVS_NAMESPACE, true) {
+            Ok(nvs) => Some(nvs),
+            Err(err) => {
+                warn!("NVS settings unavailable, using defaults: {:?}", err);
+                None
+            }
+        };
+        Self { nvs }
+    }
+
+    pub fn co2_alarm_ppm(&self) -> u16 {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u16(KEY_CO2_ALARM).ok().flatten());
+        clamp_co2_alarm(stored.unwrap_or(CO2_ALARM_DEFAULT))
+    }
+
+    pub fn set_co2_alarm_ppm(&mut self, ppm: u16) -> Result<u16> {
+        let ppm = clamp_co2_alarm(ppm);
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u16(KEY_CO2_ALARM, ppm)?;
+        }
+        Ok(ppm)
+    }
+
+    pub fn co2_smoothing(&self) -> u8 {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_CO2_SMOOTHING).ok().flatten());
+        clamp_co2_smoothing(stored.unwrap_or(CO2_SMOOTHING_DEFAULT))
+    }
+
+    pub fn set_co2_smoothing(&mut self, window: u8) -> Result<u8> {
+        let window = clamp_co2_smoothing(window);
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_CO2_SMOOTHING, window)?;
+        }
+        Ok(window)
+    }
+
+    pub fn temp_hum_source(&self) -> TempHumSource {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_TEMP_HUM_SOURCE).ok().flatten());
+        stored.map(TempHumSource::from_u8).unwrap_or(TempHumSource::Auto)
+    }
+
+    pub fn set_temp_hum_source(&mut self, source: TempHumSource) -> Result<()> {
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_TEMP_HUM_SOURCE, source.to_u8())?;
+        }
+        Ok(())
+    }
+
+    // None until a theme is picked at runtime; callers fall back to the build default.
+    pub fn theme(&self) -> Option<ThemePreset> {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_THEME).ok().flatten())?;
+        match stored {
+            0 => Some(ThemePreset::Dark),
+            1 => Some(ThemePreset::HighContrast),
+            2 => Some(ThemePreset::Amber),
+            _ => None,
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: ThemePreset) -> Result<()> {
+        let value = match theme {
+            ThemePreset::Dark => 0,
+            ThemePreset::HighContrast => 1,
+            ThemePreset::Amber => 2,
+        };
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_THEME, value)?;
+        }
+        Ok(())
+    }
+
+    // None until a unit is picked at runtime; callers fall back to the build default.
+    pub fn temp_unit(&self) -> Option<TempUnit> {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_TEMP_UNIT).ok().flatten())?;
+        match stored {
+            0 => Some(TempUnit::Celsius),
+            1 => Some(TempUnit::Fahrenheit),
+            _ => None,
+        }
+    }
+
+    pub fn set_temp_unit(&mut self, unit: TempUnit) -> Result<()> {
+        let value = match unit {
+            TempUnit::Celsius => 0,
+            TempUnit::Fahrenheit => 1,
+        };
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_TEMP_UNIT, value)?;
+        }
+        Ok(())
+    }
+
+    // None until a layout is picked at runtime; callers fall back to the build default.
+    pub fn layout(&self) -> Option<Layout> {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_LAYOUT).ok().flatten())?;
+        match stored {
+            0 => Some(Layout::Cards),
+            1 => Some(Layout::Big),
+            _ => None,
+        }
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) -> Result<()> {
+        let value = match layout {
+            Layout::Cards => 0,
+            Layout::Big => 1,
+        };
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_LAYOUT, value)?;
+        }
+        Ok(())
+    }
+
+    // None until set at runtime; the panel then keeps its LCD_INVERT build default.
+    pub fn lcd_invert(&self) -> Option<bool> {
+        self.nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_LCD_INVERT).ok().flatten())
+            .map(|value| value != 0)
+    }
+
+    pub fn set_lcd_invert(&mut self, invert: bool) -> Result<()> {
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_LCD_INVERT, u8::from(invert))?;
+        }
+        Ok(())
+    }
+
+    // Temperature decimals on the display, 0 or 1; defaults to 1.
+    pub fn temp_decimals(&self) -> u8 {
+        let stored = self
+            .nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u8(KEY_TEMP_DECIMALS).ok().flatten());
+        stored.unwrap_or(1).min(1)
+    }
+
+    pub fn set_temp_decimals(&mut self, decimals: u8) -> Result<()> {
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u8(KEY_TEMP_DECIMALS, decimals.min(1))?;
+        }
+        Ok(())
+    }
+
+    pub fn co2_peak(&self) -> Option<u16> {
+        self.nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u16(KEY_CO2_PEAK).ok().flatten())
+    }
+
+    // None clears the stored peak.
+    pub fn set_co2_peak(&mut self, peak: Option<u16>) -> Result<()> {
+        if let Some(nvs) = self.nvs.as_mut() {
+            match peak {
+                Some(ppm) => nvs.set_u16(KEY_CO2_PEAK, ppm)?,
+                None => {
+                    nvs.remove(KEY_CO2_PEAK)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Abnormal resets (panic, watchdog, brownout) since the last reset_crashes.
+    pub fn crash_count(&self) -> u32 {
+        self.nvs
+            .as_ref()
+            .and_then(|nvs| nvs.get_u32(KEY_CRASH_COUNT).ok().flatten())
+            .unwrap_or(0)
+    }
+
+    // Add one crash and return the new total.
+    pub fn record_crash(&mut self) -> Result<u32> {
+        let count = self.crash_count().saturating_add(1);
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.set_u32(KEY_CRASH_COUNT, count)?;
+        }
+        Ok(count)
+    }
+
+    pub fn reset_crash_count(&mut self) -> Result<()> {
+        if let Some(nvs) = self.nvs.as_mut() {
+            nvs.remove(KEY_CRASH_COUNT)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_co2_history(&self) -> Vec<u16> {
+        let Some(nvs) = self.nvs.as_ref() else {
+            return Vec::new();
+        };
+        let mut buf = [0u8; 2 + 2 * CO2_HISTORY_MAX_POINTS];
+        let blob = match nvs.get_blob(KEY_CO2_HISTORY, &mut buf) {
+            Ok(Some(blob)) => blob,
+            Ok(None) => return Vec::new(),
+            Err(err) => {
+                warn!("CO2 history load failed: {:?}", err);
+                return Vec::new();
+            }
+        };
+        if blob.len() < 2 || blob[0] != CO2_HISTORY_VERSION {
+            warn!("CO2 history blob has stale version, discarding");
+            return Vec::new();
+        }
+        let count = blob[1] as usize;
+        if blob.len() != 2 + 2 * count {
+            warn!("CO2 history blob has bad length, discarding");
+            return Vec::new();
+        }
+        blob[2..]
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    }
+
+    pub fn save_co2_history(&mut self, points: &[u16]) -> Result<()> {
+        let Some(nvs) = self.nvs.as_mut() else {
+            return Ok(());
+        };
+        let skip = points.len().saturating_sub(CO2_HISTORY_MAX_POINTS);
+        let points = &points[skip..];
+        let mut blob = Vec::with_capacity(2 + 2 * points.len());
+        blob.push(CO2_HISTORY_VERSION);
+        blob.push(points.len() as u8);
+        for p in points {
+            blob.extend_from_slice(&p.to_le_bytes());
+        }
+        nvs.set_blob(KEY_CO2_HISTORY, &blob)?;
+        Ok(())
+    }
+
+    pub fn factory_reset(&mut self) -> Result<()> {
+        let Some(nvs) = self.nvs.as_mut() else {
+            return Ok(());
+        };
+        for key in ALL_KEYS {
+            info!("Factory reset: erasing NVS {}/{}", NVS_NAMESPACE, key);
+            nvs.remove(key)?;
+        }
+        Ok(())
+    }
+}
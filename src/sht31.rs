@@ -1,29 +1,55 @@
-use std::thread;
-use std::time::Duration;
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+use esp_idf_hal::delay::{FreeRtos, TickType, BLOCK};
+use esp_idf_hal::i2c::{I2cDriver, I2cError};
 
-use esp_idf_hal::i2c::I2cDriver;
+use crate::sensor_hal::sht31::{self as hal, Repeatability};
 
-#[derive(Debug, Clone, Copy)]
-pub struct ShtReading {
-    pub temperature_c: f32,
-    pub humidity_pct: f32,
-}
+pub type ShtReading = hal::Measurement;
+pub type ShtError = hal::Error<I2cError>;
 
-#[derive(Debug)]
-pub enum ShtError {
-    I2c(esp_idf_hal::sys::EspError),
-    Crc,
-}
+// Measurement repeatability: "low", "medium" or "high" (default). Lower is
+// faster and noisier.
+const REPEATABILITY: Repeatability = match option_env!("SHT31_REPEATABILITY") {
+    Some(v) if matches!(v.as_bytes(), b"low") => Repeatability::Low,
+    Some(v) if matches!(v.as_bytes(), b"medium") => Repeatability::Medium,
+    _ => Repeatability::High,
+};
+// Single-shot reads with clock stretching: the sensor holds SCL low until the
+// result is ready instead of the driver sleeping for the worst-case time.
+const CLOCK_STRETCH: bool = match option_env!("SHT31_CLOCK_STRETCH") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+// How long a stretched read may take before it counts as failed.
+const STRETCH_TIMEOUT_MS_DEFAULT: u64 = 50;
+const STRETCH_TIMEOUT_MS_ENV: Option<&str> = option_env!("SHT31_STRETCH_TIMEOUT_MS");
 
+// The measurement logic (retries, CRC, plausibility) lives in
+// sensor_hal::sht31, shared with the host tests; this adds the build-time
+// config and the bus timeout for clock-stretched reads.
 pub struct Sht31 {
-    addr: u8,
+    driver: hal::Sht31,
+    // Bus timeout in ticks for single-shot transactions (BLOCK without clock
+    // stretching).
+    read_timeout: u32,
 }
 
 impl Sht31 {
-    pub const DEFAULT_ADDR: u8 = 0x44;
+    pub const DEFAULT_ADDR: u8 = hal::DEFAULT_ADDR;
 
     pub fn new(addr: u8) -> Self {
-        Self { addr }
+        let read_timeout = if CLOCK_STRETCH {
+            let ms = STRETCH_TIMEOUT_MS_ENV
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(STRETCH_TIMEOUT_MS_DEFAULT);
+            TickType::new_millis(ms).ticks()
+        } else {
+            BLOCK
+        };
+        Self {
+            driver: hal::Sht31::new(addr).with_mode(REPEATABILITY, CLOCK_STRETCH),
+            read_timeout,
+        }
     }
 
     pub fn new_default() -> Self {
@@ -31,48 +57,42 @@ impl Sht31 {
     }
 
     pub fn read(&self, i2c: &mut I2cDriver<'_>) -> Result<ShtReading, ShtError> {
-        // Single-shot, high repeatability, no clock stretching.
-        // Command: 0x24 0x00 (datasheet).
-        let cmd = [0x24, 0x00];
-        i2c.write(self.addr, &cmd, esp_idf_hal::delay::BLOCK)
-            .map_err(ShtError::I2c)?;
-
-        // Measurement time up to ~15ms for high repeatability.
-        thread::sleep(Duration::from_millis(15));
-
-        let mut data = [0u8; 6];
-        i2c.read(self.addr, &mut data, esp_idf_hal::delay::BLOCK)
-            .map_err(ShtError::I2c)?;
+        let mut bus = TimedI2c {
+            i2c,
+            timeout: self.read_timeout,
+        };
+        self.driver.read(&mut bus, &mut FreeRtos)
+    }
 
-        if crc8(&data[0..2]) != data[2] || crc8(&data[3..5]) != data[5] {
-            return Err(ShtError::Crc);
-        }
+    // Periodic acquisition, 1 measurement per second.
+    pub fn start_periodic(&self, i2c: &mut I2cDriver<'_>) -> Result<(), ShtError> {
+        self.driver.start_periodic(i2c, &mut FreeRtos)
+    }
 
-        let raw_temp = u16::from_be_bytes([data[0], data[1]]);
-        let raw_rh = u16::from_be_bytes([data[3], data[4]]);
+    pub fn fetch_periodic(&self, i2c: &mut I2cDriver<'_>) -> Result<ShtReading, ShtError> {
+        self.driver.fetch_periodic(i2c, &mut FreeRtos)
+    }
+}
 
-        let temperature = -45.0 + 175.0 * (raw_temp as f32) / 65535.0;
-        let humidity = 100.0 * (raw_rh as f32) / 65535.0;
+// `I2cDriver` with a timeout of our choosing; its own embedded-hal impl always
+// blocks, which a stretched read held by a faulty sensor never returns from.
+struct TimedI2c<'a, 'd> {
+    i2c: &'a mut I2cDriver<'d>,
+    timeout: u32,
+}
 
-        Ok(ShtReading {
-            temperature_c: temperature,
-            humidity_pct: humidity,
-        })
-    }
+impl ErrorType for TimedI2c<'_, '_> {
+    type Error = I2cError;
 }
 
-fn crc8(bytes: &[u8]) -> u8 {
-    // CRC-8 with polynomial 0x31, init 0xFF (Sensirion standard).
-    let mut crc = 0xFFu8;
-    for byte in bytes {
-        crc ^= *byte;
-        for _ in 0..8 {
-            if (crc & 0x80) != 0 {
-                crc = (crc << 1) ^ 0x31;
-            } else {
-                crc <<= 1;
-            }
-        }
+impl I2c for TimedI2c<'_, '_> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.i2c
+            .transaction(address, operations, self.timeout)
+            .map_err(I2cError::other)
     }
-    crc
 }
@@ -1,5 +1,9 @@
-use anyhow::{bail, Result};
+use core::fmt;
+
+use anyhow::Result;
 use embedded_graphics::pixelcolor::{IntoStorage, Rgb565};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
 use esp_idf_hal::gpio::{AnyIOPin, PinDriver};
 use esp_idf_hal::ledc::{self, LedcDriver, LedcTimerDriver};
 use esp_idf_hal::ledc::config::TimerConfig as LedcTimerConfig;
@@ -10,13 +14,6 @@ use esp_idf_hal::spi::{SpiDeviceDriver, SpiDriver};
 use std::thread;
 use std::time::Duration;
 
-pub const LCD_CLK_GPIO: i32 = 1;   // SPI CLK (Clock)
-pub const LCD_MOSI_GPIO: i32 = 2;  // SPI MOSI (Master Out, Slave In)
-pub const LCD_CS_GPIO: i32 = 14;   // SPI CS (Chip Select)
-pub const LCD_DC_GPIO: i32 = 15;   // Data/Command control
-pub const LCD_RST_GPIO: i32 = 22;  // Reset
-pub const LCD_BL_GPIO: i32 = 23;   // Backlight PWM
-
 // Panel resolution (physical pixels).
 pub const LCD_W: usize = 172;
 pub const LCD_H: usize = 320;
@@ -27,20 +24,100 @@ pub const LCD_VIEW_H: usize = LCD_W;
 pub const LCD_X_GAP: u16 = 0;
 pub const LCD_Y_GAP: u16 = 34;
 
+// Time for a full 0..100% backlight ramp; smaller changes take proportionally
+// less. BACKLIGHT_RAMP_MS=0 makes every change immediate.
+const BACKLIGHT_RAMP_MS_DEFAULT: u64 = 200;
+const BACKLIGHT_RAMP_MS_ENV: Option<&str> = option_env!("BACKLIGHT_RAMP_MS");
+const BACKLIGHT_RAMP_STEP: Duration = Duration::from_millis(10);
+// Display inversion, sent explicitly at init (INVON 0x21 / INVOFF 0x20). Off is
+// the panel's reset state and what this module has always shown; LCD_INVERT=1
+// is for JD9853 panels whose colours look washed out or negative without it.
+const LCD_INVERT: bool = match option_env!("LCD_INVERT") {
+    Some(v) => matches!(v.as_bytes(), b"1" | b"true" | b"on"),
+    None => false,
+};
+
+// Driver errors, split so callers can tell a transient bus glitch (Spi/Gpio)
+// from a programming error (FrameSizeMismatch/WindowOutOfBounds).
+#[derive(Debug)]
+pub enum LcdError {
+    Spi(esp_idf_hal::sys::EspError),
+    Gpio(esp_idf_hal::sys::EspError),
+    Backlight(esp_idf_hal::sys::EspError),
+    FrameSizeMismatch { len: usize, expected: usize },
+    WindowOutOfBounds(Rectangle),
+}
+
+impl fmt::Display for LcdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spi(err) => write!(f, "LCD SPI error: {err}"),
+            Self::Gpio(err) => write!(f, "LCD GPIO error: {err}"),
+            Self::Backlight(err) => write!(f, "LCD backlight PWM error: {err}"),
+            Self::FrameSizeMismatch { len, expected } => {
+                write!(f, "frame size mismatch: {len}, expected {expected}")
+            }
+            Self::WindowOutOfBounds(area) => write!(
+                f,
+                "window {}x{} at ({}, {}) outside the panel",
+                area.size.width, area.size.height, area.top_left.x, area.top_left.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LcdError {}
+
 pub struct St7789<'a, T>
 where
     T: ledc::LedcTimer,
 {
-    spi_dev: SpiDeviceDriver<'a, SpiDriver<'a>>,
+    spi_dev: SpiDeviceDriver<'a, &'a SpiDriver<'a>>,
     dc: PinDriver<'a, AnyIOPin, esp_idf_hal::gpio::Output>,
     rst: PinDriver<'a, AnyIOPin, esp_idf_hal::gpio::Output>,
     bl_pwm: LedcDriver<'a>,
     bl_timer: LedcTimerDriver<'a, T>,
     x_gap: u16,
     y_gap: u16,
+    // Configured (rotated) resolution; frames and windows are checked against it.
     w: u16,
     h: u16,
     txbuf: Vec<u8>,
+    gamma: Option<GammaLut>,
+    backlight_ramp: Duration,
+    invert: bool,
+}
+
+// Per-channel gamma tables for RGB565 (5-bit R/B, 6-bit G).
+struct GammaLut {
+    r: [u8; 32],
+    g: [u8; 64],
+    b: [u8; 32],
+}
+
+impl GammaLut {
+    fn new(gamma: f32) -> Self {
+        fn table<const N: usize>(gamma: f32) -> [u8; N] {
+            let max = (N - 1) as f32;
+            let mut out = [0u8; N];
+            for (i, v) in out.iter_mut().enumerate() {
+                *v = ((i as f32 / max).powf(gamma) * max).round() as u8;
+            }
+            out
+        }
+        Self {
+            r: table::<32>(gamma),
+            g: table::<64>(gamma),
+            b: table::<32>(gamma),
+        }
+    }
+
+    fn apply(&self, raw: u16) -> u16 {
+        let r = self.r[(raw >> 11) as usize & 0x1F] as u16;
+        let g = self.g[(raw >> 5) as usize & 0x3F] as u16;
+        let b = self.b[raw as usize & 0x1F] as u16;
+        (r << 11) | (g << 5) | b
+    }
 }
 
 impl<'a, T> St7789<'a, T>
@@ -48,12 +125,12 @@ where
     T: ledc::LedcTimer,
 {
     pub fn new(
-        spi_dev: SpiDeviceDriver<'a, SpiDriver<'a>>,
+        spi_dev: SpiDeviceDriver<'a, &'a SpiDriver<'a>>,
         dc: PinDriver<'a, AnyIOPin, esp_idf_hal::gpio::Output>,
         rst: PinDriver<'a, AnyIOPin, esp_idf_hal::gpio::Output>,
         bl_pwm: LedcDriver<'a>,
         bl_timer: LedcTimerDriver<'a, T>,
-    ) -> Result<Self> {
+    ) -> Result<Self, LcdError> {
         let mut lcd = Self {
             spi_dev,
             dc,
@@ -64,7 +141,14 @@ where
             y_gap: LCD_Y_GAP,
             w: LCD_VIEW_W as u16,
             h: LCD_VIEW_H as u16,
-            txbuf: vec![0u8; LCD_W * LCD_H * 2],
+            txbuf: vec![0u8; LCD_VIEW_W * LCD_VIEW_H * 2],
+            gamma: None,
+            backlight_ramp: Duration::from_millis(
+                BACKLIGHT_RAMP_MS_ENV
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .unwrap_or(BACKLIGHT_RAMP_MS_DEFAULT),
+            ),
+            invert: LCD_INVERT,
         };
 
         // Hardware reset and panel init sequence for this ST7789 module.
@@ -73,35 +157,34 @@ where
         Ok(lcd)
     }
 
-    fn reset(&mut self) -> Result<()> {
-        // Reset pulse + backlight enable for this panel wiring.
+    // Reset pulse; the backlight stays off until the caller sets a brightness.
+    fn reset(&mut self) -> Result<(), LcdError> {
         self.set_backlight_pwm(0)?;
-        self.rst.set_low()?;
+        self.rst.set_low().map_err(LcdError::Gpio)?;
         thread::sleep(Duration::from_millis(10));
-        self.rst.set_high()?;
+        self.rst.set_high().map_err(LcdError::Gpio)?;
         thread::sleep(Duration::from_millis(120));
-        self.set_backlight_pwm(100)?;
         Ok(())
     }
 
-    fn write_cmd(&mut self, cmd: u8) -> Result<()> {
+    fn write_cmd(&mut self, cmd: u8) -> Result<(), LcdError> {
         // D/C low selects command phase.
-        self.dc.set_low()?;
-        self.spi_dev.write(&[cmd])?;
+        self.dc.set_low().map_err(LcdError::Gpio)?;
+        self.spi_dev.write(&[cmd]).map_err(LcdError::Spi)?;
         Ok(())
     }
 
-    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+    fn write_data(&mut self, data: &[u8]) -> Result<(), LcdError> {
         // D/C high selects data phase; chunk to limit SPI transaction size.
-        self.dc.set_high()?;
+        self.dc.set_high().map_err(LcdError::Gpio)?;
         const CHUNK: usize = 4096;
         for chunk in data.chunks(CHUNK) {
-            self.spi_dev.write(chunk)?;
+            self.spi_dev.write(chunk).map_err(LcdError::Spi)?;
         }
         Ok(())
     }
 
-    fn cmd(&mut self, cmd: u8, data: &[u8]) -> Result<()> {
+    fn cmd(&mut self, cmd: u8, data: &[u8]) -> Result<(), LcdError> {
         self.write_cmd(cmd)?;
         if !data.is_empty() {
             self.write_data(data)?;
@@ -109,7 +192,7 @@ where
         Ok(())
     }
 
-    fn init_sequence(&mut self) -> Result<()> {
+    fn init_sequence(&mut self) -> Result<(), LcdError> {
         // Vendor-provided init sequence tuned for this ST7789 module.
         self.cmd(0xDF, &[0x98, 0x53, 0x81])?;
         self.cmd(0xDE, &[0x00])?;
@@ -146,6 +229,7 @@ where
         // Rotate to landscape using MV+MX and enable BGR color order.
         self.cmd(0x36, &[0x68])?; // MADCTL
         self.cmd(0x3A, &[0x55])?; // RGB565
+        self.cmd(Self::inversion_cmd(self.invert), &[])?;
 
         self.cmd(0x11, &[])?; // sleep out
         thread::sleep(Duration::from_millis(120));
@@ -156,7 +240,7 @@ where
         Ok(())
     }
 
-    fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<()> {
+    fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<(), LcdError> {
         // Apply panel offsets before setting address window.
         let x0 = x0 + self.x_gap;
         let x1 = x1 + self.x_gap;
@@ -171,12 +255,38 @@ where
         Ok(())
     }
 
-    pub fn flush_full(&mut self, frame: &[Rgb565]) -> Result<()> {
-        if frame.len() != LCD_W * LCD_H {
-            bail!("frame size mismatch: {}", frame.len());
+    // Whole panel at the configured resolution.
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), Size::new(self.w as u32, self.h as u32))
+    }
+
+    pub fn flush_full(&mut self, frame: &[Rgb565]) -> Result<(), LcdError> {
+        self.flush_window(frame, self.bounds())
+    }
+
+    // Write a row-major `frame` covering just `area` (in view coordinates).
+    pub fn flush_window(&mut self, frame: &[Rgb565], area: Rectangle) -> Result<(), LcdError> {
+        let bounds = self.bounds();
+        let Some(bottom_right) = area.bottom_right() else {
+            return Err(LcdError::WindowOutOfBounds(area));
+        };
+        if !bounds.contains(area.top_left) || !bounds.contains(bottom_right) {
+            return Err(LcdError::WindowOutOfBounds(area));
+        }
+        let expected = (area.size.width * area.size.height) as usize;
+        if frame.len() != expected {
+            return Err(LcdError::FrameSizeMismatch {
+                len: frame.len(),
+                expected,
+            });
         }
 
-        self.set_window(0, 0, self.w - 1, self.h - 1)?;
+        self.set_window(
+            area.top_left.x as u16,
+            area.top_left.y as u16,
+            bottom_right.x as u16,
+            bottom_right.y as u16,
+        )?;
 
         let need = frame.len() * 2;
         if self.txbuf.len() != need {
@@ -185,36 +295,107 @@ where
 
         // Convert RGB565 to big-endian byte stream (panel expects BE).
         for (i, px) in frame.iter().copied().enumerate() {
-            let raw: u16 = px.into_storage();
+            let mut raw: u16 = px.into_storage();
+            if let Some(lut) = &self.gamma {
+                raw = lut.apply(raw);
+            }
             self.txbuf[2 * i] = (raw >> 8) as u8;
             self.txbuf[2 * i + 1] = (raw & 0xFF) as u8;
         }
 
         self.write_cmd(0x2C)?;
 
-        self.dc.set_high()?;
+        self.dc.set_high().map_err(LcdError::Gpio)?;
         const CHUNK: usize = 4096;
         for chunk in self.txbuf.chunks(CHUNK) {
-            self.spi_dev.write(chunk)?;
+            self.spi_dev.write(chunk).map_err(LcdError::Spi)?;
         }
 
         Ok(())
     }
 
-    pub fn set_brightness(&mut self, percent: u8) -> Result<()> {
-        self.set_backlight_pwm(percent)?;
+    // Raw ESP-IDF handle of the panel's SPI device (used by the panic screen).
+    pub fn spi_handle(&self) -> esp_idf_hal::sys::spi_device_handle_t {
+        self.spi_dev.device()
+    }
+
+    // Switch display inversion at runtime; kept across re-inits.
+    pub fn set_invert(&mut self, invert: bool) -> Result<(), LcdError> {
+        self.cmd(Self::inversion_cmd(invert), &[])?;
+        self.invert = invert;
         Ok(())
     }
 
-    fn set_backlight_pwm(&mut self, percent: u8) -> Result<()> {
-        let pct = percent.min(100) as u32;
+    pub fn inverted(&self) -> bool {
+        self.invert
+    }
+
+    fn inversion_cmd(invert: bool) -> u8 {
+        if invert {
+            0x21 // INVON
+        } else {
+            0x20 // INVOFF
+        }
+    }
+
+    // Gamma < 1.0 lifts dark channels to keep colors saturated at low backlight;
+    // 1.0 disables correction.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = if (gamma - 1.0).abs() < f32::EPSILON || gamma <= 0.0 {
+            None
+        } else {
+            Some(GammaLut::new(gamma))
+        };
+    }
+
+    // Fades the backlight to `percent` (blocking for up to BACKLIGHT_RAMP_MS) so
+    // wake-ups do not flash the panel from dark to full.
+    pub fn set_brightness(&mut self, percent: u8) -> Result<(), LcdError> {
         let max = self.bl_pwm.get_max_duty();
-        let duty = max * pct / 100;
-        self.bl_pwm.set_duty(duty)?;
+        let from = self.bl_pwm.get_duty().min(max);
+        let to = Self::percent_to_duty(max, percent);
+        let span = from.abs_diff(to);
+        if span == 0 || max == 0 {
+            return self.set_brightness_immediate(percent);
+        }
+        let ramp = self.backlight_ramp.mul_f32(span as f32 / max as f32);
+        let steps = (ramp.as_millis() / BACKLIGHT_RAMP_STEP.as_millis()) as u32;
+        for step in 1..steps {
+            let duty = if to > from {
+                from + span * step / steps
+            } else {
+                from - span * step / steps
+            };
+            self.bl_pwm.set_duty(duty).map_err(LcdError::Backlight)?;
+            thread::sleep(BACKLIGHT_RAMP_STEP);
+        }
+        self.set_brightness_immediate(percent)
+    }
+
+    // Display off and sleep-in; the panel keeps drawing nothing until re-init.
+    pub fn sleep(&mut self) -> Result<(), LcdError> {
+        self.cmd(0x28, &[])?;
+        self.cmd(0x10, &[])?;
+        Ok(())
+    }
+
+    // Jumps straight to `percent`, e.g. for switching the backlight fully off.
+    pub fn set_brightness_immediate(&mut self, percent: u8) -> Result<(), LcdError> {
+        self.set_backlight_pwm(percent)?;
+        Ok(())
+    }
+
+    fn percent_to_duty(max: u32, percent: u8) -> u32 {
+        max * percent.min(100) as u32 / 100
+    }
+
+    fn set_backlight_pwm(&mut self, percent: u8) -> Result<(), LcdError> {
+        let duty = Self::percent_to_duty(self.bl_pwm.get_max_duty(), percent);
+        self.bl_pwm.set_duty(duty).map_err(LcdError::Backlight)?;
         Ok(())
     }
 
-    fn set_display_brightness(&mut self, percent: u8) -> Result<()> {
+    fn set_display_brightness(&mut self, percent: u8) -> Result<(), LcdError> {
         // Enable brightness control (BCTRL) and backlight (BL) in WRCTRLD.
         self.cmd(0x53, &[0x24])?;
         let value = ((percent.min(100) as u16) * 255 / 100) as u8;
@@ -223,36 +404,42 @@ where
     }
 }
 
-pub fn init_lcd(
+// SPI2 bus (write-only) for the LCD and the optional e-paper panel, each on
+// its own CS; lives for the rest of the program.
+pub fn init_spi_bus(
     spi: impl Peripheral<P = esp_idf_hal::spi::SPI2> + 'static,
-    ledc: esp_idf_hal::ledc::LEDC,
     sclk: AnyIOPin,
     mosi: AnyIOPin,
-    cs: AnyIOPin,
-    dc: AnyIOPin,
-    rst: AnyIOPin,
-    bl: AnyIOPin,
-) -> Result<St7789<'static, ledc::TIMER0>> {
-    let spi_driver_cfg = SpiDriverConfig::new();
-    let spi_dev_cfg = SpiDeviceConfig::new().baudrate(40.MHz().into());
-
+) -> Result<&'static SpiDriver<'static>> {
     let spi_driver = SpiDriver::new(
         spi,
         sclk,
         mosi,
         Option::<AnyIOPin>::None,
-        &spi_driver_cfg,
+        &SpiDriverConfig::new(),
     )?;
+    Ok(Box::leak(Box::new(spi_driver)))
+}
 
-    let spi_dev = SpiDeviceDriver::new(spi_driver, Some(cs), &spi_dev_cfg)?;
+pub fn init_lcd(
+    spi_bus: &'static SpiDriver<'static>,
+    bl_timer: ledc::TIMER0,
+    bl_channel: ledc::CHANNEL0,
+    cs: AnyIOPin,
+    dc: AnyIOPin,
+    rst: AnyIOPin,
+    bl: AnyIOPin,
+) -> Result<St7789<'static, ledc::TIMER0>> {
+    let spi_dev_cfg = SpiDeviceConfig::new().baudrate(40.MHz().into());
+    let spi_dev = SpiDeviceDriver::new(spi_bus, Some(cs), &spi_dev_cfg)?;
 
     let dc = PinDriver::output(dc)?;
     let rst = PinDriver::output(rst)?;
     let bl_timer = LedcTimerDriver::new(
-        ledc.timer0,
+        bl_timer,
         &LedcTimerConfig::default().frequency(5.kHz().into()),
     )?;
-    let bl_pwm = LedcDriver::new(ledc.channel0, &bl_timer, bl)?;
+    let bl_pwm = LedcDriver::new(bl_channel, &bl_timer, bl)?;
 
     let lcd = St7789::new(spi_dev, dc, rst, bl_pwm, bl_timer)?;
     Ok(lcd)
@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+use crate::schedule::local_day;
+
+// Mold risk: relative humidity above MOLD_RISK_RH_PCT without a break for
+// MOLD_RISK_DURATION.
+pub const MOLD_RISK_RH_PCT: f32 = 70.0;
+pub const MOLD_RISK_DURATION: Duration = Duration::from_secs(2 * 60 * 60);
+
+// Today's temperature/humidity extremes. Resets at local midnight; before SNTP
+// has synced the day is unknown, so the first sync also starts a fresh day.
+#[derive(Debug, Default)]
+pub struct TempHumStats {
+    day: Option<i32>,
+    temp: Option<(f32, f32)>,
+    humidity: Option<(f32, f32)>,
+}
+
+impl TempHumStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, temperature_c: f32, humidity_pct: f32) {
+        let today = local_day();
+        if today != self.day {
+            *self = Self {
+                day: today,
+                ..Self::default()
+            };
+        }
+        self.temp = Some(extend(self.temp, temperature_c));
+        self.humidity = Some(extend(self.humidity, humidity_pct));
+    }
+
+    // (min, max) in °C.
+    pub fn temp_range(&self) -> Option<(f32, f32)> {
+        self.temp
+    }
+
+    // (min, max) in %RH.
+    pub fn humidity_range(&self) -> Option<(f32, f32)> {
+        self.humidity
+    }
+}
+
+// Tracks how long humidity has stayed above MOLD_RISK_RH_PCT. Uses the
+// monotonic clock, so it works before SNTP has synced; any reading at or
+// below the threshold restarts the count.
+#[derive(Debug, Default)]
+pub struct MoldRisk {
+    above_since: Option<Instant>,
+}
+
+impl MoldRisk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns true when the risk flag flipped.
+    pub fn update(&mut self, humidity_pct: f32) -> bool {
+        let was_active = self.active();
+        if humidity_pct > MOLD_RISK_RH_PCT {
+            self.above_since.get_or_insert_with(Instant::now);
+        } else {
+            self.above_since = None;
+        }
+        self.active() != was_active
+    }
+
+    pub fn active(&self) -> bool {
+        self.above_since
+            .is_some_and(|since| since.elapsed() >= MOLD_RISK_DURATION)
+    }
+}
+
+// Room occupancy inferred from CO2, for meeting rooms without a PIR. People
+// breathing make CO2 climb, then plateau at a level set by the ventilation, so
+// either a sustained rise or a high level that is not falling counts as
+// evidence; the room reads empty once there has been none for OCCUPANCY_HOLD.
+pub const OCCUPANCY_RISE_PPM_PER_MIN: f32 = 8.0;
+pub const OCCUPANCY_LEVEL_PPM: u16 = 800;
+// A slope below this is a room airing out, even above OCCUPANCY_LEVEL_PPM.
+pub const OCCUPANCY_FALL_PPM_PER_MIN: f32 = -5.0;
+pub const OCCUPANCY_HOLD: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Default)]
+pub struct Occupancy {
+    occupied: bool,
+    last_evidence: Option<Instant>,
+}
+
+impl Occupancy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `slope` is the recent CO2 slope (history::Co2History::slope_ppm_per_min),
+    // None until there is enough data. Returns true when the state flipped.
+    pub fn update(&mut self, ppm: u16, slope: Option<f32>) -> bool {
+        let Some(slope) = slope else {
+            return false;
+        };
+        let now = Instant::now();
+        let rising = slope >= OCCUPANCY_RISE_PPM_PER_MIN;
+        let high = ppm >= OCCUPANCY_LEVEL_PPM && slope > OCCUPANCY_FALL_PPM_PER_MIN;
+        if rising || high {
+            self.last_evidence = Some(now);
+        }
+        let occupied = self
+            .last_evidence
+            .is_some_and(|at| now.duration_since(at) < OCCUPANCY_HOLD);
+        let flipped = occupied != self.occupied;
+        self.occupied = occupied;
+        flipped
+    }
+
+    pub fn occupied(&self) -> bool {
+        self.occupied
+    }
+}
+
+// Highest CO2 reading since the last explicit reset. Survives reboots: the
+// main loop seeds it from NVS and saves it back when it grows.
+#[derive(Debug, Default)]
+pub struct Co2Peak {
+    peak: Option<u16>,
+}
+
+impl Co2Peak {
+    pub fn with_peak(peak: Option<u16>) -> Self {
+        Self { peak }
+    }
+
+    // Returns true when `ppm` is a new peak.
+    pub fn update(&mut self, ppm: u16) -> bool {
+        if self.peak.is_some_and(|peak| peak >= ppm) {
+            return false;
+        }
+        self.peak = Some(ppm);
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = None;
+    }
+
+    pub fn peak(&self) -> Option<u16> {
+        self.peak
+    }
+}
+
+fn extend(range: Option<(f32, f32)>, value: f32) -> (f32, f32) {
+    match range {
+        Some((min, max)) => (min.min(value), max.max(value)),
+        None => (value, value),
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use esp_idf_hal::gpio::OutputPin;
+use esp_idf_hal::ledc::config::TimerConfig as LedcTimerConfig;
+use esp_idf_hal::ledc::{LedcDriver, LedcTimerDriver, CHANNEL1, CHANNEL2, CHANNEL3, TIMER1};
+use esp_idf_hal::peripheral::Peripheral;
+use esp_idf_hal::prelude::*;
+use esp_idf_hal::rmt::config::TransmitConfig;
+use esp_idf_hal::rmt::{FixedLengthSignal, PinState, Pulse, RmtChannel, TxRmtDriver};
+
+use crate::display::AirQuality;
+
+// Scale applied to every channel; a WS2812 at full power is blinding indoors.
+const STATUS_LED_BRIGHTNESS: f32 = 0.15;
+
+// LED colour per CO2 band, matching the CO2 card status colours.
+fn quality_rgb(quality: AirQuality) -> (u8, u8, u8) {
+    match quality {
+        AirQuality::Good => (0, 255, 0),
+        AirQuality::Fair => (255, 255, 0),
+        AirQuality::Poor => (255, 96, 0),
+        AirQuality::Bad => (255, 0, 0),
+    }
+}
+
+enum Output {
+    Ws2812 {
+        tx: TxRmtDriver<'static>,
+    },
+    Rgb {
+        r: LedcDriver<'static>,
+        g: LedcDriver<'static>,
+        b: LedcDriver<'static>,
+        _timer: LedcTimerDriver<'static, TIMER1>,
+    },
+}
+
+pub struct StatusLed {
+    output: Output,
+    // Last colour written; only changes are sent to the LED.
+    current: Option<(u8, u8, u8)>,
+}
+
+impl StatusLed {
+    pub fn ws2812(
+        channel: impl Peripheral<P = impl RmtChannel> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+    ) -> Result<Self> {
+        // 80 MHz RMT clock (divider 1) gives enough resolution for WS2812 timing.
+        let tx = TxRmtDriver::new(channel, pin, &TransmitConfig::new().clock_divider(1))?;
+        Ok(Self {
+            output: Output::Ws2812 { tx },
+            current: None,
+        })
+    }
+
+    // LEDC timer 1 / channels 1-3; timer 0 / channel 0 drive the LCD backlight.
+    pub fn rgb(
+        timer: TIMER1,
+        channels: (CHANNEL1, CHANNEL2, CHANNEL3),
+        pins: (
+            impl Peripheral<P = impl OutputPin> + 'static,
+            impl Peripheral<P = impl OutputPin> + 'static,
+            impl Peripheral<P = impl OutputPin> + 'static,
+        ),
+    ) -> Result<Self> {
+        let timer =
+            LedcTimerDriver::new(timer, &LedcTimerConfig::default().frequency(5.kHz().into()))?;
+        let r = LedcDriver::new(channels.0, &timer, pins.0)?;
+        let g = LedcDriver::new(channels.1, &timer, pins.1)?;
+        let b = LedcDriver::new(channels.2, &timer, pins.2)?;
+        Ok(Self {
+            output: Output::Rgb {
+                r,
+                g,
+                b,
+                _timer: timer,
+            },
+            current: None,
+        })
+    }
+
+    // Show the colour for `quality`, or switch off when there is no valid reading.
+    pub fn show(&mut self, quality: Option<AirQuality>) -> Result<()> {
+        let scale = |c: u8| (c as f32 * STATUS_LED_BRIGHTNESS).round() as u8;
+        let rgb = quality
+            .map(quality_rgb)
+            .map(|(r, g, b)| (scale(r), scale(g), scale(b)))
+            .unwrap_or((0, 0, 0));
+        if self.current == Some(rgb) {
+            return Ok(());
+        }
+        match &mut self.output {
+            Output::Ws2812 { tx } => write_ws2812(tx, rgb)?,
+            Output::Rgb { r, g, b, .. } => {
+                for (driver, value) in [(r, rgb.0), (g, rgb.1), (b, rgb.2)] {
+                    let duty = driver.get_max_duty() * value as u32 / 255;
+                    driver.set_duty(duty)?;
+                }
+            }
+        }
+        self.current = Some(rgb);
+        Ok(())
+    }
+}
+
+fn write_ws2812(tx: &mut TxRmtDriver<'static>, (r, g, b): (u8, u8, u8)) -> Result<()> {
+    let ticks_hz = tx.counter_clock()?;
+    let pulse = |state, ns| Pulse::new_with_duration(ticks_hz, state, &Duration::from_nanos(ns));
+    let zero = (pulse(PinState::High, 350)?, pulse(PinState::Low, 800)?);
+    let one = (pulse(PinState::High, 700)?, pulse(PinState::Low, 600)?);
+
+    // WS2812 expects GRB, most significant bit first.
+    let grb = ((g as u32) << 16) | ((r as u32) << 8) | b as u32;
+    let mut signal = FixedLengthSignal::<24>::new();
+    for i in 0..24 {
+        let bit = (grb >> (23 - i)) & 1 != 0;
+        signal.set(i, if bit { &one } else { &zero })?;
+    }
+    tx.start_blocking(&signal)?;
+    Ok(())
+}
@@ -0,0 +1,250 @@
+// Telemetry snapshot published over MQTT and its JSON encoding, with per-field
+// age tracking so a sensor that stops updating is published as null instead of
+// its last value. Free of esp-idf, like sensor_proto.rs, so tools/driver_tests
+// can include it.
+
+use std::time::{Duration, Instant};
+
+use crate::options::TempUnit;
+
+// Optional HA entities, all on by default (set e.g. MQTT_ENTITY_BATTERY=0 on
+// mains-powered units). A disabled entity's retained discovery config is
+// cleared and its telemetry fields are left out.
+pub const ENTITY_CO2: bool = match option_env!("MQTT_ENTITY_CO2") {
+    Some(v) => !matches!(v.as_bytes(), b"0" | b"false" | b"off"),
+    None => true,
+};
+pub const ENTITY_HUMIDITY: bool = match option_env!("MQTT_ENTITY_HUMIDITY") {
+    Some(v) => !matches!(v.as_bytes(), b"0" | b"false" | b"off"),
+    None => true,
+};
+pub const ENTITY_BATTERY: bool = match option_env!("MQTT_ENTITY_BATTERY") {
+    Some(v) => !matches!(v.as_bytes(), b"0" | b"false" | b"off"),
+    None => true,
+};
+
+#[derive(Default, Debug, Clone)]
+pub struct Telemetry {
+    // Temperature-compensated when enabled, otherwise equal to `co2_raw_ppm`.
+    pub co2_ppm: Option<u16>,
+    pub co2_raw_ppm: Option<u16>,
+    // Always °C; published as `temp_c` and, in the client's unit, as `temp`.
+    pub temp_c: Option<f32>,
+    pub humidity_pct: Option<u8>,
+    pub battery_v: Option<f32>,
+    // State of charge; only with a fuel gauge fitted.
+    pub battery_pct: Option<u8>,
+    pub uptime_s: Option<u32>,
+    // Touch controller answering (see touch::TouchHealth), diagnostic only.
+    pub touch_ok: Option<bool>,
+    // Active temperature/humidity sensor ("sht31"/"scd4x"), diagnostic only.
+    pub temp_hum_source: Option<&'static str>,
+    // Today's (min, max), reset at local midnight.
+    pub temp_range: Option<(f32, f32)>,
+    pub humidity_range: Option<(f32, f32)>,
+    // Sustained high humidity (see stats::MoldRisk); None without a humidity source.
+    pub mold_risk: Option<bool>,
+    // Highest CO2 since the last reset_peak command (see stats::Co2Peak).
+    pub co2_peak_ppm: Option<u16>,
+    // Room looks occupied from the CO2 trend (see stats::Occupancy); None
+    // without a CO2 sensor.
+    pub occupied: Option<bool>,
+    // When each TelemetryField was last set, and how long it stays valid after
+    // that (None = never goes stale).
+    updated_at: [Option<Instant>; TelemetryField::COUNT],
+    max_age: [Option<Duration>; TelemetryField::COUNT],
+}
+
+// Sensor-backed Telemetry fields with age tracking. Co2 covers both
+// `co2_ppm` and `co2_raw_ppm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryField {
+    Co2,
+    Temp,
+    Humidity,
+    Battery,
+}
+
+impl TelemetryField {
+    const COUNT: usize = 4;
+}
+
+impl Telemetry {
+    // A field not set again within `max_age` is published as null.
+    pub fn set_max_age(&mut self, field: TelemetryField, max_age: Duration) {
+        self.max_age[field as usize] = Some(max_age);
+    }
+
+    pub fn set_co2(&mut self, ppm: u16, raw_ppm: u16) {
+        self.co2_ppm = Some(ppm);
+        self.co2_raw_ppm = Some(raw_ppm);
+        self.touch(TelemetryField::Co2);
+    }
+
+    pub fn set_temp(&mut self, temp_c: f32) {
+        self.temp_c = Some(temp_c);
+        self.touch(TelemetryField::Temp);
+    }
+
+    pub fn set_humidity(&mut self, humidity_pct: u8) {
+        self.humidity_pct = Some(humidity_pct);
+        self.touch(TelemetryField::Humidity);
+    }
+
+    pub fn set_battery(&mut self, battery_v: f32, battery_pct: Option<u8>) {
+        self.battery_v = Some(battery_v);
+        self.battery_pct = battery_pct;
+        self.touch(TelemetryField::Battery);
+    }
+
+    // Publish null for `field` until it is set again (failed read, sensor
+    // switched, ...).
+    pub fn mark_stale(&mut self, field: TelemetryField) {
+        match field {
+            TelemetryField::Co2 => {
+                self.co2_ppm = None;
+                self.co2_raw_ppm = None;
+            }
+            TelemetryField::Temp => self.temp_c = None,
+            TelemetryField::Humidity => self.humidity_pct = None,
+            TelemetryField::Battery => {
+                self.battery_v = None;
+                self.battery_pct = None;
+            }
+        }
+        self.updated_at[field as usize] = None;
+    }
+
+    // Copy with every field older than its max age marked stale.
+    pub fn fresh(&self, now: Instant) -> Self {
+        let mut fresh = self.clone();
+        for field in [
+            TelemetryField::Co2,
+            TelemetryField::Temp,
+            TelemetryField::Humidity,
+            TelemetryField::Battery,
+        ] {
+            let i = field as usize;
+            if let (Some(at), Some(max_age)) = (self.updated_at[i], self.max_age[i]) {
+                if now.saturating_duration_since(at) > max_age {
+                    fresh.mark_stale(field);
+                }
+            }
+        }
+        fresh
+    }
+
+    fn touch(&mut self, field: TelemetryField) {
+        self.updated_at[field as usize] = Some(Instant::now());
+    }
+
+    pub fn has_any(&self) -> bool {
+        self.co2_ppm.is_some()
+            || self.temp_c.is_some()
+            || self.humidity_pct.is_some()
+            || self.battery_v.is_some()
+    }
+}
+
+pub fn telemetry_payload(t: &Telemetry, unit: TempUnit) -> String {
+    let fields: Vec<String> = telemetry_fields(t, unit)
+        .into_iter()
+        .map(|(key, value)| format!(r#""{key}":{value}"#))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+// (key, JSON-encoded value) for every telemetry field, in publish order.
+// `temp_c`, `temp_min` and `temp_max` are always °C; `temp` is the same
+// reading in `unit`, for the HA entity.
+pub fn telemetry_fields(t: &Telemetry, unit: TempUnit) -> Vec<(&'static str, String)> {
+    let co2 = t
+        .co2_ppm
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let co2_raw = t
+        .co2_raw_ppm
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let temp_c = t
+        .temp_c
+        .map(|v| format!("{:.1}", v))
+        .unwrap_or_else(|| "null".to_string());
+    let temp = t
+        .temp_c
+        .map(|v| format!("{:.1}", unit.convert(v)))
+        .unwrap_or_else(|| "null".to_string());
+    let hum = t
+        .humidity_pct
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let bat = t
+        .battery_v
+        .map(|v| format!("{:.2}", v))
+        .unwrap_or_else(|| "null".to_string());
+    let bat_pct = t
+        .battery_pct
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let uptime = t
+        .uptime_s
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let range = |range: Option<(f32, f32)>, decimals: usize| match range {
+        Some((min, max)) => (format!("{min:.decimals$}"), format!("{max:.decimals$}")),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    let (temp_min, temp_max) = range(t.temp_range, 1);
+    let (hum_min, hum_max) = range(t.humidity_range, 0);
+    let th_source = t
+        .temp_hum_source
+        .map(|v| format!("\"{v}\""))
+        .unwrap_or_else(|| "null".to_string());
+    let mold_risk = t
+        .mold_risk
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let co2_peak = t
+        .co2_peak_ppm
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let occupied = t
+        .occupied
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    let touch_ok = t
+        .touch_ok
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+
+    let mut fields = vec![
+        ("co2_ppm", co2),
+        ("co2_raw_ppm", co2_raw),
+        ("temp_c", temp_c),
+        ("temp", temp),
+        ("humidity_pct", hum),
+        ("battery_v", bat),
+        ("battery_pct", bat_pct),
+        ("uptime_s", uptime),
+        ("temp_hum_source", th_source),
+        ("temp_min", temp_min),
+        ("temp_max", temp_max),
+        ("hum_min", hum_min),
+        ("hum_max", hum_max),
+        ("mold_risk", mold_risk),
+        ("co2_peak_ppm", co2_peak),
+        ("occupied", occupied),
+        ("touch_ok", touch_ok),
+    ];
+    fields.retain(|(key, _)| field_enabled(key));
+    fields
+}
+
+fn field_enabled(key: &str) -> bool {
+    match key {
+        "co2_ppm" | "co2_raw_ppm" | "co2_peak_ppm" | "occupied" => ENTITY_CO2,
+        "humidity_pct" | "hum_min" | "hum_max" => ENTITY_HUMIDITY,
+        "battery_v" | "battery_pct" => ENTITY_BATTERY,
+        _ => true,
+    }
+}
@@ -0,0 +1,148 @@
+// Compact telemetry encodings for metered links (`MQTT_COMPACT_FORMAT=csv` or
+// `msgpack`), published on `<prefix>/status/compact` next to (or, with
+// `MQTT_COMPACT_ONLY=1`, instead of) the JSON status.
+//
+// Both carry the same fixed fields in this order; a missing value is an empty
+// CSV field or a MessagePack nil:
+//
+//   0  schema version     always SCHEMA_VERSION (bumped on any change below)
+//   1  co2_ppm            u16
+//   2  co2_raw_ppm        u16
+//   3  temperature        CSV: "21.4"; msgpack: int, tenths of a degree;
+//                         always °C (schema 1 followed the display unit)
+//   4  humidity_pct       u8
+//   5  battery            CSV: volts "3.92"; msgpack: uint, millivolts
+//   6  battery_pct        u8
+//   7  uptime_s           u32
+//   8  co2_peak_ppm       u16
+//   9  mold_risk          CSV: 0/1; msgpack: bool
+//
+// CSV is one line without a header, e.g. "2,842,851,21.4,48,3.92,,3605,1210,0"
+// (~40 bytes against ~300 for the JSON). MessagePack is a 10-element fixarray
+// (~25 bytes). Daily min/max, occupancy, touch_ok and the temp/humidity
+// source are JSON-only.
+
+use crate::telemetry::Telemetry;
+
+pub const SCHEMA_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactFormat {
+    Csv,
+    MsgPack,
+}
+
+pub const COMPACT_FORMAT: Option<CompactFormat> = match option_env!("MQTT_COMPACT_FORMAT") {
+    Some(v) => match v.as_bytes() {
+        b"csv" => Some(CompactFormat::Csv),
+        b"msgpack" => Some(CompactFormat::MsgPack),
+        _ => None,
+    },
+    None => None,
+};
+
+pub fn encode(format: CompactFormat, t: &Telemetry) -> Vec<u8> {
+    match format {
+        CompactFormat::Csv => encode_csv(t).into_bytes(),
+        CompactFormat::MsgPack => encode_msgpack(t),
+    }
+}
+
+fn encode_csv(t: &Telemetry) -> String {
+    fn field<T: ToString>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+    let fields = [
+        SCHEMA_VERSION.to_string(),
+        field(t.co2_ppm),
+        field(t.co2_raw_ppm),
+        field(t.temp_c.map(|v| format!("{:.1}", v))),
+        field(t.humidity_pct),
+        field(t.battery_v.map(|v| format!("{:.2}", v))),
+        field(t.battery_pct),
+        field(t.uptime_s),
+        field(t.co2_peak_ppm),
+        field(t.mold_risk.map(u8::from)),
+    ];
+    fields.join(",")
+}
+
+fn encode_msgpack(t: &Telemetry) -> Vec<u8> {
+    let mut out = MsgPack(Vec::with_capacity(32));
+    out.array_header(10);
+    out.uint(SCHEMA_VERSION.into());
+    out.opt_uint(t.co2_ppm.map(u32::from));
+    out.opt_uint(t.co2_raw_ppm.map(u32::from));
+    match t.temp_c {
+        Some(v) => out.int((v * 10.0).round() as i32),
+        None => out.nil(),
+    }
+    out.opt_uint(t.humidity_pct.map(u32::from));
+    out.opt_uint(t.battery_v.map(|v| (v * 1000.0).round().max(0.0) as u32));
+    out.opt_uint(t.battery_pct.map(u32::from));
+    out.opt_uint(t.uptime_s);
+    out.opt_uint(t.co2_peak_ppm.map(u32::from));
+    match t.mold_risk {
+        Some(v) => out.bool(v),
+        None => out.nil(),
+    }
+    out.0
+}
+
+// The handful of MessagePack types the schema needs, always in their
+// smallest encoding.
+struct MsgPack(Vec<u8>);
+
+impl MsgPack {
+    // fixarray; the schema stays well under 16 elements.
+    fn array_header(&mut self, len: u8) {
+        debug_assert!(len < 16);
+        self.0.push(0x90 | len);
+    }
+
+    fn nil(&mut self) {
+        self.0.push(0xC0);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.0.push(if value { 0xC3 } else { 0xC2 });
+    }
+
+    fn uint(&mut self, value: u32) {
+        if value < 0x80 {
+            self.0.push(value as u8);
+        } else if value <= u8::MAX as u32 {
+            self.0.extend_from_slice(&[0xCC, value as u8]);
+        } else if value <= u16::MAX as u32 {
+            self.0.push(0xCD);
+            self.0.extend_from_slice(&(value as u16).to_be_bytes());
+        } else {
+            self.0.push(0xCE);
+            self.0.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn opt_uint(&mut self, value: Option<u32>) {
+        match value {
+            Some(v) => self.uint(v),
+            None => self.nil(),
+        }
+    }
+
+    fn int(&mut self, value: i32) {
+        if value >= 0 {
+            self.uint(value as u32);
+        } else if value >= -32 {
+            // negative fixint
+            self.0.push(value as i8 as u8);
+        } else if value >= i8::MIN as i32 {
+            self.0.extend_from_slice(&[0xD0, value as i8 as u8]);
+        } else if value >= i16::MIN as i32 {
+            self.0.push(0xD1);
+            self.0.extend_from_slice(&(value as i16).to_be_bytes());
+        } else {
+            self.0.push(0xD2);
+            self.0.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
@@ -1,38 +1,102 @@
 use anyhow::Result;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use embedded_graphics::geometry::Point;
 use esp_idf_hal::gpio::{InputPin, OutputPin};
 use esp_idf_hal::i2c::{I2cConfig, I2cDriver};
 use esp_idf_hal::prelude::*;
 use esp_idf_hal::i2c::I2c;
 use esp_idf_sys as sys;
-use log::{error, info};
+use log::{debug, error, info, warn};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use crate::i2c_retry::retry_corrupt;
+use crate::pins::{I2C_SCL_GPIO, I2C_SDA_GPIO, TP_INT_GPIO, TP_RST_GPIO};
+use crate::st7789::{LCD_H, LCD_W};
 
 type HalResult<T> = core::result::Result<T, esp_idf_hal::sys::EspError>;
 
 pub const TP_ADDR: u8 = 0x63;
-pub const TP_SDA_GPIO: i32 = 18;
-pub const TP_SCL_GPIO: i32 = 19;
-pub const TP_RST_GPIO: i32 = 20;
-pub const TP_INT_GPIO: i32 = 21;
+pub const TP_MAX_POINTS: usize = 2;
+// Report layout @0x02: [count, then 6 bytes per point: XH, XL, YH, YL, weight, misc].
+const TP_POINT_STRIDE: usize = 6;
+const TP_REPORT_LEN: usize = 1 + TP_MAX_POINTS * TP_POINT_STRIDE;
+// Shared touch/SHT31 bus speed. The AXS5106L is unreliable above 100 kHz.
+const I2C_BAUD_KHZ_DEFAULT: u32 = 100;
+const I2C_BAUD_KHZ_ENV: Option<&str> = option_env!("I2C_BAUD_KHZ");
+// Coordinate width in the point report: "12" (default) or "10", see `TouchFormat`.
+const TOUCH_FORMAT: TouchFormat = match option_env!("TOUCH_REPORT_FORMAT") {
+    Some(v) if matches!(v.as_bytes(), b"10") => TouchFormat::Bits10,
+    _ => TouchFormat::Bits12,
+};
+
+// Consecutive failed reads before touch is treated as gone.
+const TOUCH_FAIL_LIMIT: u8 = 5;
+// While unavailable, how often the controller is probed again (e.g. a loose
+// FPC that reseats itself); everything in between skips the bus.
+const TOUCH_REPROBE_INTERVAL: Duration = Duration::from_secs(60);
 
 static TOUCH_PENDING: AtomicBool = AtomicBool::new(false);
+// Point count of the last report, so the raw bytes are logged once per change
+// rather than on every poll while a finger is down.
+static LAST_REPORT_COUNT: AtomicU8 = AtomicU8::new(0);
+
+// How AXS5106L firmware variants pack a point's XH/XL/YH/YL bytes. Both keep
+// the event flag in bits 7:6 of XH and the touch ID in bits 7:4 of YH; they
+// differ in how many high coordinate bits follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchFormat {
+    // XH/YH bits 3:0 are coordinate bits 11:8 (the common firmware).
+    Bits12,
+    // XH/YH bits 1:0 are coordinate bits 9:8; bits 5:2 carry other flags.
+    Bits10,
+}
+
+impl TouchFormat {
+    fn high_mask(self) -> u8 {
+        match self {
+            Self::Bits12 => 0x0F,
+            Self::Bits10 => 0x03,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchPoint {
+    // Native panel coordinates (172x320).
+    pub x: u16,
+    pub y: u16,
+    // XH bits 7:6: 0 = press, 1 = lift, 2 = contact.
+    pub event: u8,
+    // YH bits 7:4.
+    pub id: u8,
+}
+
+// Decode one 6-byte point record [XH, XL, YH, YL, weight, misc].
+pub fn parse_point(p: &[u8], format: TouchFormat) -> TouchPoint {
+    let mask = format.high_mask();
+    TouchPoint {
+        x: (((p[0] & mask) as u16) << 8) | p[1] as u16,
+        y: (((p[2] & mask) as u16) << 8) | p[3] as u16,
+        event: p[0] >> 6,
+        id: p[2] >> 4,
+    }
+}
 
 // Touch controller uses open-drain I2C + external/internal pull-ups.
 pub fn gpio_setup_touch_lines() {
     unsafe {
-        sys::gpio_reset_pin(TP_SDA_GPIO);
-        sys::gpio_reset_pin(TP_SCL_GPIO);
+        sys::gpio_reset_pin(I2C_SDA_GPIO);
+        sys::gpio_reset_pin(I2C_SCL_GPIO);
         sys::gpio_reset_pin(TP_RST_GPIO);
         sys::gpio_reset_pin(TP_INT_GPIO);
 
-        sys::gpio_set_direction(TP_SDA_GPIO, sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD);
-        sys::gpio_set_direction(TP_SCL_GPIO, sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD);
-        sys::gpio_pullup_en(TP_SDA_GPIO);
-        sys::gpio_pullup_en(TP_SCL_GPIO);
-        sys::gpio_pulldown_dis(TP_SDA_GPIO);
-        sys::gpio_pulldown_dis(TP_SCL_GPIO);
+        sys::gpio_set_direction(I2C_SDA_GPIO, sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD);
+        sys::gpio_set_direction(I2C_SCL_GPIO, sys::gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD);
+        sys::gpio_pullup_en(I2C_SDA_GPIO);
+        sys::gpio_pullup_en(I2C_SCL_GPIO);
+        sys::gpio_pulldown_dis(I2C_SDA_GPIO);
+        sys::gpio_pulldown_dis(I2C_SCL_GPIO);
 
         // Touch reset line is a push-pull output.
         sys::gpio_set_direction(TP_RST_GPIO, sys::gpio_mode_t_GPIO_MODE_OUTPUT);
@@ -87,18 +151,20 @@ pub fn touch_reset_pulse() {
     }
 }
 
-pub fn i2c_scan(i2c: &mut I2cDriver<'_>) {
+// Logs and returns the 7-bit addresses that ACK an empty write.
+pub fn i2c_scan(i2c: &mut I2cDriver<'_>) -> Vec<u8> {
     info!("Scanning I2C...");
-    let mut found = 0;
+    let mut found = Vec::new();
     for addr in 0x08u8..0x78u8 {
         if i2c.write(addr, &[], esp_idf_hal::delay::BLOCK).is_ok() {
             info!("I2C device at 0x{:02X}", addr);
-            found += 1;
+            found.push(addr);
         }
     }
-    if found == 0 {
+    if found.is_empty() {
         error!("No I2C devices found (wrong pins / no pullups / power gating)");
     }
+    found
 }
 
 pub fn init_i2c<'d, I2C: I2c>(
@@ -110,12 +176,14 @@ pub fn init_i2c<'d, I2C: I2c>(
     touch_reset_pulse();
     init_touch_irq();
 
-    let i2c_cfg = I2cConfig::new().baudrate(100.kHz().into());
+    let baud_khz = I2C_BAUD_KHZ_ENV
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|v| (10..=1000).contains(v))
+        .unwrap_or(I2C_BAUD_KHZ_DEFAULT);
+    info!("I2C bus at {} kHz", baud_khz);
+    let i2c_cfg = I2cConfig::new().baudrate(baud_khz.kHz().into());
     let mut driver = I2cDriver::new(i2c, sda, scl, &i2c_cfg)?;
     i2c_scan(&mut driver);
-    if let Err(e) = probe_touch(&mut driver) {
-        error!("Touch probe failed: {:?}", e);
-    }
 
     Ok(driver)
 }
@@ -142,18 +210,118 @@ pub fn probe_touch(i2c: &mut I2cDriver<'_>) -> Result<()> {
     Ok(())
 }
 
-pub fn read_touch(i2c: &mut I2cDriver<'_>) -> Result<Option<(u16, u16)>> {
-    let mut d = [0u8; 8];
-    read_reg_no_restart(i2c, 0x02, &mut d)?;
+// Whether the touch controller answers. Starts from the boot probe; reads are
+// skipped while it is unavailable, so a missing (DNP) or broken controller
+// does not cost a failing bus transaction and an error log on every loop.
+pub struct TouchHealth {
+    available: bool,
+    failures: u8,
+    last_probe: Instant,
+}
+
+impl TouchHealth {
+    pub fn new(available: bool) -> Self {
+        Self {
+            available,
+            failures: 0,
+            last_probe: Instant::now(),
+        }
+    }
+
+    pub fn available(&self) -> bool {
+        self.available
+    }
+
+    // Re-probe an unavailable controller every TOUCH_REPROBE_INTERVAL.
+    // Returns true when it answered again.
+    pub fn maintain(&mut self, i2c: &mut I2cDriver<'_>) -> bool {
+        if self.available || self.last_probe.elapsed() < TOUCH_REPROBE_INTERVAL {
+            return false;
+        }
+        self.last_probe = Instant::now();
+        if probe_touch(i2c).is_err() {
+            return false;
+        }
+        self.available = true;
+        self.failures = 0;
+        true
+    }
+
+    pub fn record_ok(&mut self) {
+        self.failures = 0;
+    }
+
+    // Returns true when this failure marked the controller unavailable.
+    pub fn record_error(&mut self) -> bool {
+        if !self.available {
+            return false;
+        }
+        self.failures = self.failures.saturating_add(1);
+        if self.failures < TOUCH_FAIL_LIMIT {
+            return false;
+        }
+        warn!(
+            "Touch controller not responding after {} reads, touch disabled",
+            self.failures
+        );
+        self.available = false;
+        self.last_probe = Instant::now();
+        true
+    }
+}
+
+#[derive(Debug)]
+enum ReportError {
+    I2c(esp_idf_hal::sys::EspError),
+    // A point outside the panel: a corrupted read, or a report format that
+    // does not match the controller firmware (TOUCH_REPORT_FORMAT).
+    OffPanel(TouchPoint),
+}
+
+// All active touch points in native panel coordinates (172x320). A report
+// with an off-panel point is read again; if it stays off panel the point is
+// dropped.
+pub fn read_touches(i2c: &mut I2cDriver<'_>) -> Result<heapless::Vec<Point, TP_MAX_POINTS>> {
+    match retry_corrupt(
+        "Touch report",
+        || read_report(i2c),
+        |err| matches!(err, ReportError::OffPanel(_)),
+    ) {
+        Ok(points) => Ok(points),
+        Err(ReportError::OffPanel(point)) => {
+            debug!(
+                "Touch point off panel, ignored: x={} y={} event={} id={}",
+                point.x, point.y, point.event, point.id
+            );
+            Ok(heapless::Vec::new())
+        }
+        Err(ReportError::I2c(err)) => Err(err.into()),
+    }
+}
+
+fn read_report(i2c: &mut I2cDriver<'_>) -> Result<heapless::Vec<Point, TP_MAX_POINTS>, ReportError> {
+    let mut d = [0u8; TP_REPORT_LEN];
+    read_reg_no_restart(i2c, 0x02, &mut d).map_err(ReportError::I2c)?;
 
     // First byte low nibble = number of touch points.
-    let points = d[0] & 0x0F;
-    if points == 0 {
-        return Ok(None);
+    let count = ((d[0] & 0x0F) as usize).min(TP_MAX_POINTS);
+    if LAST_REPORT_COUNT.swap(count as u8, Ordering::Relaxed) != count as u8 {
+        // First 8 bytes: count plus the first point record, raw.
+        debug!("Touch report @0x02: {:02X?}", &d[..8]);
+    }
+    let mut points = heapless::Vec::new();
+    for i in 0..count {
+        let point = parse_point(&d[1 + i * TP_POINT_STRIDE..], TOUCH_FORMAT);
+        if point.x as usize >= LCD_W || point.y as usize >= LCD_H {
+            return Err(ReportError::OffPanel(point));
+        }
+        let _ = points.push(Point::new(point.x as i32, point.y as i32));
     }
 
-    let x = (((d[1] as u16) & 0x0F) << 8) | d[2] as u16;
-    let y = (((d[3] as u16) & 0x0F) << 8) | d[4] as u16;
+    Ok(points)
+}
 
-    Ok(Some((x, y)))
+pub fn read_touch(i2c: &mut I2cDriver<'_>) -> Result<Option<(u16, u16)>> {
+    let points = read_touches(i2c)?;
+    Ok(points.first().map(|p| (p.x as u16, p.y as u16)))
 }
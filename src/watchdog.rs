@@ -0,0 +1,71 @@
+// Task watchdog feeding. ESP-IDF only watches tasks that subscribed with
+// esp_task_wdt_add(); for any other task the calls here do nothing, so they are
+// safe to sprinkle before the main task is subscribed.
+//
+// Anything that blocks for longer than a main loop iteration (OTA download,
+// sensor calibration waits) runs inside a `LongOperation` and calls `feed()`
+// per chunk of work, or uses its `sleep()` for fixed waits.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use esp_idf_sys as sys;
+use log::debug;
+
+// Longest single sleep between feeds; well below the 5 s default timeout.
+const FEED_SLICE: Duration = Duration::from_millis(500);
+
+// Reset the watchdog for the calling task, if it is subscribed.
+pub fn feed() {
+    unsafe {
+        // esp_task_wdt_reset() logs an error for unsubscribed tasks.
+        if sys::esp_task_wdt_status(core::ptr::null_mut()) == sys::ESP_OK {
+            sys::esp_task_wdt_reset();
+        }
+    }
+}
+
+// Feeds on start and when dropped, so the caller resumes with a full timeout
+// whichever way the operation ends (including `?` returns).
+pub struct LongOperation {
+    name: &'static str,
+    started: Instant,
+}
+
+impl LongOperation {
+    pub fn start(name: &'static str) -> Self {
+        feed();
+        Self {
+            name,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn feed(&self) {
+        feed();
+    }
+
+    // thread::sleep in slices short enough to keep the watchdog fed.
+    pub fn sleep(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        loop {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(FEED_SLICE));
+            feed();
+        }
+    }
+}
+
+impl Drop for LongOperation {
+    fn drop(&mut self) {
+        feed();
+        debug!(
+            "{} took {} ms",
+            self.name,
+            self.started.elapsed().as_millis()
+        );
+    }
+}
@@ -4,6 +4,10 @@ use esp_idf_hal::modem::Modem;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use esp_idf_sys as sys;
+use log::{info, warn, Level};
+
+use crate::event_log;
 
 const WIFI_SSID: &str = match option_env!("WIFI_SSID") {
     Some(v) => v,
@@ -13,18 +17,42 @@ const WIFI_PASS: &str = match option_env!("WIFI_PASS") {
     Some(v) => v,
     None => "HalfLife2",
 };
+// Regulatory domain as an ISO 3166 alpha-2 code (e.g. "DE", "US"). The
+// default "01" is ESP-IDF's worldwide safe mode (channels 1-11, passive scan
+// above), which then follows the country the AP advertises (802.11d).
+const WIFI_COUNTRY_WORLD: &str = "01";
+const WIFI_COUNTRY: &str = match option_env!("WIFI_COUNTRY") {
+    Some(v) => v,
+    None => WIFI_COUNTRY_WORLD,
+};
+// AP channel, if known: the station scans it first instead of sweeping all
+// channels, which shortens every (re)connect. Unset or 0 scans as usual.
+const WIFI_CHANNEL_ENV: Option<&str> = option_env!("WIFI_CHANNEL");
 
-pub fn init_wifi(modem: Modem) -> Result<BlockingWifi<EspWifi<'static>>> {
+pub fn init_wifi(
+    modem: Modem,
+    nvs: EspDefaultNvsPartition,
+) -> Result<BlockingWifi<EspWifi<'static>>> {
     if WIFI_SSID == "YOUR_WIFI_SSID" {
         return Err(anyhow!("WIFI_SSID not configured"));
     }
     let sys_loop = EspSystemEventLoop::take()?;
-    let nvs = EspDefaultNvsPartition::take()?;
 
     let mut wifi = BlockingWifi::wrap(
         EspWifi::new(modem, sys_loop.clone(), Some(nvs))?,
         sys_loop,
     )?;
+    set_country()?;
+    let channel = WIFI_CHANNEL_ENV
+        .and_then(|v| v.trim().parse::<u8>().ok())
+        .filter(|&ch| ch != 0);
+    match channel {
+        Some(ch) if ch > 14 => {
+            warn!("WIFI_CHANNEL {} out of range, scanning all channels", ch);
+        }
+        Some(ch) => info!("Wi-Fi AP channel pinned to {}", ch),
+        None => info!("Wi-Fi AP channel: scan"),
+    }
 
     let auth_method = if WIFI_PASS.is_empty() {
         AuthMethod::None
@@ -43,6 +71,7 @@ pub fn init_wifi(modem: Modem) -> Result<BlockingWifi<EspWifi<'static>>> {
         ssid,
         password,
         auth_method,
+        channel: channel.filter(|&ch| ch <= 14),
         ..Default::default()
     });
 
@@ -52,11 +81,42 @@ pub fn init_wifi(modem: Modem) -> Result<BlockingWifi<EspWifi<'static>>> {
     Ok(wifi)
 }
 
+// Apply WIFI_COUNTRY and log the channel range the driver ends up using. An
+// invalid code falls back to the worldwide default rather than failing boot.
+fn set_country() -> Result<()> {
+    let code = WIFI_COUNTRY.trim().to_ascii_uppercase();
+    let code = if code.len() == 2 && code.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        code
+    } else {
+        warn!("WIFI_COUNTRY {:?} invalid, using {}", WIFI_COUNTRY, WIFI_COUNTRY_WORLD);
+        WIFI_COUNTRY_WORLD.to_string()
+    };
+    let world = code == WIFI_COUNTRY_WORLD;
+    let cc = [code.as_bytes()[0], code.as_bytes()[1], 0];
+    // 802.11d (adopting the AP's country) only in worldwide mode; an explicit
+    // country is kept as configured.
+    sys::EspError::convert(unsafe {
+        sys::esp_wifi_set_country_code(cc.as_ptr() as *const core::ffi::c_char, world)
+    })?;
+
+    let mut country: sys::wifi_country_t = unsafe { core::mem::zeroed() };
+    sys::EspError::convert(unsafe { sys::esp_wifi_get_country(&mut country) })?;
+    info!(
+        "Wi-Fi country {} ({}), channels {}-{}",
+        code,
+        if world { "follows AP" } else { "fixed" },
+        country.schan,
+        country.schan + country.nchan.saturating_sub(1)
+    );
+    Ok(())
+}
+
 pub fn ensure_connected(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
     if !wifi.is_started()? {
         wifi.start()?;
     }
     if !wifi.is_connected()? {
+        event_log::push(Level::Warn, "Wi-Fi connecting");
         wifi.connect()?;
     }
     wifi.wait_netif_up()?;
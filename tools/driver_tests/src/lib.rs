@@ -0,0 +1,28 @@
+// Host-side tests for the esp-idf-free firmware code: src/sensor_proto.rs and
+// the SHT31/MH-Z19B drivers in src/sensor_hal.rs (with the i2c_retry.rs helper
+// they use), driven by the mock bus and serial port in `mock`; the command parser in src/command.rs; and the
+// telemetry age tracking and JSON payload in src/telemetry.rs. The modules
+// are compiled unchanged; the tests live in tests/.
+//
+// Run via `scripts/driver_tests.sh`.
+
+// The firmware is a binary crate, so public-API lints do not apply to it.
+#[allow(dead_code, clippy::new_without_default)]
+#[path = "../../../src/command.rs"]
+pub mod command;
+#[path = "../../../src/i2c_retry.rs"]
+pub mod i2c_retry;
+#[allow(dead_code)]
+#[path = "../../../src/options.rs"]
+pub mod options;
+#[allow(dead_code)]
+#[path = "../../../src/sensor_proto.rs"]
+pub mod sensor_proto;
+#[allow(dead_code)]
+#[path = "../../../src/sensor_hal.rs"]
+pub mod sensor_hal;
+#[allow(dead_code)]
+#[path = "../../../src/telemetry.rs"]
+pub mod telemetry;
+
+pub mod mock;
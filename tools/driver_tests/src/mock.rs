@@ -0,0 +1,164 @@
+// Minimal stand-ins for the bus traits the drivers take: writes are recorded,
+// reads are answered from a queue filled by the test. Anything the test did
+// not queue fails the way absent hardware would (NACK, no byte ready).
+
+use std::collections::VecDeque;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, NoAcknowledgeSource, Operation};
+use embedded_hal_nb::nb;
+use embedded_hal_nb::serial;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockError {
+    // Nothing queued for this read, or a queued failure.
+    Nack,
+    // A serial read error queued with `MockSerial::queue_read_error`.
+    Serial,
+}
+
+impl i2c::Error for MockError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+    }
+}
+
+impl serial::Error for MockError {
+    fn kind(&self) -> serial::ErrorKind {
+        serial::ErrorKind::Other
+    }
+}
+
+#[derive(Default)]
+pub struct MockI2c {
+    // (address, bytes) of every write, in order.
+    pub writes: Vec<(u8, Vec<u8>)>,
+    reads: VecDeque<Result<Vec<u8>, MockError>>,
+    write_errors: usize,
+}
+
+impl MockI2c {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Answer the next read with `bytes`; its length must match the read.
+    pub fn queue_read(&mut self, bytes: &[u8]) {
+        self.reads.push_back(Ok(bytes.to_vec()));
+    }
+
+    pub fn queue_read_error(&mut self) {
+        self.reads.push_back(Err(MockError::Nack));
+    }
+
+    // NACK the next write (e.g. a sensor that is not fitted).
+    pub fn fail_next_write(&mut self) {
+        self.write_errors += 1;
+    }
+}
+
+impl i2c::ErrorType for MockI2c {
+    type Error = MockError;
+}
+
+impl i2c::I2c for MockI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    if self.write_errors > 0 {
+                        self.write_errors -= 1;
+                        return Err(MockError::Nack);
+                    }
+                    self.writes.push((address, bytes.to_vec()));
+                }
+                Operation::Read(buf) => {
+                    let bytes = self.reads.pop_front().unwrap_or(Err(MockError::Nack))?;
+                    assert_eq!(bytes.len(), buf.len(), "queued read has the wrong length");
+                    buf.copy_from_slice(&bytes);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Serial port where a queued reply only arrives once the driver has flushed
+// its next command, like a sensor answering a request. Bytes queued with
+// `queue_stale` are readable straight away (left over from an earlier frame).
+#[derive(Default)]
+pub struct MockSerial {
+    // Every byte written, in order.
+    pub tx: Vec<u8>,
+    rx: VecDeque<u8>,
+    replies: VecDeque<Vec<u8>>,
+    read_errors: usize,
+}
+
+impl MockSerial {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn queue_stale(&mut self, bytes: &[u8]) {
+        self.rx.extend(bytes);
+    }
+
+    // Sent back after the next flush; one reply per command.
+    pub fn queue_reply(&mut self, bytes: &[u8]) {
+        self.replies.push_back(bytes.to_vec());
+    }
+
+    pub fn queue_read_error(&mut self) {
+        self.read_errors += 1;
+    }
+
+    // Bytes still unread by the driver.
+    pub fn unread(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl serial::ErrorType for MockSerial {
+    type Error = MockError;
+}
+
+impl serial::Read<u8> for MockSerial {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        if self.read_errors > 0 {
+            self.read_errors -= 1;
+            return Err(nb::Error::Other(MockError::Serial));
+        }
+        self.rx.pop_front().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl serial::Write<u8> for MockSerial {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.tx.push(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if let Some(reply) = self.replies.pop_front() {
+            self.rx.extend(reply);
+        }
+        Ok(())
+    }
+}
+
+// Returns at once; adds up the requested time so tests can check waits.
+#[derive(Default)]
+pub struct MockDelay {
+    pub total_ns: u64,
+}
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.total_ns += u64::from(ns);
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use driver_tests::command::{parse_command, Command, CommandThrottle};
+use driver_tests::options::{Layout, RelayMode, TempHumSource, TempUnit, ThemePreset};
+
+fn parse(text: &str) -> Option<Command> {
+    parse_command(text.as_bytes())
+}
+
+// Both separators the parser accepts for `key` with `value`.
+fn parse_kv(key: &str, value: &str) -> [Option<Command>; 2] {
+    [
+        parse(&format!("{key}={value}")),
+        parse(&format!("{key}:{value}")),
+    ]
+}
+
+fn assert_kv(key: &str, value: &str, expected: Command) {
+    for parsed in parse_kv(key, value) {
+        assert_eq!(parsed.as_ref(), Some(&expected), "{key} {value}");
+    }
+}
+
+fn assert_kv_rejected(key: &str, value: &str) {
+    for parsed in parse_kv(key, value) {
+        assert_eq!(parsed, None, "{key} {value}");
+    }
+}
+
+#[test]
+fn plain_commands() {
+    for (text, expected) in [
+        ("zero", Command::ZeroCalibrate),
+        ("zero_calibrate", Command::ZeroCalibrate),
+        ("reboot", Command::Reboot),
+        ("reset_peak", Command::ResetPeak),
+        ("reset_crashes", Command::ResetCrashCount),
+        ("settings", Command::ReportSettings),
+        ("get", Command::ReportSettings),
+        ("factory_reset", Command::FactoryReset),
+    ] {
+        assert_eq!(parse(text), Some(expected), "{text}");
+    }
+}
+
+#[test]
+fn case_and_surrounding_whitespace_are_ignored() {
+    assert_eq!(parse("  Reboot\r\n"), Some(Command::Reboot));
+    assert_eq!(parse("ABC:ON"), Some(Command::SetAbc(true)));
+    assert_eq!(parse("brightness: 40 "), Some(Command::SetBrightness(40)));
+}
+
+#[test]
+fn on_off_setters() {
+    for (value, on) in [
+        ("on", true),
+        ("1", true),
+        ("true", true),
+        ("off", false),
+        ("0", false),
+        ("false", false),
+    ] {
+        assert_kv("abc", value, Command::SetAbc(on));
+        assert_kv("lcd_invert", value, Command::SetLcdInvert(on));
+    }
+    assert_kv_rejected("abc", "maybe");
+    assert_kv_rejected("lcd_invert", "");
+}
+
+#[test]
+fn brightness_is_clamped_to_percent() {
+    assert_kv("brightness", "0", Command::SetBrightness(0));
+    assert_kv("brightness", "75", Command::SetBrightness(75));
+    assert_kv("brightness", "150", Command::SetBrightness(100));
+    for value in ["", "-1", "256", "half", "50%"] {
+        assert_kv_rejected("brightness", value);
+    }
+}
+
+#[test]
+fn co2_alarm_is_clamped_to_range() {
+    assert_kv("co2_alarm", "1200", Command::SetCo2Alarm(1200));
+    assert_kv("co2_alarm", "100", Command::SetCo2Alarm(600));
+    assert_kv("co2_alarm", "9000", Command::SetCo2Alarm(3000));
+    for value in ["", "70000", "1e3"] {
+        assert_kv_rejected("co2_alarm", value);
+    }
+}
+
+#[test]
+fn co2_smoothing_is_clamped_to_range() {
+    assert_kv("co2_smoothing", "5", Command::SetCo2Smoothing(5));
+    assert_kv("co2_smoothing", "0", Command::SetCo2Smoothing(1));
+    assert_kv("co2_smoothing", "99", Command::SetCo2Smoothing(15));
+    assert_kv_rejected("co2_smoothing", "three");
+}
+
+#[test]
+fn temp_hum_source() {
+    assert_kv("temp_hum_source", "auto", Command::SetTempHumSource(TempHumSource::Auto));
+    assert_kv("temp_hum_source", "sht31", Command::SetTempHumSource(TempHumSource::Sht31));
+    assert_kv("temp_hum_source", "scd4x", Command::SetTempHumSource(TempHumSource::Scd4x));
+    assert_kv_rejected("temp_hum_source", "dht22");
+}
+
+#[test]
+fn theme_and_layout() {
+    assert_kv("theme", "dark", Command::SetTheme(ThemePreset::Dark));
+    assert_kv("theme", "high_contrast", Command::SetTheme(ThemePreset::HighContrast));
+    assert_kv("theme", "contrast", Command::SetTheme(ThemePreset::HighContrast));
+    assert_kv("theme", "amber", Command::SetTheme(ThemePreset::Amber));
+    assert_kv_rejected("theme", "neon");
+
+    assert_kv("layout", "cards", Command::SetLayout(Layout::Cards));
+    assert_kv("layout", "big", Command::SetLayout(Layout::Big));
+    assert_kv_rejected("layout", "tiny");
+}
+
+#[test]
+fn temperature_unit_and_decimals() {
+    assert_kv("temp_unit", "c", Command::SetTempUnit(TempUnit::Celsius));
+    assert_kv("temp_unit", "celsius", Command::SetTempUnit(TempUnit::Celsius));
+    assert_kv("temp_unit", "f", Command::SetTempUnit(TempUnit::Fahrenheit));
+    assert_kv("temp_unit", "fahrenheit", Command::SetTempUnit(TempUnit::Fahrenheit));
+    assert_kv_rejected("temp_unit", "k");
+
+    assert_kv("temp_decimals", "0", Command::SetTempDecimals(0));
+    assert_kv("temp_decimals", "1", Command::SetTempDecimals(1));
+    assert_kv_rejected("temp_decimals", "2");
+}
+
+#[test]
+fn relay_mode() {
+    assert_kv("relay", "auto", Command::SetRelay(RelayMode::Auto));
+    for value in ["on", "1", "true"] {
+        assert_kv("relay", value, Command::SetRelay(RelayMode::On));
+    }
+    for value in ["off", "0", "false"] {
+        assert_kv("relay", value, Command::SetRelay(RelayMode::Off));
+    }
+    assert_kv_rejected("relay", "toggle");
+}
+
+#[test]
+fn malformed_payloads_are_rejected() {
+    for payload in [
+        &b""[..],
+        b"   \n",
+        b"unknown",
+        b"reboot now",
+        b"zero calibrate",
+        b"abc",
+        b"brightness",
+        b"brightness 50",
+        b"=on",
+        b"{\"brightness\":50}",
+        b"\xFF\xFE",
+    ] {
+        assert_eq!(parse_command(payload), None, "{payload:?}");
+    }
+}
+
+#[test]
+fn plain_command_names_parse_back() {
+    for cmd in [
+        Command::ZeroCalibrate,
+        Command::ResetPeak,
+        Command::ResetCrashCount,
+        Command::ReportSettings,
+        Command::Reboot,
+        Command::FactoryReset,
+    ] {
+        assert_eq!(parse(cmd.name()).as_ref(), Some(&cmd));
+    }
+}
+
+#[test]
+fn only_setters_change_settings() {
+    assert!(Command::SetBrightness(50).changes_settings());
+    assert!(Command::SetRelay(RelayMode::Auto).changes_settings());
+    assert!(!Command::ReportSettings.changes_settings());
+    assert!(!Command::ResetPeak.changes_settings());
+    assert!(!Command::Reboot.changes_settings());
+}
+
+#[test]
+fn throttle_applies_cooldowns() {
+    let mut throttle = CommandThrottle::new();
+
+    // Setters are never throttled.
+    throttle.record(&Command::SetBrightness(10));
+    assert_eq!(throttle.remaining(&Command::SetBrightness(20)), None);
+
+    // A 5 s command only after it ran once.
+    assert_eq!(throttle.remaining(&Command::ResetPeak), None);
+    throttle.record(&Command::ResetPeak);
+    let left = throttle.remaining(&Command::ResetPeak).unwrap();
+    assert!(left <= Duration::from_secs(5));
+    assert_eq!(throttle.remaining(&Command::ResetCrashCount), None);
+
+    // Restarting commands wait out their cooldown from boot.
+    assert!(throttle.remaining(&Command::Reboot).is_some());
+}
@@ -0,0 +1,87 @@
+// Byte-exact frames (datasheet examples and replies in the layout the sensors
+// send them) fed straight to the pure parsing in sensor_proto, without a bus.
+
+use driver_tests::sensor_proto::mhz19b::{
+    self, command, validate_frame, FrameError, CMD_ABC, CMD_READ_PPM, CMD_ZERO_POINT,
+};
+use driver_tests::sensor_proto::sht31::{
+    crc8, parse_measurement, parse_raw, raw_to_celsius, raw_to_humidity, CrcError,
+};
+
+const MHZ_READ_REQUEST: [u8; 9] = [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79];
+const MHZ_ZERO_REQUEST: [u8; 9] = [0xFF, 0x01, 0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78];
+const MHZ_ABC_OFF_REQUEST: [u8; 9] = [0xFF, 0x01, 0x79, 0x00, 0x00, 0x00, 0x00, 0x00, 0x86];
+const MHZ_ABC_ON_REQUEST: [u8; 9] = [0xFF, 0x01, 0x79, 0xA0, 0x00, 0x00, 0x00, 0x00, 0xE6];
+// 608 ppm (datasheet example) and 418 ppm.
+const MHZ_REPLY_608: [u8; 9] = [0xFF, 0x86, 0x02, 0x60, 0x47, 0x00, 0x00, 0x00, 0xD1];
+const MHZ_REPLY_418: [u8; 9] = [0xFF, 0x86, 0x01, 0xA2, 0x3F, 0x00, 0x00, 0x00, 0x98];
+
+// 22.75 °C / 48.0 %RH and 19.45 °C / 57.0 %RH.
+const SHT_MEASUREMENT_A: [u8; 6] = [0x63, 0x1C, 0xC7, 0x7A, 0xE1, 0xA4];
+const SHT_MEASUREMENT_B: [u8; 6] = [0x5E, 0x48, 0x8F, 0x91, 0xEB, 0x13];
+
+fn assert_close(got: f32, expected: f32) {
+    assert!((got - expected).abs() < 0.01, "{got} != {expected}");
+}
+
+#[test]
+fn mhz19b_requests_match_datasheet() {
+    assert_eq!(command(CMD_READ_PPM, [0; 5]), MHZ_READ_REQUEST);
+    assert_eq!(command(CMD_ZERO_POINT, [0; 5]), MHZ_ZERO_REQUEST);
+    assert_eq!(command(CMD_ABC, [0; 5]), MHZ_ABC_OFF_REQUEST);
+    assert_eq!(command(CMD_ABC, [0xA0, 0, 0, 0, 0]), MHZ_ABC_ON_REQUEST);
+}
+
+#[test]
+fn mhz19b_replies_validate_and_parse() {
+    for (frame, ppm) in [(MHZ_REPLY_608, 608), (MHZ_REPLY_418, 418)] {
+        assert_eq!(mhz19b::checksum(&frame[1..8]), frame[8]);
+        assert_eq!(validate_frame(&frame, CMD_READ_PPM), Ok(()));
+        assert_eq!(mhz19b::ppm(&frame), ppm);
+    }
+}
+
+#[test]
+fn mhz19b_reply_with_flipped_bit_fails_checksum() {
+    let mut frame = MHZ_REPLY_608;
+    frame[3] ^= 0x04;
+    assert_eq!(
+        validate_frame(&frame, CMD_READ_PPM),
+        Err(FrameError::Checksum {
+            expected: mhz19b::checksum(&frame[1..8]),
+            got: 0xD1,
+        })
+    );
+}
+
+#[test]
+fn sht31_measurements_parse() {
+    assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+
+    let (temp, rh) = parse_measurement(&SHT_MEASUREMENT_A).unwrap();
+    assert_close(temp, 22.75);
+    assert_close(rh, 48.0);
+
+    let (temp, rh) = parse_measurement(&SHT_MEASUREMENT_B).unwrap();
+    assert_close(temp, 19.45);
+    assert_close(rh, 57.0);
+}
+
+#[test]
+fn sht31_measurement_with_flipped_bit_fails_crc() {
+    let mut data = SHT_MEASUREMENT_A;
+    data[0] ^= 0x01;
+    assert_eq!(parse_raw(&data), Err(CrcError::Temperature));
+
+    let mut data = SHT_MEASUREMENT_B;
+    data[4] ^= 0x10;
+    assert_eq!(parse_raw(&data), Err(CrcError::Humidity));
+}
+
+#[test]
+fn sht31_conversion_covers_full_range() {
+    assert_close(raw_to_celsius(0), -45.0);
+    assert_close(raw_to_celsius(u16::MAX), 130.0);
+    assert_close(raw_to_humidity(0), 0.0);
+    assert_close(raw_to_humidity(u16::MAX), 100.0);
+}
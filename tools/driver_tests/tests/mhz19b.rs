@@ -0,0 +1,345 @@
+use driver_tests::mock::{MockDelay, MockError, MockSerial};
+use driver_tests::sensor_hal::mhz19b::{Error, Mhz19b};
+use driver_tests::sensor_proto::mhz19b::{
+    apply_temp_compensation, checksum, command, ppm, realign, validate_frame, FrameError,
+    CMD_ABC, CMD_READ_PPM, CMD_ZERO_POINT, CO2_TEMP_COMP_COEFF_PER_C, CO2_TEMP_COMP_REF_C,
+    FRAME_LEN, PPM_FLOOR,
+};
+
+const READ_PPM_REQUEST: [u8; FRAME_LEN] = [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79];
+
+// A 0x86 reply carrying `ppm`, with a valid checksum.
+fn reply(ppm: u16) -> [u8; FRAME_LEN] {
+    let [hi, lo] = ppm.to_be_bytes();
+    let mut frame = [0xFF, CMD_READ_PPM, hi, lo, 0x47, 0x00, 0x00, 0x00, 0x00];
+    frame[8] = checksum(&frame[1..8]);
+    frame
+}
+
+#[test]
+fn command_matches_datasheet_frame() {
+    assert_eq!(command(CMD_READ_PPM, [0; 5]), READ_PPM_REQUEST);
+}
+
+#[test]
+fn validate_accepts_good_reply() {
+    let frame = reply(842);
+    assert_eq!(validate_frame(&frame, CMD_READ_PPM), Ok(()));
+    assert_eq!(ppm(&frame), 842);
+}
+
+#[test]
+fn validate_rejects_bad_header() {
+    let mut frame = reply(842);
+    frame[0] = 0xFE;
+    assert_eq!(validate_frame(&frame, CMD_READ_PPM), Err(FrameError::Header));
+    // Reply to a different command.
+    assert_eq!(validate_frame(&reply(842), CMD_ABC), Err(FrameError::Header));
+}
+
+#[test]
+fn validate_rejects_bad_checksum() {
+    let mut frame = reply(842);
+    let expected = frame[8];
+    frame[8] = expected.wrapping_add(1);
+    assert_eq!(
+        validate_frame(&frame, CMD_READ_PPM),
+        Err(FrameError::Checksum {
+            expected,
+            got: expected.wrapping_add(1),
+        })
+    );
+}
+
+#[test]
+fn realign_skips_leading_noise() {
+    let mut buf = [0u8; FRAME_LEN];
+    buf[..4].copy_from_slice(&[0x12, 0xFF, 0xFF, CMD_READ_PPM]);
+    assert_eq!(realign(&mut buf, 4), 2);
+    assert_eq!(&buf[..2], &[0xFF, CMD_READ_PPM]);
+
+    // A trailing 0xFF may still be the start of a frame.
+    buf[..2].copy_from_slice(&[0x00, 0xFF]);
+    assert_eq!(realign(&mut buf, 2), 1);
+    assert_eq!(buf[0], 0xFF);
+}
+
+#[test]
+fn temperature_compensation_is_a_no_op_when_disabled() {
+    for temp_c in [None, Some(-10.0), Some(CO2_TEMP_COMP_REF_C), Some(40.0)] {
+        assert_eq!(apply_temp_compensation(842, temp_c, false), 842, "{temp_c:?}");
+    }
+}
+
+#[test]
+fn temperature_compensation_scales_with_offset_from_reference() {
+    let comp = |temp_c: f32| apply_temp_compensation(1000, Some(temp_c), true);
+    let delta = (1000.0 * CO2_TEMP_COMP_COEFF_PER_C * 10.0).round() as u16;
+    assert_eq!(comp(CO2_TEMP_COMP_REF_C), 1000);
+    assert_eq!(comp(CO2_TEMP_COMP_REF_C + 10.0), 1000 + delta);
+    assert_eq!(comp(CO2_TEMP_COMP_REF_C - 10.0), 1000 - delta);
+    // No temperature yet: nothing to compensate with.
+    assert_eq!(apply_temp_compensation(1000, None, true), 1000);
+}
+
+#[test]
+fn read_ppm_flushes_stale_bytes_and_parses_reply() {
+    let mut serial = MockSerial::new();
+    serial.queue_stale(&[0x01, 0x02, 0x03]);
+    serial.queue_reply(&reply(1234));
+
+    let value = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap();
+
+    assert_eq!(value, 1234);
+    assert_eq!(serial.tx, READ_PPM_REQUEST);
+    assert_eq!(serial.unread(), 0);
+}
+
+#[test]
+fn read_ppm_resyncs_on_noise_before_reply() {
+    let mut serial = MockSerial::new();
+    let mut bytes = vec![0x00, 0xFF];
+    bytes.extend_from_slice(&reply(600));
+    serial.queue_reply(&bytes);
+
+    let value = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap();
+    assert_eq!(value, 600);
+}
+
+#[test]
+fn read_ppm_rejects_zero_ppm_with_valid_checksum() {
+    let mut serial = MockSerial::new();
+    let frame = reply(0);
+    assert_eq!(validate_frame(&frame, CMD_READ_PPM), Ok(()));
+    serial.queue_reply(&frame);
+
+    let err = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap_err();
+    assert!(matches!(err, Error::OutOfRange(0)), "{err:?}");
+}
+
+#[test]
+fn read_ppm_floor_is_inclusive() {
+    let mut mhz = Mhz19b::new_default();
+    let mut serial = MockSerial::new();
+    let mut delay = MockDelay::default();
+
+    serial.queue_reply(&reply(PPM_FLOOR - 1));
+    let err = mhz.read_ppm(&mut serial, &mut delay, 100).unwrap_err();
+    assert!(matches!(err, Error::OutOfRange(ppm) if ppm == PPM_FLOOR - 1), "{err:?}");
+
+    serial.queue_reply(&reply(PPM_FLOOR));
+    assert_eq!(mhz.read_ppm(&mut serial, &mut delay, 100).unwrap(), PPM_FLOOR);
+}
+
+#[test]
+fn read_ppm_reports_checksum_error() {
+    let mut serial = MockSerial::new();
+    let mut frame = reply(900);
+    frame[8] ^= 0xFF;
+    serial.queue_reply(&frame);
+
+    let err = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap_err();
+    assert!(matches!(err, Error::Frame(FrameError::Checksum { .. })), "{err:?}");
+}
+
+#[test]
+fn read_ppm_times_out_without_reply() {
+    let mut serial = MockSerial::new();
+    let mut delay = MockDelay::default();
+
+    let err = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut delay, 20)
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Timeout), "{err:?}");
+    assert!(delay.total_ns >= 20_000_000);
+}
+
+#[test]
+fn read_ppm_reports_serial_error() {
+    let mut serial = MockSerial::new();
+    serial.queue_read_error();
+
+    let err = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap_err();
+    assert!(matches!(err, Error::Serial(MockError::Serial)), "{err:?}");
+    // Failed while flushing, before anything was sent.
+    assert!(serial.tx.is_empty());
+}
+
+#[test]
+fn calibration_commands() {
+    let mut serial = MockSerial::new();
+    let mut mhz = Mhz19b::new_default();
+    mhz.calibrate_zero(&mut serial).unwrap();
+    mhz.set_abc(&mut serial, false).unwrap();
+
+    assert_eq!(serial.tx.len(), 2 * FRAME_LEN);
+    assert_eq!(serial.tx[..FRAME_LEN], command(CMD_ZERO_POINT, [0; 5]));
+    assert_eq!(serial.tx[FRAME_LEN..], command(CMD_ABC, [0; 5]));
+}
+
+#[test]
+fn frames_after_a_state_change_are_discarded() {
+    let mut mhz = Mhz19b::new(2);
+    let mut serial = MockSerial::new();
+    let mut delay = MockDelay::default();
+    assert_eq!(mhz.pending_discard(), 0);
+
+    mhz.set_abc(&mut serial, false).unwrap();
+    assert_eq!(mhz.pending_discard(), 2);
+
+    // No reply in time: nothing arrived, so nothing is used up.
+    let err = mhz.read_ppm(&mut serial, &mut delay, 20).unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{err:?}");
+    assert_eq!(mhz.pending_discard(), 2);
+
+    // The late reply to the timed-out query is flushed, not counted; the
+    // reply to the new query is the one dropped.
+    serial.queue_stale(&reply(450));
+    serial.queue_reply(&reply(500));
+    let err = mhz.read_ppm(&mut serial, &mut delay, 100).unwrap_err();
+    assert!(matches!(err, Error::Settling), "{err:?}");
+    assert_eq!(serial.unread(), 0);
+    assert_eq!(mhz.pending_discard(), 1);
+
+    // Another state change re-arms the full count.
+    mhz.calibrate_zero(&mut serial).unwrap();
+    assert_eq!(mhz.pending_discard(), 2);
+
+    for ppm in [510, 520] {
+        serial.queue_reply(&reply(ppm));
+        let err = mhz.read_ppm(&mut serial, &mut delay, 100).unwrap_err();
+        assert!(matches!(err, Error::Settling), "{err:?}");
+    }
+    assert_eq!(mhz.pending_discard(), 0);
+
+    serial.queue_reply(&reply(530));
+    assert_eq!(mhz.read_ppm(&mut serial, &mut delay, 100).unwrap(), 530);
+}
+
+#[test]
+fn corrupt_frame_does_not_use_up_a_discard() {
+    let mut mhz = Mhz19b::new(1);
+    let mut serial = MockSerial::new();
+    mhz.calibrate_zero(&mut serial).unwrap();
+
+    let mut frame = reply(700);
+    frame[8] ^= 0xFF;
+    serial.queue_reply(&frame);
+    let err = mhz
+        .read_ppm(&mut serial, &mut MockDelay::default(), 100)
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::Frame(FrameError::Checksum { .. })),
+        "{err:?}"
+    );
+    assert_eq!(mhz.pending_discard(), 1);
+}
+
+#[test]
+fn read_ppm_reports_missing_header_in_junk() {
+    let mut serial = MockSerial::new();
+    serial.queue_reply(&[0x12, 0x34, 0x56]);
+
+    let err = Mhz19b::new_default()
+        .read_ppm(&mut serial, &mut MockDelay::default(), 20)
+        .unwrap_err();
+    assert!(matches!(err, Error::Frame(FrameError::Header)), "{err:?}");
+}
+
+#[test]
+fn read_ppm_retry_requeries_after_transient_failures() {
+    let mut mhz = Mhz19b::new_default();
+    let mut serial = MockSerial::new();
+    let mut frame = reply(900);
+    frame[8] ^= 0xFF;
+    // First query unanswered, second corrupt, third good.
+    serial.queue_reply(&[]);
+    serial.queue_reply(&frame);
+    serial.queue_reply(&reply(910));
+
+    let value = mhz
+        .read_ppm_retry(&mut serial, &mut MockDelay::default(), 20, 3)
+        .unwrap();
+    assert_eq!(value, 910);
+    assert_eq!(serial.tx.len(), 3 * FRAME_LEN);
+}
+
+#[test]
+fn read_ppm_retry_gives_up_after_last_attempt() {
+    let mut serial = MockSerial::new();
+
+    let err = Mhz19b::new_default()
+        .read_ppm_retry(&mut serial, &mut MockDelay::default(), 20, 2)
+        .unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{err:?}");
+    assert_eq!(serial.tx.len(), 2 * FRAME_LEN);
+}
+
+#[test]
+fn read_ppm_retry_does_not_retry_out_of_range() {
+    let mut serial = MockSerial::new();
+    serial.queue_reply(&reply(0));
+
+    let err = Mhz19b::new_default()
+        .read_ppm_retry(&mut serial, &mut MockDelay::default(), 20, 3)
+        .unwrap_err();
+    assert!(matches!(err, Error::OutOfRange(0)), "{err:?}");
+    assert_eq!(serial.tx.len(), FRAME_LEN);
+}
+
+#[test]
+fn read_holds_last_value_while_settling() {
+    let mut mhz = Mhz19b::new(2);
+    let mut serial = MockSerial::new();
+    let mut delay = MockDelay::default();
+
+    serial.queue_reply(&reply(800));
+    assert_eq!(mhz.read(&mut serial, &mut delay, 20, 1).unwrap(), 800);
+
+    mhz.set_abc(&mut serial, true).unwrap();
+    serial.queue_reply(&reply(1500));
+    assert_eq!(mhz.read(&mut serial, &mut delay, 20, 1).unwrap(), 800);
+    assert_eq!(mhz.pending_discard(), 1);
+}
+
+#[test]
+fn read_without_previous_value_reads_through_discards() {
+    let mut mhz = Mhz19b::new(2);
+    let mut serial = MockSerial::new();
+    mhz.calibrate_zero(&mut serial).unwrap();
+    for ppm in [1500, 1200, 420] {
+        serial.queue_reply(&reply(ppm));
+    }
+
+    let value = mhz
+        .read(&mut serial, &mut MockDelay::default(), 20, 1)
+        .unwrap();
+    assert_eq!(value, 420);
+    assert_eq!(mhz.pending_discard(), 0);
+}
+
+#[test]
+fn raw_frame_is_kept_even_when_invalid() {
+    let mut mhz = Mhz19b::new_default();
+    let mut serial = MockSerial::new();
+    let mut frame = reply(900);
+    frame[8] ^= 0xFF;
+    serial.queue_reply(&frame);
+
+    assert!(mhz
+        .read_ppm(&mut serial, &mut MockDelay::default(), 20)
+        .is_err());
+    assert_eq!(mhz.take_raw_frame(), Some(frame));
+    assert_eq!(mhz.take_raw_frame(), None);
+}
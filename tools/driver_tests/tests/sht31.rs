@@ -0,0 +1,153 @@
+use driver_tests::mock::{MockDelay, MockError, MockI2c};
+use driver_tests::sensor_hal::sht31::{Error, Repeatability, Sht31, DEFAULT_ADDR};
+use driver_tests::sensor_proto::sht31::{crc8, parse_raw, CrcError, MEASUREMENT_LEN};
+
+// Raw words packed with their CRCs, as the sensor sends them.
+fn measurement(raw_temp: u16, raw_rh: u16) -> [u8; MEASUREMENT_LEN] {
+    let [t0, t1] = raw_temp.to_be_bytes();
+    let [h0, h1] = raw_rh.to_be_bytes();
+    [t0, t1, crc8(&[t0, t1]), h0, h1, crc8(&[h0, h1])]
+}
+
+#[test]
+fn crc8_matches_datasheet_example() {
+    assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+}
+
+#[test]
+fn parse_raw_rejects_each_word() {
+    let good = measurement(0x6666, 0x8000);
+    assert_eq!(parse_raw(&good), Ok((0x6666, 0x8000)));
+
+    let mut bad_temp = good;
+    bad_temp[2] ^= 0x01;
+    assert_eq!(parse_raw(&bad_temp), Err(CrcError::Temperature));
+
+    let mut bad_rh = good;
+    bad_rh[4] ^= 0x80;
+    assert_eq!(parse_raw(&bad_rh), Err(CrcError::Humidity));
+}
+
+#[test]
+fn read_sends_single_shot_and_converts() {
+    let mut i2c = MockI2c::new();
+    let mut delay = MockDelay::default();
+    // 0x6666 -> 25.0 °C, 0x8000 -> 50.0 %RH (to within rounding).
+    i2c.queue_read(&measurement(0x6666, 0x8000));
+
+    let m = Sht31::new_default().read(&mut i2c, &mut delay).unwrap();
+
+    assert_eq!(i2c.writes, vec![(DEFAULT_ADDR, vec![0x24, 0x00])]);
+    assert!((m.temperature_c - 25.0).abs() < 0.01, "{}", m.temperature_c);
+    assert!((m.humidity_pct - 50.0).abs() < 0.01, "{}", m.humidity_pct);
+    // Waits out the measurement before reading.
+    assert!(delay.total_ns >= 15_000_000);
+}
+
+#[test]
+fn read_repeats_measurement_after_crc_failure() {
+    let mut i2c = MockI2c::new();
+    let mut data = measurement(0x6666, 0x8000);
+    data[5] = !data[5];
+    i2c.queue_read(&data);
+    i2c.queue_read(&measurement(0x6666, 0x8000));
+
+    let m = Sht31::new_default()
+        .read(&mut i2c, &mut MockDelay::default())
+        .unwrap();
+    assert!((m.temperature_c - 25.0).abs() < 0.01, "{}", m.temperature_c);
+    // The whole single shot ran again, command included.
+    assert_eq!(i2c.writes.len(), 2);
+}
+
+#[test]
+fn read_reports_crc_failure_after_last_attempt() {
+    let mut i2c = MockI2c::new();
+    let mut data = measurement(0x6666, 0x8000);
+    data[5] = !data[5];
+    for _ in 0..3 {
+        i2c.queue_read(&data);
+    }
+
+    let err = Sht31::new_default()
+        .read(&mut i2c, &mut MockDelay::default())
+        .unwrap_err();
+    assert!(matches!(err, Error::Crc(CrcError::Humidity)), "{err:?}");
+    assert_eq!(i2c.writes.len(), 3);
+}
+
+#[test]
+fn read_retries_a_nacked_command() {
+    let mut i2c = MockI2c::new();
+    i2c.fail_next_write();
+    i2c.queue_read(&measurement(0x6666, 0x8000));
+
+    assert!(Sht31::new_default()
+        .read(&mut i2c, &mut MockDelay::default())
+        .is_ok());
+    assert_eq!(i2c.writes, vec![(DEFAULT_ADDR, vec![0x24, 0x00])]);
+}
+
+#[test]
+fn read_reports_missing_sensor() {
+    let mut i2c = MockI2c::new();
+    for _ in 0..3 {
+        i2c.fail_next_write();
+    }
+
+    let err = Sht31::new(0x45)
+        .read(&mut i2c, &mut MockDelay::default())
+        .unwrap_err();
+    assert!(matches!(err, Error::I2c(MockError::Nack)), "{err:?}");
+    assert!(i2c.writes.is_empty());
+}
+
+#[test]
+fn read_rejects_implausible_values() {
+    let mut i2c = MockI2c::new();
+    // 0xFFFF -> 130 °C, above the sensor's rated range.
+    i2c.queue_read(&measurement(0xFFFF, 0x8000));
+
+    let err = Sht31::new_default()
+        .read(&mut i2c, &mut MockDelay::default())
+        .unwrap_err();
+    assert!(matches!(err, Error::Implausible(m) if m.temperature_c > 85.0), "{err:?}");
+}
+
+#[test]
+fn clock_stretched_read_does_not_wait() {
+    let mut i2c = MockI2c::new();
+    let mut delay = MockDelay::default();
+    i2c.queue_read(&measurement(0x6666, 0x8000));
+
+    Sht31::new_default()
+        .with_mode(Repeatability::Low, true)
+        .read(&mut i2c, &mut delay)
+        .unwrap();
+
+    assert_eq!(i2c.writes, vec![(DEFAULT_ADDR, vec![0x2C, 0x10])]);
+    assert_eq!(delay.total_ns, 0);
+}
+
+#[test]
+fn periodic_mode_starts_and_fetches() {
+    let mut i2c = MockI2c::new();
+    let mut delay = MockDelay::default();
+    let sht = Sht31::new_default().with_mode(Repeatability::Medium, false);
+    sht.start_periodic(&mut i2c, &mut delay).unwrap();
+
+    // No new result yet: the read is NACKed.
+    let err = sht.fetch_periodic(&mut i2c, &mut delay).unwrap_err();
+    assert!(matches!(err, Error::I2c(MockError::Nack)), "{err:?}");
+
+    i2c.queue_read(&measurement(0x6666, 0x8000));
+    assert!(sht.fetch_periodic(&mut i2c, &mut delay).is_ok());
+    assert_eq!(
+        i2c.writes,
+        vec![
+            (DEFAULT_ADDR, vec![0x21, 0x26]),
+            (DEFAULT_ADDR, vec![0xE0, 0x00]),
+            (DEFAULT_ADDR, vec![0xE0, 0x00]),
+        ]
+    );
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use driver_tests::options::TempUnit;
+use driver_tests::telemetry::{telemetry_payload, Telemetry, TelemetryField};
+
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+fn telemetry() -> Telemetry {
+    let mut t = Telemetry::default();
+    t.set_max_age(TelemetryField::Co2, MAX_AGE);
+    t.set_max_age(TelemetryField::Temp, MAX_AGE);
+    t
+}
+
+#[test]
+fn fields_expire_after_max_age() {
+    let mut t = telemetry();
+    t.set_co2(812, 800);
+    t.set_temp(21.5);
+    let set_at = Instant::now();
+
+    let fresh = t.fresh(set_at + MAX_AGE / 2);
+    assert_eq!(fresh.co2_ppm, Some(812));
+    assert_eq!(fresh.co2_raw_ppm, Some(800));
+    assert_eq!(fresh.temp_c, Some(21.5));
+
+    let expired = t.fresh(set_at + MAX_AGE + Duration::from_secs(1));
+    assert_eq!(expired.co2_ppm, None);
+    assert_eq!(expired.co2_raw_ppm, None);
+    assert_eq!(expired.temp_c, None);
+    assert!(!expired.has_any());
+    // `fresh` works on a copy; the stored values are untouched.
+    assert_eq!(t.co2_ppm, Some(812));
+}
+
+#[test]
+fn fields_without_max_age_never_expire() {
+    let mut t = telemetry();
+    t.set_humidity(45);
+    t.set_battery(3.9, Some(80));
+
+    let later = t.fresh(Instant::now() + Duration::from_secs(24 * 3600));
+    assert_eq!(later.humidity_pct, Some(45));
+    assert_eq!(later.battery_v, Some(3.9));
+    assert_eq!(later.battery_pct, Some(80));
+}
+
+#[test]
+fn mark_stale_clears_only_that_field() {
+    let mut t = telemetry();
+    t.set_co2(812, 800);
+    t.set_temp(21.5);
+    t.set_battery(3.9, Some(80));
+
+    t.mark_stale(TelemetryField::Co2);
+    assert_eq!(t.co2_ppm, None);
+    assert_eq!(t.co2_raw_ppm, None);
+    assert_eq!(t.temp_c, Some(21.5));
+
+    t.mark_stale(TelemetryField::Battery);
+    assert_eq!(t.battery_v, None);
+    assert_eq!(t.battery_pct, None);
+    assert!(t.has_any());
+}
+
+#[test]
+fn field_is_fresh_again_after_new_value() {
+    let mut t = telemetry();
+    t.set_co2(812, 800);
+    t.mark_stale(TelemetryField::Co2);
+    assert!(!t.has_any());
+
+    t.set_co2(900, 880);
+    let set_at = Instant::now();
+    let fresh = t.fresh(set_at + MAX_AGE / 2);
+    assert_eq!(fresh.co2_ppm, Some(900));
+    assert_eq!(fresh.co2_raw_ppm, Some(880));
+
+    // The age restarts from the new value, not the first one.
+    assert_eq!(t.fresh(set_at + MAX_AGE + Duration::from_secs(1)).co2_ppm, None);
+}
+
+// Every field set, as after a normal read cycle.
+fn full() -> Telemetry {
+    let mut t = telemetry();
+    t.set_co2(812, 800);
+    t.set_temp(21.46);
+    t.set_humidity(45);
+    t.set_battery(3.9, Some(80));
+    t.uptime_s = Some(3600);
+    t.temp_hum_source = Some("sht31");
+    t.temp_range = Some((19.04, 23.5));
+    t.humidity_range = Some((40.4, 55.6));
+    t.mold_risk = Some(false);
+    t.co2_peak_ppm = Some(1210);
+    t.occupied = Some(true);
+    t.touch_ok = Some(true);
+    t
+}
+
+#[test]
+fn payload_with_all_fields() {
+    assert_eq!(
+        telemetry_payload(&full(), TempUnit::Celsius),
+        concat!(
+            r#"{"co2_ppm":812,"co2_raw_ppm":800,"temp_c":21.5,"temp":21.5,"humidity_pct":45,"#,
+            r#""battery_v":3.90,"battery_pct":80,"uptime_s":3600,"temp_hum_source":"sht31","#,
+            r#""temp_min":19.0,"temp_max":23.5,"hum_min":40,"hum_max":56,"mold_risk":false,"#,
+            r#""co2_peak_ppm":1210,"occupied":true,"touch_ok":true}"#
+        )
+    );
+}
+
+#[test]
+fn payload_converts_only_the_temp_key() {
+    let mut t = full();
+    t.set_temp(20.0);
+    t.temp_range = Some((0.0, 100.0));
+    let payload = telemetry_payload(&t, TempUnit::Fahrenheit);
+    assert!(payload.contains(r#""temp_c":20.0,"temp":68.0,"#), "{payload}");
+    assert!(payload.contains(r#""temp_min":0.0,"temp_max":100.0,"#), "{payload}");
+    assert!(payload.contains(r#""humidity_pct":45,"#), "{payload}");
+}
+
+#[test]
+fn payload_of_empty_telemetry_is_all_null() {
+    assert_eq!(
+        telemetry_payload(&Telemetry::default(), TempUnit::Celsius),
+        concat!(
+            r#"{"co2_ppm":null,"co2_raw_ppm":null,"temp_c":null,"temp":null,"humidity_pct":null,"#,
+            r#""battery_v":null,"battery_pct":null,"uptime_s":null,"temp_hum_source":null,"#,
+            r#""temp_min":null,"temp_max":null,"hum_min":null,"hum_max":null,"mold_risk":null,"#,
+            r#""co2_peak_ppm":null,"occupied":null,"touch_ok":null}"#
+        )
+    );
+}
+
+#[test]
+fn payload_publishes_stale_fields_as_null() {
+    let t = full();
+    let expired = t.fresh(Instant::now() + MAX_AGE + Duration::from_secs(1));
+    let payload = telemetry_payload(&expired, TempUnit::Celsius);
+
+    let stale = r#"{"co2_ppm":null,"co2_raw_ppm":null,"temp_c":null,"temp":null,"#;
+    assert!(payload.starts_with(stale), "{payload}");
+    // No max age set for these, so they keep their values.
+    assert!(payload.contains(r#""humidity_pct":45,"battery_v":3.90,"#), "{payload}");
+    assert!(payload.contains(r#""co2_peak_ppm":1210,"#), "{payload}");
+
+    let mut failed = full();
+    failed.mark_stale(TelemetryField::Battery);
+    let payload = telemetry_payload(&failed, TempUnit::Celsius);
+    assert!(payload.contains(r#""battery_v":null,"battery_pct":null,"#), "{payload}");
+    assert!(payload.contains(r#""co2_ppm":812,"#), "{payload}");
+}
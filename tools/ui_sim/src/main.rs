@@ -0,0 +1,273 @@
+// Host-side UI preview: renders the firmware's screens (src/display.rs, compiled
+// unchanged) with sample readings and writes one PNG per scenario, so layout
+// changes can be reviewed in a PR without flashing a board.
+//
+// Run via `scripts/ui_sim.sh [out_dir]`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+
+#[allow(dead_code)]
+#[path = "../../../src/display.rs"]
+mod display;
+#[allow(dead_code)]
+#[path = "../../../src/epaper_ui.rs"]
+mod epaper_ui;
+#[allow(dead_code)]
+#[path = "../../../src/history.rs"]
+mod history;
+#[allow(dead_code)]
+#[path = "../../../src/options.rs"]
+mod options;
+
+// Stand-ins for the hardware modules `display.rs` imports from. Keep in sync
+// with src/board.rs, src/st7789.rs and src/event_log.rs.
+mod board {
+    #[derive(Debug, Clone, Copy)]
+    pub struct SensorPresence {
+        pub co2: bool,
+        pub sht31: bool,
+    }
+}
+
+mod st7789 {
+    pub const LCD_VIEW_W: usize = 320;
+    pub const LCD_VIEW_H: usize = 172;
+}
+
+mod event_log {
+    #[derive(Debug, Clone)]
+    pub struct LogEntry {
+        pub uptime_s: u32,
+        pub level: log::Level,
+        pub msg: String,
+    }
+}
+
+use board::SensorPresence;
+use display::{
+    render_big_number, render_log_page, render_notice, render_ui_mock1, AirQuality, BigPage,
+    SensorsRead, UiState,
+};
+use epaper_ui::{render_epaper, MonoFrame, EPAPER_VIEW_H, EPAPER_VIEW_W};
+use event_log::LogEntry;
+use history::Co2Trend;
+use options::{TempUnit, ThemePreset};
+use st7789::{LCD_VIEW_H, LCD_VIEW_W};
+
+fn main() -> Result<()> {
+    let out_dir = PathBuf::from(
+        std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| "ui_sim_out".into()),
+    );
+    std::fs::create_dir_all(&out_dir)?;
+
+    let base = UiState {
+        temperature_c: Some(22.4),
+        humidity_pct: Some(46),
+        co2_ppm: Some(742),
+        co2_error: false,
+        calibrating: false,
+        battery_v: Some(3.96),
+        presence: SensorPresence {
+            co2: true,
+            sht31: true,
+        },
+        co2_alarm_ppm: 1500,
+        co2_trend: Co2Trend::Rising,
+        co2_quality: Some(AirQuality::Fair),
+        temp_range: Some((19.8, 23.1)),
+        humidity_range: Some((41.0, 52.0)),
+        mold_risk: false,
+        occupied: false,
+        co2_warmup: None,
+        co2_peak: Some(1240),
+        temp_unit: TempUnit::Celsius,
+        temp_decimals: 1,
+        sensors_read: SensorsRead {
+            co2: true,
+            temp_hum: true,
+        },
+        hold: false,
+    };
+    let scenarios = [
+        ("main", base),
+        (
+            "alarm",
+            UiState {
+                co2_ppm: Some(1720),
+                co2_quality: Some(AirQuality::Bad),
+                ..base
+            },
+        ),
+        (
+            "calibrating",
+            UiState {
+                calibrating: true,
+                ..base
+            },
+        ),
+        (
+            "warmup",
+            UiState {
+                co2_ppm: None,
+                co2_warmup: Some((Duration::from_secs(70), Duration::from_secs(180))),
+                ..base
+            },
+        ),
+        (
+            "mold_risk",
+            UiState {
+                humidity_pct: Some(78),
+                humidity_range: Some((71.0, 81.0)),
+                mold_risk: true,
+                ..base
+            },
+        ),
+        (
+            "occupied",
+            UiState {
+                co2_ppm: Some(960),
+                occupied: true,
+                ..base
+            },
+        ),
+        (
+            "fahrenheit",
+            UiState {
+                temp_unit: TempUnit::Fahrenheit,
+                temp_decimals: 0,
+                ..base
+            },
+        ),
+        (
+            "hold",
+            UiState {
+                hold: true,
+                ..base
+            },
+        ),
+        (
+            "initializing",
+            UiState {
+                temperature_c: None,
+                humidity_pct: None,
+                co2_ppm: None,
+                co2_quality: None,
+                temp_range: None,
+                humidity_range: None,
+                co2_peak: None,
+                sensors_read: SensorsRead::default(),
+                ..base
+            },
+        ),
+        (
+            "sensor_error",
+            UiState {
+                co2_ppm: None,
+                co2_error: true,
+                presence: SensorPresence {
+                    co2: true,
+                    sht31: false,
+                },
+                ..base
+            },
+        ),
+    ];
+
+    let mut frame = vec![Rgb565::BLACK; LCD_VIEW_W * LCD_VIEW_H];
+    for preset in [
+        ThemePreset::Dark,
+        ThemePreset::HighContrast,
+        ThemePreset::Amber,
+    ] {
+        for (name, ui) in &scenarios {
+            render_ui_mock1(&mut frame, ui, preset.theme())?;
+            write_png(
+                &out_dir.join(format!("{}_{}.png", name, preset.as_str())),
+                &frame,
+            )?;
+        }
+    }
+
+    for (name, page) in [
+        ("co2", BigPage::Co2),
+        ("temperature", BigPage::Temperature),
+        ("humidity", BigPage::Humidity),
+    ] {
+        render_big_number(&mut frame, &base, page, ThemePreset::Dark.theme())?;
+        write_png(&out_dir.join(format!("big_{}_dark.png", name)), &frame)?;
+    }
+    let (_, alarm) = &scenarios[1];
+    render_big_number(&mut frame, alarm, BigPage::Co2, ThemePreset::Dark.theme())?;
+    write_png(&out_dir.join("big_alarm_dark.png"), &frame)?;
+
+    let log = [
+        (12, log::Level::Info, "Reset: power-on"),
+        (15, log::Level::Info, "MQTT reconnected"),
+        (3605, log::Level::Warn, "Mold risk: sustained high humidity"),
+        (4210, log::Level::Error, "MH-Z19B read: timeout"),
+    ]
+    .map(|(uptime_s, level, msg)| LogEntry {
+        uptime_s,
+        level,
+        msg: msg.to_string(),
+    });
+    render_log_page(&mut frame, &log, ThemePreset::Dark.theme())?;
+    write_png(&out_dir.join("log_dark.png"), &frame)?;
+
+    render_notice(&mut frame, "LOW BATTERY", "Shutting down", ThemePreset::Dark.theme())?;
+    write_png(&out_dir.join("low_battery_dark.png"), &frame)?;
+
+    let mut mono = MonoFrame::new();
+    for (name, ui) in &scenarios {
+        render_epaper(&mut mono, ui)?;
+        write_mono_png(&out_dir.join(format!("epaper_{}.png", name)), &mono)?;
+    }
+
+    println!("Wrote previews to {}", out_dir.display());
+    Ok(())
+}
+
+// Expand RGB565 to 8-bit RGB (replicating the high bits into the low ones).
+fn write_png(path: &Path, frame: &[Rgb565]) -> Result<()> {
+    let mut rgb = Vec::with_capacity(frame.len() * 3);
+    for px in frame {
+        rgb.push((px.r() << 3) | (px.r() >> 2));
+        rgb.push((px.g() << 2) | (px.g() >> 4));
+        rgb.push((px.b() << 3) | (px.b() >> 2));
+    }
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        LCD_VIEW_W as u32,
+        LCD_VIEW_H as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&rgb)?;
+    Ok(())
+}
+
+fn write_mono_png(path: &Path, frame: &MonoFrame) -> Result<()> {
+    let mut gray = Vec::with_capacity(EPAPER_VIEW_W * EPAPER_VIEW_H);
+    for y in 0..EPAPER_VIEW_H {
+        for x in 0..EPAPER_VIEW_W {
+            gray.push(if frame.is_black(x, y) { 0 } else { 255 });
+        }
+    }
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        EPAPER_VIEW_W as u32,
+        EPAPER_VIEW_H as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&gray)?;
+    Ok(())
+}